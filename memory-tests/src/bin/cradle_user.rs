@@ -1,13 +1,31 @@
 use cradle::prelude::*;
+use std::{fs::File, io::Write};
 
 fn main() {
     let mut args = std::env::args();
     let stream_type: String = args.nth(1).unwrap();
     let bytes: usize = args.next().unwrap().parse().unwrap();
     eprintln!("consuming {} KiB", bytes / 2_usize.pow(10));
-    run!(
-        "./target/release/produce_bytes",
-        stream_type,
-        bytes.to_string()
-    );
+    if stream_type == "stdin" {
+        let stdin_file = "./target/release/stdin_file";
+        write_file(stdin_file, bytes);
+        run!("cat", StdinFile(stdin_file));
+        std::fs::remove_file(stdin_file).unwrap();
+    } else {
+        run!(
+            "./target/release/produce_bytes",
+            stream_type,
+            bytes.to_string()
+        );
+    }
+}
+
+fn write_file(path: &str, mut bytes: usize) {
+    let buffer = &[b'x'; 1024];
+    let mut file = File::create(path).unwrap();
+    while bytes > 0 {
+        let chunk_size = bytes.min(1024);
+        file.write_all(&buffer[..chunk_size]).unwrap();
+        bytes -= chunk_size;
+    }
 }
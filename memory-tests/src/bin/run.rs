@@ -8,6 +8,7 @@ fn from_mib(mebibytes: usize) -> usize {
 fn main() -> Result<()> {
     test("stdout")?;
     test("stderr")?;
+    test("stdin")?;
     Ok(())
 }
 
@@ -1,27 +1,99 @@
-//! An internal module used for the outputs of child processes.
+//! The [`ChildOutput`] type that holds all the outputs of a child process.
 
+#[cfg(unix)]
+use crate::pty::PseudoTerminal;
 use crate::{
-    collected_output::Waiter, config::Config, context::Context, error::Error, output::Output,
+    collected_output::{CollectedOutput, OutputStream, Waiter},
+    config::{Config, OutputFile},
+    context::Context,
+    error::{io_error_with_path, Error},
+    input::Timeout,
+    output::Output,
 };
 use std::{
+    env,
     ffi::OsString,
-    io::Write,
-    process::{Command, ExitStatus, Stdio},
+    fs::{self, File},
+    io::{self, Seek, SeekFrom, Write},
+    process::{Child, ChildStdout, Command, ExitStatus, Stdio},
+    thread,
+    time::{Duration, Instant},
 };
 
-/// Internal type to capture all the outputs of a child process.
-/// Usually you don't have to use this type directly.
+/// All the outputs of a child process.
+/// Usually you don't have to use this type directly,
+/// it's passed to [`OnExit`](crate::OnExit) callbacks.
 ///
 /// See also the documentation for
 /// [Custom `Output` impls](crate::Output#custom-output-impls).
 #[derive(Clone, Debug)]
 pub struct ChildOutput {
     pub(crate) stdout: Option<Vec<u8>>,
+    pub(crate) stdout_truncated: bool,
     pub(crate) stderr: Option<Vec<u8>>,
+    pub(crate) combined: Option<Vec<u8>>,
     pub(crate) exit_status: ExitStatus,
+    pub(crate) duration: Duration,
 }
 
+/// A spawned child process of a pipeline,
+/// together with the threads that relay its standard streams.
+pub(crate) type RunningStage = (Child, Waiter);
+
+/// The configs of all stages of a pipeline, the running child processes,
+/// and the `stdout` of the last stage, if it's not relayed.
+pub(crate) type SpawnedPipeline = (Vec<Config>, Vec<RunningStage>, Option<ChildStdout>);
+
 impl ChildOutput {
+    /// The exit status of the child process.
+    /// For a [`Pipe`](crate::Pipe), this is the exit status of the last command.
+    pub fn exit_status(&self) -> ExitStatus {
+        self.exit_status
+    }
+
+    /// How long the child process was running.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// The captured `stdout` of the child process,
+    /// or `None` if `stdout` wasn't captured.
+    pub fn stdout(&self) -> Option<&[u8]> {
+        self.stdout.as_deref()
+    }
+
+    /// The captured `stderr` of the child process,
+    /// or `None` if `stderr` wasn't captured.
+    pub fn stderr(&self) -> Option<&[u8]> {
+        self.stderr.as_deref()
+    }
+
+    /// The output for commands that are skipped with [`When`](crate::When):
+    /// empty captured output and a successful exit status.
+    fn skipped(config: &Config) -> Self {
+        let empty = |captured: bool| if captured { Some(Vec::new()) } else { None };
+        ChildOutput {
+            stdout: empty(config.capture_stdout),
+            stdout_truncated: false,
+            stderr: empty(config.capture_stderr),
+            combined: empty(config.capture_combined_output),
+            exit_status: Self::success_exit_status(),
+            duration: Duration::from_secs(0),
+        }
+    }
+
+    #[cfg(unix)]
+    fn success_exit_status() -> ExitStatus {
+        use std::os::unix::process::ExitStatusExt;
+        ExitStatus::from_raw(0)
+    }
+
+    #[cfg(windows)]
+    fn success_exit_status() -> ExitStatus {
+        use std::os::windows::process::ExitStatusExt;
+        ExitStatus::from_raw(0)
+    }
+
     pub(crate) fn run_child_process_output<Stdout, Stderr, T>(
         context: Context<Stdout, Stderr>,
         mut config: Config,
@@ -32,69 +104,718 @@ impl ChildOutput {
         T: Output,
     {
         <T as Output>::configure(&mut config);
-        let child_output = ChildOutput::run_child_process(context, &config)?;
+        let child_output = ChildOutput::run_child_process_with_retries(context, &config)?;
         T::from_child_output(&config, &child_output)
     }
 
+    fn run_child_process_with_retries<Stdout, Stderr>(
+        context: Context<Stdout, Stderr>,
+        config: &Config,
+    ) -> Result<Self, Error>
+    where
+        Stdout: Write + Clone + Send + 'static,
+        Stderr: Write + Clone + Send + 'static,
+    {
+        if let Some(error) = config.take_input_error() {
+            return Err(error);
+        }
+        if config.is_skipped() {
+            return Ok(ChildOutput::skipped(config));
+        }
+        let retry = match config.options.retry {
+            Some(retry) => retry,
+            None => return ChildOutput::run_child_process(context, config),
+        };
+        let stdin_handle = Self::pipeline_stages(config)[0].options.stdin_handles.pop();
+        // Files given with `StdinFromFile` share their offset with the child processes,
+        // so they're rewound before every attempt. Pipes can't be rewound.
+        let stdin_position = stdin_handle
+            .as_ref()
+            .and_then(|file| Self::file_position(file));
+        let mut attempt = 1;
+        loop {
+            match ChildOutput::run_child_process(context.clone(), config) {
+                Err(Error::NonZeroExitCode { .. }) | Err(Error::CommandIoError { .. })
+                    if attempt < retry.attempts =>
+                {
+                    thread::sleep(retry.delay);
+                    attempt += 1;
+                    if let (Some(file), Some(position)) = (&stdin_handle, stdin_position) {
+                        (&**file)
+                            .seek(SeekFrom::Start(position))
+                            .map_err(|error| Error::command_io_error(config, error))?;
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// The current offset of the given file, or `None` if it's not seekable.
+    // `Seek::stream_position` is not available in all supported rust versions.
+    #[rustversion::attr(since(1.67), allow(clippy::seek_from_current))]
+    fn file_position(mut file: &File) -> Option<u64> {
+        file.seek(SeekFrom::Current(0)).ok()
+    }
+
     fn run_child_process<Stdout, Stderr>(
-        mut context: Context<Stdout, Stderr>,
+        context: Context<Stdout, Stderr>,
         config: &Config,
     ) -> Result<Self, Error>
     where
         Stdout: Write + Clone + Send + 'static,
         Stderr: Write + Clone + Send + 'static,
     {
-        let (executable, arguments) = Self::parse_input(config.arguments.clone())?;
-        if config.log_command {
+        let start = Instant::now();
+        let (stages, mut running_stages, _) = Self::spawn_pipeline(context, config)?;
+        let timed_out = match config.options.timeout {
+            Some(timeout) => Self::wait_with_timeout(config, &mut running_stages, start, timeout)?,
+            None => false,
+        };
+        // Output streams can be kept open by other processes, e.g. by grandchildren.
+        // So with a timeout, they're not waited for indefinitely either.
+        let deadline = config
+            .options
+            .timeout
+            .map(|timeout| start + timeout.after + timeout.grace);
+        let mut joined_stages = Vec::new();
+        for (mut child, waiter) in running_stages {
+            // The output is collected before waiting for the child process,
+            // so that child processes that produce too much output can be killed.
+            let collected_output = waiter.join(config, deadline);
+            match collected_output {
+                Err(Error::OutputTooLarge { .. }) | Err(Error::Timeout { .. }) => {
+                    let _ = child.kill();
+                }
+                _ => {}
+            }
+            let exit_status = child
+                .wait()
+                .map_err(|error| Error::command_io_error(config, error))?;
+            joined_stages.push((exit_status, collected_output));
+        }
+        // Errors from collecting the output are returned after calling the `OnExit` callbacks.
+        let mut collection_error = None;
+        let mut finished_stages = Vec::new();
+        for (exit_status, collected_output) in joined_stages {
+            let collected_output = match collected_output {
+                Ok(collected_output) => collected_output,
+                Err(error) => {
+                    collection_error = collection_error.or(Some(error));
+                    CollectedOutput::default()
+                }
+            };
+            finished_stages.push((exit_status, collected_output));
+        }
+        let duration = start.elapsed();
+        let (exit_status, collected_output) = finished_stages
+            .pop()
+            .ok_or_else(|| Error::internal("no pipeline stages", config))?;
+        let child_output = Self {
+            stdout: collected_output.stdout,
+            stdout_truncated: collected_output.stdout_truncated,
+            stderr: collected_output.stderr,
+            combined: collected_output.combined,
+            exit_status,
+            duration,
+        };
+        for callback in &config.options.on_exit {
+            (*callback.lock())(&child_output);
+        }
+        if let Some(error) = collection_error {
+            return Err(error);
+        }
+        if let (true, Some(timeout)) = (timed_out, config.options.timeout) {
+            return Err(Error::Timeout {
+                full_command: config.full_command(),
+                timeout: timeout.after,
+            });
+        }
+        for (stage, (exit_status, collected_output)) in stages.iter().zip(&finished_stages) {
+            Self::check_exit_status(config, stage, *exit_status, &collected_output.stderr)?;
+        }
+        let last_stage = stages
+            .last()
+            .ok_or_else(|| Error::internal("no pipeline stages", config))?;
+        Self::check_exit_status(config, last_stage, exit_status, &child_output.stderr)?;
+        Ok(child_output)
+    }
+
+    /// Waits for all stages of a pipeline to exit, until the given [`Timeout`] expires.
+    /// Then the remaining child processes are terminated.
+    /// Returns whether the timeout expired.
+    fn wait_with_timeout(
+        config: &Config,
+        running_stages: &mut [RunningStage],
+        start: Instant,
+        timeout: Timeout,
+    ) -> Result<bool, Error> {
+        if Self::wait_until(config, running_stages, start + timeout.after)? {
+            return Ok(false);
+        }
+        for (child, _) in running_stages.iter_mut() {
+            Self::terminate(config, child)?;
+        }
+        if !Self::wait_until(config, running_stages, Instant::now() + timeout.grace)? {
+            for (child, _) in running_stages.iter_mut() {
+                let _ = child.kill();
+            }
+        }
+        Ok(true)
+    }
+
+    /// Polls the child processes until they all exited or until the deadline.
+    /// Returns whether they all exited.
+    fn wait_until(
+        config: &Config,
+        running_stages: &mut [RunningStage],
+        deadline: Instant,
+    ) -> Result<bool, Error> {
+        loop {
+            let mut all_exited = true;
+            for (child, _) in running_stages.iter_mut() {
+                let exit_status = child
+                    .try_wait()
+                    .map_err(|error| Error::command_io_error(config, error))?;
+                all_exited &= exit_status.is_some();
+            }
+            let now = Instant::now();
+            if all_exited || now >= deadline {
+                return Ok(all_exited);
+            }
+            thread::sleep(std::cmp::min(deadline - now, Duration::from_millis(10)));
+        }
+    }
+
+    /// Asks the child process to exit by sending `SIGTERM`.
+    #[cfg(unix)]
+    fn terminate(config: &Config, child: &mut Child) -> Result<(), Error> {
+        // The child hasn't been reaped yet, if `try_wait` returns `None`,
+        // so its process id can't have been reused.
+        let is_running = child
+            .try_wait()
+            .map_err(|error| Error::command_io_error(config, error))?
+            .is_none();
+        if is_running && unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGTERM) } != 0 {
+            return Err(Error::command_io_error(config, io::Error::last_os_error()));
+        }
+        Ok(())
+    }
+
+    /// Kills the child process with `TerminateProcess`,
+    /// since windows doesn't have a way to ask a single process to exit.
+    #[cfg(not(unix))]
+    fn terminate(_config: &Config, child: &mut Child) -> Result<(), Error> {
+        let _ = child.kill();
+        Ok(())
+    }
+
+    /// Spawns all stages of a pipeline, without waiting for them to finish.
+    pub(crate) fn spawn_pipeline<Stdout, Stderr>(
+        mut context: Context<Stdout, Stderr>,
+        config: &Config,
+    ) -> Result<SpawnedPipeline, Error>
+    where
+        Stdout: Write + Clone + Send + 'static,
+        Stderr: Write + Clone + Send + 'static,
+    {
+        if let Some(error) = config.take_input_error() {
+            return Err(error);
+        }
+        let stages = Self::pipeline_stages(config);
+        let stdin_sources = usize::from(!stages[0].options.stdin.is_empty())
+            + stages[0].options.stdin_handles.len()
+            + usize::from(stages[0].stdin_null);
+        if stdin_sources > 1 {
+            return Err(Error::MultipleStdinSources {
+                full_command: config.full_command(),
+            });
+        }
+        if stages.iter().any(|stage| stage.options.log_command_verbose) {
+            writeln!(context.stderr, "+ {}", config.verbose_command())
+                .map_err(|error| Error::command_io_error(config, error))?;
+        } else if stages.iter().any(|stage| stage.log_command) {
             writeln!(context.stderr, "+ {}", config.full_command())
                 .map_err(|error| Error::command_io_error(config, error))?;
         }
+        for stage in stages.iter() {
+            for callback in &stage.options.log_command_to {
+                (*callback.lock())(&config.full_command());
+            }
+        }
+        let mut running_stages: Vec<RunningStage> = Vec::new();
+        let mut previous_stdout = None;
+        for stage in stages.iter() {
+            let is_last_stage = running_stages.len() == stages.len() - 1;
+            let stdin = match (previous_stdout.take(), stage.options.stdin_handles.first()) {
+                (Some(previous_stdout), _) => Stdio::from(previous_stdout),
+                (None, Some(file)) => Stdio::from(
+                    file.try_clone()
+                        .map_err(|error| Error::command_io_error(config, error))?,
+                ),
+                (None, None) if stage.stdin_null => Stdio::null(),
+                (None, None) if stage.interactive && stage.options.stdin.is_empty() => {
+                    Stdio::inherit()
+                }
+                (None, None) => Stdio::piped(),
+            };
+            match Self::spawn_stage(&context, config, stage, stdin, is_last_stage) {
+                Ok((child, waiter, stdout)) => {
+                    previous_stdout = stdout;
+                    running_stages.push((child, waiter));
+                }
+                Err(error) => {
+                    for (mut child, _) in running_stages {
+                        let _ = child.kill();
+                        let _ = child.wait();
+                    }
+                    return Err(error);
+                }
+            }
+        }
+        Ok((stages, running_stages, previous_stdout))
+    }
+
+    /// Flattens a pipeline into its stages, in the order in which the data flows.
+    /// A command that is not part of a pipeline results in a single stage.
+    fn pipeline_stages(config: &Config) -> Vec<Config> {
+        let mut stages = Vec::new();
+        let mut stage = Some(config.clone());
+        while let Some(mut current) = stage {
+            stage = current.piped_from.take().map(|source| *source);
+            stages.push(current);
+        }
+        stages.reverse();
+        // Only the first stage reads from the parent's stdin sources,
+        // the stdin of the other stages is connected to the previous stage's stdout.
+        let mut stdin = Vec::new();
+        for stage in stages.iter_mut() {
+            stdin.append(&mut stage.options.stdin);
+        }
+        stages[0].options.stdin = stdin;
+        let mut stdin_handles = Vec::new();
+        for stage in stages.iter_mut() {
+            stdin_handles.append(&mut stage.options.stdin_handles);
+        }
+        stages[0].options.stdin_handles = stdin_handles;
+        let mut stdin_null = false;
+        for stage in stages.iter_mut() {
+            stdin_null |= std::mem::take(&mut stage.stdin_null);
+        }
+        stages[0].stdin_null = stdin_null;
+        // `Quiet`, `Interactive`, `OkExitCodes` and `Elevated` given for the whole pipeline
+        // apply to all stages.
+        let last_stage = stages.len() - 1;
+        for stage in stages[..last_stage].iter_mut() {
+            stage.quiet |= config.quiet;
+            stage.interactive |= config.interactive;
+            stage
+                .options
+                .ok_exit_codes
+                .extend(&config.options.ok_exit_codes);
+            if stage.options.elevation.is_none() {
+                stage.options.elevation = config.options.elevation.clone();
+            }
+        }
+        stages
+    }
+
+    fn spawn_stage<Stdout, Stderr>(
+        context: &Context<Stdout, Stderr>,
+        config: &Config,
+        stage: &Config,
+        stdin: Stdio,
+        is_last_stage: bool,
+    ) -> Result<(Child, Waiter, Option<ChildStdout>), Error>
+    where
+        Stdout: Write + Clone + Send + 'static,
+        Stderr: Write + Clone + Send + 'static,
+    {
+        if stage.executables_given > 1 {
+            return Err(Error::MultipleExecutablesGiven {
+                full_command: config.full_command(),
+            });
+        }
+        let (executable, arguments) = Self::parse_input(stage.spawned_arguments())?;
+        // The working directory is checked before opening output files,
+        // so that they aren't truncated when the command can't be run.
+        if let Some(working_directory) = &stage.options.working_directory {
+            if stage.options.create_working_directory {
+                fs::create_dir_all(working_directory).map_err(|error| {
+                    Error::command_io_error(config, io_error_with_path(working_directory, error))
+                })?;
+            }
+            if !working_directory.is_dir() {
+                return Err(Error::WorkingDirectoryNotFound {
+                    full_command: config.full_command(),
+                    path: working_directory.clone(),
+                });
+            }
+        }
+        let stdout_file = if is_last_stage {
+            Self::open_output_file(config, &stage.options.stdout_file)?
+        } else {
+            None
+        };
+        let stderr_file = Self::open_output_file(config, &stage.options.stderr_file)?;
         let mut command = Command::new(&executable);
         command.args(arguments);
-        for (key, value) in &config.added_environment_variables {
+        if stage.options.clear_env {
+            command.env_clear();
+        }
+        for key in &stage.options.removed_environment_variables {
+            command.env_remove(key);
+        }
+        for (key, value) in &stage.options.added_environment_variables {
             command.env(key, value);
         }
+        if let Some(path) = Self::path_with_prefix(config, stage)? {
+            command.env("PATH", path);
+        }
+        let inherit_stdout = stage.interactive && is_last_stage;
+        if inherit_stdout
+            && (stage.capture_stdout
+                || stage.capture_stderr
+                || stage.capture_combined_output
+                || stage.options.stream_stdout)
+        {
+            return Err(Error::InteractiveOutputCaptured {
+                full_command: config.full_command(),
+            });
+        }
         command
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
-        if let Some(working_directory) = &config.working_directory {
+            .stdin(stdin)
+            .stdout(if inherit_stdout {
+                Stdio::inherit()
+            } else {
+                Stdio::piped()
+            })
+            .stderr(if stage.interactive {
+                Stdio::inherit()
+            } else {
+                Stdio::piped()
+            });
+        #[cfg(unix)]
+        let (mut child_stdout, mut child_stderr) =
+            Self::connect_pseudo_terminals(config, &mut command, stage, is_last_stage)?;
+        #[cfg(not(unix))]
+        let (mut child_stdout, mut child_stderr): (
+            Option<OutputStream>,
+            Option<OutputStream>,
+        ) = (None, None);
+        if let Some(working_directory) = &stage.options.working_directory {
             command.current_dir(working_directory);
         }
+        #[cfg(unix)]
+        Self::set_process_group(&mut command, stage);
+        #[cfg(unix)]
+        Self::set_arg0(&mut command, stage);
+        #[cfg(unix)]
+        Self::set_umask(&mut command, stage);
+        #[cfg(unix)]
+        Self::set_niceness(&mut command, stage);
+        #[cfg(unix)]
+        Self::set_cpu_time_limit(&mut command, stage);
+        #[cfg(unix)]
+        Self::set_memory_limit(&mut command, stage);
+        #[cfg(unix)]
+        Self::set_user_and_group(&mut command, stage);
+        #[cfg(unix)]
+        Self::close_fds(&mut command, stage);
+        #[cfg(windows)]
+        Self::set_creation_flags(&mut command, stage);
+        for callback in &stage.options.configure_command {
+            (*callback.lock())(&mut command);
+        }
         let mut child = command.spawn().map_err(|source| {
-            if source.kind() == std::io::ErrorKind::NotFound {
+            if source.kind() == io::ErrorKind::NotFound {
                 Error::FileNotFound { executable, source }
             } else {
                 Error::command_io_error(config, source)
             }
         })?;
+        for callback in &stage.options.on_spawn {
+            (*callback.lock())(child.id());
+        }
+        let next_stage_stdin = if is_last_stage && !stage.options.stream_stdout {
+            if let Some(stdout) = child.stdout.take() {
+                child_stdout = Some(Box::new(stdout));
+            }
+            None
+        } else {
+            child.stdout.take()
+        };
+        if let Some(stderr) = child.stderr.take() {
+            child_stderr = Some(Box::new(stderr));
+        }
         let waiter = Waiter::spawn_standard_stream_relaying(
-            &context,
-            config,
-            child.stdin.take().expect("child process should have stdin"),
-            child
-                .stdout
-                .take()
-                .expect("child process should have stdout"),
-            child
-                .stderr
-                .take()
-                .expect("child process should have stderr"),
+            context,
+            stage,
+            child.stdin.take(),
+            child_stdout,
+            child_stderr,
+            stdout_file,
+            stderr_file,
         );
-        let exit_status = child
-            .wait()
-            .map_err(|error| Error::command_io_error(config, error))?;
-        let collected_output = waiter
-            .join()
-            .map_err(|error| Error::command_io_error(config, error))?;
-        Self::check_exit_status(config, exit_status)?;
-        Ok(Self {
-            stdout: collected_output.stdout,
-            stderr: collected_output.stderr,
-            exit_status,
+        Ok((child, waiter, next_stage_stdin))
+    }
+
+    /// Connects the child's `stdout` and `stderr` to pseudo-terminals,
+    /// if configured with [`Pty`](crate::Pty).
+    /// Returns the master sides of the pseudo-terminals to read the output from.
+    /// `stdout` is only connected for the last stage of a pipeline.
+    #[cfg(unix)]
+    fn connect_pseudo_terminals(
+        config: &Config,
+        command: &mut Command,
+        stage: &Config,
+        is_last_stage: bool,
+    ) -> Result<(Option<OutputStream>, Option<OutputStream>), Error> {
+        if !stage.options.pty {
+            return Ok((None, None));
+        }
+        let open =
+            || PseudoTerminal::open().map_err(|error| Error::command_io_error(config, error));
+        let stdout = if is_last_stage && !stage.options.stream_stdout {
+            let PseudoTerminal { master, slave } = open()?;
+            command.stdout(slave);
+            Some(Box::new(master) as OutputStream)
+        } else {
+            None
+        };
+        let PseudoTerminal { master, slave } = open()?;
+        command.stderr(slave);
+        Ok((stdout, Some(Box::new(master))))
+    }
+
+    /// Computes the child's `PATH` with the directories given by
+    /// [`WithPath`](crate::WithPath) prepended.
+    pub(crate) fn path_with_prefix(
+        config: &Config,
+        stage: &Config,
+    ) -> Result<Option<OsString>, Error> {
+        if stage.options.path_prefix.is_empty() {
+            return Ok(None);
+        }
+        let is_path = |key: &OsString| {
+            if cfg!(windows) {
+                key.to_string_lossy().eq_ignore_ascii_case("PATH")
+            } else {
+                key == "PATH"
+            }
+        };
+        let child_path = match stage
+            .options
+            .added_environment_variables
+            .iter()
+            .rev()
+            .find(|(key, _)| is_path(key))
+        {
+            Some((_, value)) => Some(value.clone()),
+            None if stage.options.clear_env
+                || stage
+                    .options
+                    .removed_environment_variables
+                    .iter()
+                    .any(is_path) =>
+            {
+                None
+            }
+            None => env::var_os("PATH"),
+        };
+        let mut directories = stage.options.path_prefix.clone();
+        if let Some(child_path) = child_path {
+            directories.extend(env::split_paths(&child_path));
+        }
+        env::join_paths(directories).map(Some).map_err(|error| {
+            Error::command_io_error(config, io::Error::new(io::ErrorKind::InvalidInput, error))
         })
     }
 
+    fn open_output_file(
+        config: &Config,
+        output_file: &Option<OutputFile>,
+    ) -> Result<Option<File>, Error> {
+        match output_file {
+            Some(output_file) => Ok(Some(output_file.open().map_err(|error| {
+                Error::command_io_error(config, io_error_with_path(&output_file.path, error))
+            })?)),
+            None => Ok(None),
+        }
+    }
+
+    #[cfg(unix)]
+    #[rustversion::since(1.64)]
+    fn set_process_group(command: &mut Command, config: &Config) {
+        use std::os::unix::process::CommandExt;
+        if config.options.process_group {
+            command.process_group(0);
+        }
+    }
+
+    #[cfg(unix)]
+    #[rustversion::before(1.64)]
+    fn set_process_group(_command: &mut Command, _config: &Config) {}
+
+    #[cfg(unix)]
+    #[rustversion::since(1.45)]
+    fn set_arg0(command: &mut Command, config: &Config) {
+        use std::os::unix::process::CommandExt;
+        if let Some(arg0) = &config.options.arg0 {
+            command.arg0(arg0);
+        }
+    }
+
+    #[cfg(unix)]
+    #[rustversion::before(1.45)]
+    fn set_arg0(_command: &mut Command, _config: &Config) {}
+
+    #[cfg(unix)]
+    fn set_umask(command: &mut Command, config: &Config) {
+        use std::os::unix::process::CommandExt;
+        if let Some(umask) = config.options.umask {
+            // `umask` is async-signal-safe, so it's safe to call after `fork`.
+            unsafe {
+                command.pre_exec(move || {
+                    libc::umask(umask as libc::mode_t);
+                    Ok(())
+                });
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    fn set_niceness(command: &mut Command, config: &Config) {
+        use std::os::unix::process::CommandExt;
+        if let Some(niceness) = config.options.niceness {
+            // `nice` is async-signal-safe, so it's safe to call after `fork`.
+            unsafe {
+                command.pre_exec(move || {
+                    // `nice` can legitimately return -1,
+                    // so errors are detected through `errno`. It only fails with `EPERM`.
+                    // `errno` is cleared first, since it may contain a stale value
+                    // inherited from the parent's thread.
+                    Self::clear_errno();
+                    if libc::nice(niceness) == -1 {
+                        let error = std::io::Error::last_os_error();
+                        if error.raw_os_error() == Some(libc::EPERM) {
+                            return Err(error);
+                        }
+                    }
+                    Ok(())
+                });
+            }
+        }
+    }
+
+    /// Sets `errno` to 0.
+    #[cfg(unix)]
+    unsafe fn clear_errno() {
+        #[cfg(any(target_os = "linux", target_os = "emscripten", target_os = "redox"))]
+        {
+            *libc::__errno_location() = 0;
+        }
+        #[cfg(any(target_os = "android", target_os = "netbsd", target_os = "openbsd"))]
+        {
+            *libc::__errno() = 0;
+        }
+        #[cfg(any(
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "freebsd",
+            target_os = "dragonfly"
+        ))]
+        {
+            *libc::__error() = 0;
+        }
+        #[cfg(any(target_os = "solaris", target_os = "illumos"))]
+        {
+            *libc::___errno() = 0;
+        }
+    }
+
+    #[cfg(unix)]
+    fn set_cpu_time_limit(command: &mut Command, config: &Config) {
+        use std::os::unix::process::CommandExt;
+        if let Some(cpu_time_limit) = config.options.cpu_time_limit {
+            let seconds = cpu_time_limit.as_secs() + u64::from(cpu_time_limit.subsec_nanos() > 0);
+            // With equal soft and hard limits, linux sends `SIGKILL` instead of `SIGXCPU`.
+            let limit = libc::rlimit {
+                rlim_cur: seconds as libc::rlim_t,
+                rlim_max: seconds.saturating_add(1) as libc::rlim_t,
+            };
+            // `setrlimit` is async-signal-safe, so it's safe to call after `fork`.
+            unsafe {
+                command.pre_exec(move || {
+                    if libc::setrlimit(libc::RLIMIT_CPU, &limit) != 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    fn set_memory_limit(command: &mut Command, config: &Config) {
+        use std::os::unix::process::CommandExt;
+        if let Some(memory_limit) = config.options.memory_limit {
+            let limit = libc::rlimit {
+                rlim_cur: memory_limit as libc::rlim_t,
+                rlim_max: memory_limit as libc::rlim_t,
+            };
+            // `setrlimit` is async-signal-safe, so it's safe to call after `fork`.
+            unsafe {
+                command.pre_exec(move || {
+                    if libc::setrlimit(libc::RLIMIT_AS, &limit) != 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    fn set_user_and_group(command: &mut Command, config: &Config) {
+        use std::os::unix::process::CommandExt;
+        // The group has to be set first,
+        // since setting the user may drop the privileges to set the group.
+        if let Some(gid) = config.options.gid {
+            command.gid(gid);
+        }
+        if let Some(uid) = config.options.uid {
+            command.uid(uid);
+        }
+    }
+
+    #[cfg(unix)]
+    fn close_fds(command: &mut Command, config: &Config) {
+        use std::os::unix::process::CommandExt;
+        if config.options.closed_fds.is_empty() {
+            return;
+        }
+        let closed_fds = config.options.closed_fds.clone();
+        // `close` is async-signal-safe, so it's safe to call after `fork`.
+        // Errors (e.g. `EBADF` for file descriptors that are not open) are ignored.
+        unsafe {
+            command.pre_exec(move || {
+                for fd in &closed_fds {
+                    libc::close(*fd);
+                }
+                Ok(())
+            });
+        }
+    }
+
+    #[cfg(windows)]
+    fn set_creation_flags(command: &mut Command, config: &Config) {
+        use std::os::windows::process::CommandExt;
+        if config.options.creation_flags != 0 {
+            command.creation_flags(config.options.creation_flags);
+        }
+    }
+
     fn parse_input(
         input: Vec<OsString>,
     ) -> Result<(OsString, impl Iterator<Item = OsString>), Error> {
@@ -107,11 +828,23 @@ impl ChildOutput {
         }
     }
 
-    fn check_exit_status(config: &Config, exit_status: ExitStatus) -> Result<(), Error> {
-        if config.error_on_non_zero_exit_code && !exit_status.success() {
+    fn check_exit_status(
+        config: &Config,
+        stage: &Config,
+        exit_status: ExitStatus,
+        captured_stderr: &Option<Vec<u8>>,
+    ) -> Result<(), Error> {
+        let is_ok = match exit_status.code() {
+            Some(code) => code == 0 || stage.options.ok_exit_codes.contains(&code),
+            None => false,
+        };
+        if config.error_on_non_zero_exit_code && !is_ok {
             Err(Error::NonZeroExitCode {
                 full_command: config.full_command(),
                 exit_status,
+                captured_stderr: captured_stderr
+                    .as_ref()
+                    .map(|stderr| String::from_utf8_lossy(stderr).into_owned()),
             })
         } else {
             Ok(())
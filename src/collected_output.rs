@@ -1,99 +1,692 @@
-use crate::{config::Config, context::Context};
+use crate::{
+    config::{Config, OutputFile, SharedWriter, StdinSource, StrCallback},
+    context::Context,
+    error::{io_error_with_path, Error},
+};
 use std::{
+    collections::VecDeque,
+    fs::File,
     io::{self, Read, Write},
-    process::{ChildStderr, ChildStdin, ChildStdout},
+    process::ChildStdin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
     thread::{self, JoinHandle},
+    time::Instant,
 };
 
+/// An output stream of a child process, usually a pipe.
+pub(crate) type OutputStream = Box<dyn Read + Send>;
+
 #[derive(Debug)]
 pub(crate) struct Waiter {
     stdin: Option<JoinHandle<io::Result<()>>>,
-    stdout: JoinHandle<io::Result<Option<Vec<u8>>>>,
-    stderr: JoinHandle<io::Result<Option<Vec<u8>>>>,
+    stdout: Option<JoinHandle<Result<Option<Collected>, StreamError>>>,
+    stderr: Option<JoinHandle<Result<Option<Collected>, StreamError>>>,
+    combined: Option<Arc<Mutex<Vec<u8>>>>,
+    redirected: Option<Redirected>,
+    finished_threads: Receiver<Option<usize>>,
+}
+
+/// State for child processes whose `stderr` is redirected into their `stdout`.
+#[derive(Debug)]
+struct Redirected {
+    stdout: Option<Arc<Mutex<Vec<u8>>>>,
+    stdout_tail: Option<usize>,
+    capture_stderr: bool,
+}
+
+/// Buffer for the captured output of a single stream.
+enum Collected {
+    All(Vec<u8>),
+    Tail(TailBuffer),
+}
+
+impl Collected {
+    fn new(tail: Option<usize>) -> Self {
+        match tail {
+            Some(max_lines) => Collected::Tail(TailBuffer::new(max_lines)),
+            None => Collected::All(Vec::new()),
+        }
+    }
+
+    fn when_captured(capture_stream: bool, tail: Option<usize>) -> Option<Self> {
+        if capture_stream {
+            Some(Collected::new(tail))
+        } else {
+            None
+        }
+    }
+
+    fn extend(&mut self, bytes: &[u8]) {
+        match self {
+            Collected::All(collected) => collected.extend(bytes),
+            Collected::Tail(tail_buffer) => tail_buffer.extend(bytes),
+        }
+    }
+
+    /// Returns the collected bytes,
+    /// and whether any output was dropped because of a tail limit.
+    fn into_bytes(self) -> (Vec<u8>, bool) {
+        match self {
+            Collected::All(collected) => (collected, false),
+            Collected::Tail(tail_buffer) => tail_buffer.into_bytes(),
+        }
+    }
+}
+
+/// Ring buffer that only keeps the last `max_lines` lines written to it.
+struct TailBuffer {
+    max_lines: usize,
+    complete_lines: VecDeque<Vec<u8>>,
+    current_line: Vec<u8>,
+    truncated: bool,
+}
+
+impl TailBuffer {
+    fn new(max_lines: usize) -> Self {
+        TailBuffer {
+            max_lines,
+            complete_lines: VecDeque::new(),
+            current_line: Vec::new(),
+            truncated: false,
+        }
+    }
+
+    fn extend(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.current_line.push(*byte);
+            if *byte == b'\n' {
+                self.complete_lines
+                    .push_back(std::mem::take(&mut self.current_line));
+                self.truncate();
+            }
+        }
+    }
+
+    fn truncate(&mut self) {
+        while self.complete_lines.len() > self.max_lines {
+            self.complete_lines.pop_front();
+            self.truncated = true;
+        }
+    }
+
+    fn into_bytes(mut self) -> (Vec<u8>, bool) {
+        if !self.current_line.is_empty() {
+            self.complete_lines
+                .push_back(std::mem::take(&mut self.current_line));
+            self.truncate();
+        }
+        (
+            self.complete_lines.into_iter().flatten().collect(),
+            self.truncated,
+        )
+    }
+}
+
+/// Counts the captured bytes of all output streams of a child process,
+/// to enforce [`MaxOutputSize`](crate::MaxOutputSize).
+#[derive(Clone)]
+struct OutputLimit {
+    limit: usize,
+    captured: Arc<AtomicUsize>,
+}
+
+impl OutputLimit {
+    fn new(limit: Option<usize>) -> Option<Self> {
+        limit.map(|limit| OutputLimit {
+            limit,
+            captured: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    fn add(&self, length: usize) -> Result<(), StreamError> {
+        let captured = self.captured.fetch_add(length, Ordering::SeqCst) + length;
+        if captured > self.limit {
+            Err(StreamError::OutputLimitExceeded { limit: self.limit })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// State that is shared by the threads that relay the output streams of a child process.
+#[derive(Clone)]
+struct SharedStreamState {
+    /// Buffers that capture the output of multiple streams.
+    buffers: Vec<Arc<Mutex<Vec<u8>>>>,
+    output_limit: Option<OutputLimit>,
+    /// Reports that a thread finished, and the limit, if it exceeded it.
+    finished: Sender<Option<usize>>,
+}
+
+/// Errors that stop the handling of an output stream.
+#[derive(Debug)]
+enum StreamError {
+    Io(io::Error),
+    OutputLimitExceeded { limit: usize },
+}
+
+impl From<io::Error> for StreamError {
+    fn from(error: io::Error) -> Self {
+        StreamError::Io(error)
+    }
+}
+
+/// Calls the given callbacks for every line of a stream.
+/// Lines can span multiple reads, so incomplete lines are buffered.
+struct LineCallbacks {
+    callbacks: Vec<StrCallback>,
+    current_line: Vec<u8>,
+}
+
+impl LineCallbacks {
+    fn new(callbacks: Vec<StrCallback>) -> Self {
+        LineCallbacks {
+            callbacks,
+            current_line: Vec::new(),
+        }
+    }
+
+    fn extend(&mut self, bytes: &[u8]) {
+        if self.callbacks.is_empty() {
+            return;
+        }
+        for byte in bytes {
+            if *byte == b'\n' {
+                self.call();
+            } else {
+                self.current_line.push(*byte);
+            }
+        }
+    }
+
+    fn finish(&mut self) {
+        if !self.current_line.is_empty() {
+            self.call();
+        }
+    }
+
+    fn call(&mut self) {
+        if self.current_line.last() == Some(&b'\r') {
+            self.current_line.pop();
+        }
+        let line = String::from_utf8_lossy(&self.current_line);
+        for callback in &self.callbacks {
+            (*callback.lock())(&line);
+        }
+        self.current_line.clear();
+    }
+}
+
+/// Prefixes every line written to the inner sink.
+/// Incomplete lines are buffered, so that lines from different
+/// child processes relayed to the same sink don't get mixed up.
+struct PrefixedSink {
+    prefix: Vec<u8>,
+    current_line: Vec<u8>,
+    inner: Box<dyn Write + Send>,
+}
+
+impl PrefixedSink {
+    fn new(prefix: &str, inner: Box<dyn Write + Send>) -> Self {
+        PrefixedSink {
+            prefix: prefix.as_bytes().to_vec(),
+            current_line: Vec::new(),
+            inner,
+        }
+    }
+
+    fn write_current_line(&mut self) -> io::Result<()> {
+        let mut line = self.prefix.clone();
+        line.append(&mut self.current_line);
+        self.inner.write_all(&line)
+    }
+}
+
+impl Write for PrefixedSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for byte in buf {
+            self.current_line.push(*byte);
+            if *byte == b'\n' {
+                self.write_current_line()?;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.current_line.is_empty() {
+            self.write_current_line()?;
+        }
+        self.inner.flush()
+    }
+}
+
+/// Writes output to a file and relays it to the parent at the same time,
+/// for [`StdoutTeeFile`](crate::StdoutTeeFile) and [`StderrTeeFile`](crate::StderrTeeFile).
+struct TeeSink {
+    file: File,
+    parent: Option<Box<dyn Write + Send>>,
+}
+
+impl Write for TeeSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write_all(buf)?;
+        if let Some(parent) = &mut self.parent {
+            parent.write_all(buf)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if let Some(parent) = &mut self.parent {
+            parent.flush()?;
+        }
+        Ok(())
+    }
 }
 
 impl Waiter {
     fn spawn_standard_stream_handler(
+        collected: Option<Collected>,
+        read_buffer_size: usize,
+        shared: SharedStreamState,
+        line_callbacks: LineCallbacks,
+        source: impl Read + Send + 'static,
+        relay_sink: Option<Box<dyn Write + Send>>,
+    ) -> JoinHandle<Result<Option<Collected>, StreamError>> {
+        thread::spawn(move || -> Result<Option<Collected>, StreamError> {
+            let result = Self::handle_standard_stream(
+                collected,
+                read_buffer_size,
+                &shared,
+                line_callbacks,
+                source,
+                relay_sink,
+            );
+            let exceeded_limit = match &result {
+                Err(StreamError::OutputLimitExceeded { limit }) => Some(*limit),
+                _ => None,
+            };
+            let _ = shared.finished.send(exceeded_limit);
+            result
+        })
+    }
+
+    fn handle_standard_stream(
+        mut collected: Option<Collected>,
+        read_buffer_size: usize,
+        shared: &SharedStreamState,
+        mut line_callbacks: LineCallbacks,
+        mut source: impl Read,
+        mut relay_sink: Option<Box<dyn Write + Send>>,
+    ) -> Result<Option<Collected>, StreamError> {
+        let buffer = &mut vec![0; read_buffer_size.max(1)];
+        loop {
+            let length = source.read(buffer)?;
+            if (length) == 0 {
+                break;
+            }
+            if collected.is_some() || !shared.buffers.is_empty() {
+                if let Some(output_limit) = &shared.output_limit {
+                    // Returning drops `source`, so the child process
+                    // can't block on writing to a full pipe.
+                    output_limit.add(length)?;
+                }
+            }
+            if let Some(collected) = &mut collected {
+                collected.extend(&buffer[..length]);
+            }
+            for shared_buffer in &shared.buffers {
+                shared_buffer
+                    .lock()
+                    .expect("shared output lock poisoned")
+                    .extend(&buffer[..length]);
+            }
+            if let Some(relay_sink) = &mut relay_sink {
+                relay_sink.write_all(&buffer[..length])?;
+            }
+            line_callbacks.extend(&buffer[..length]);
+        }
+        line_callbacks.finish();
+        if let Some(relay_sink) = &mut relay_sink {
+            relay_sink.flush()?;
+        }
+        Ok(collected)
+    }
+
+    /// The sink that output is relayed to, when it's not redirected into a file.
+    /// That's the parent's standard stream, unless configured otherwise.
+    /// With [`Quiet`](crate::Quiet), output for the parent is discarded.
+    fn parent_sink(
+        config: &Config,
+        relay_to: &Option<SharedWriter>,
+        context_sink: &(impl Write + Clone + Send + 'static),
+    ) -> Option<Box<dyn Write + Send>> {
+        match relay_to {
+            Some(writer) => Some(Box::new(writer.clone())),
+            None if config.quiet => None,
+            None => Some(Box::new(context_sink.clone())),
+        }
+    }
+
+    fn relay_sink(
         capture_stream: bool,
-        mut source: impl Read + Send + 'static,
-        mut relay_sink: impl Write + Send + 'static,
-    ) -> JoinHandle<io::Result<Option<Vec<u8>>>> {
-        thread::spawn(move || -> io::Result<Option<Vec<u8>>> {
-            let mut collected = if capture_stream {
-                Some(Vec::new())
+        relay_captured_stream: bool,
+        config: &Config,
+        file: Option<(File, bool)>,
+        parent_sink: Option<Box<dyn Write + Send>>,
+    ) -> Option<Box<dyn Write + Send>> {
+        let parent_sink =
+            if relay_captured_stream || (!capture_stream && !config.capture_combined_output) {
+                parent_sink
             } else {
                 None
             };
-            let buffer = &mut [0; 256];
-            loop {
-                let length = source.read(buffer)?;
-                if (length) == 0 {
-                    break;
-                }
-                if let Some(collected) = &mut collected {
-                    collected.extend(&buffer[..length]);
-                }
-                if !capture_stream {
-                    relay_sink.write_all(&buffer[..length])?;
+        match file {
+            Some((file, true)) => Some(Box::new(TeeSink {
+                file,
+                parent: parent_sink,
+            })),
+            Some((file, false)) => Some(Box::new(file)),
+            None => parent_sink,
+        }
+    }
+
+    /// Pairs an opened output file with whether its output is also relayed.
+    #[rustversion::attr(since(1.47), allow(clippy::match_like_matches_macro))]
+    fn with_tee(file: Option<File>, output_file: &Option<OutputFile>) -> Option<(File, bool)> {
+        let tee = match output_file {
+            Some(OutputFile { tee: true, .. }) => true,
+            _ => false,
+        };
+        file.map(|file| (file, tee))
+    }
+
+    fn write_stdin_source(source: StdinSource, child_stdin: &mut ChildStdin) -> io::Result<()> {
+        match source {
+            StdinSource::Bytes(bytes) => child_stdin.write_all(&bytes),
+            StdinSource::File(path) => {
+                let mut file =
+                    File::open(&path).map_err(|error| io_error_with_path(&path, error))?;
+                io::copy(&mut file, child_stdin)?;
+                Ok(())
+            }
+            StdinSource::Reader(reader) => {
+                if let Some(mut reader) = reader.take() {
+                    io::copy(&mut reader, child_stdin)?;
                 }
+                Ok(())
             }
-            Ok(collected)
-        })
+            StdinSource::Callback(callback) => (*callback.lock())(child_stdin),
+        }
     }
 
     pub(crate) fn spawn_standard_stream_relaying<Stdout, Stderr>(
         context: &Context<Stdout, Stderr>,
         config: &Config,
-        mut child_stdin: ChildStdin,
-        child_stdout: ChildStdout,
-        child_stderr: ChildStderr,
+        child_stdin: Option<ChildStdin>,
+        child_stdout: Option<OutputStream>,
+        child_stderr: Option<OutputStream>,
+        stdout_file: Option<File>,
+        stderr_file: Option<File>,
     ) -> Self
     where
         Stdout: Write + Send + Clone + 'static,
         Stderr: Write + Send + Clone + 'static,
     {
-        let stdin_join_handle = config.stdin.clone().map(|config_stdin| {
-            thread::spawn(move || -> io::Result<()> {
-                child_stdin.write_all(&config_stdin)?;
-                Ok(())
+        // All relaying threads report on this channel when they're done,
+        // see `Waiter::wait_for_threads`.
+        let (finished, finished_threads) = mpsc::channel();
+        let stdin_join_handle = match child_stdin {
+            Some(mut child_stdin) if !config.options.stdin.is_empty() => {
+                let config_stdin = config.options.stdin.clone();
+                let finished = finished.clone();
+                Some(thread::spawn(move || -> io::Result<()> {
+                    let result = config_stdin
+                        .into_iter()
+                        .try_for_each(|source| Self::write_stdin_source(source, &mut child_stdin));
+                    let _ = finished.send(None);
+                    result
+                }))
+            }
+            _ => None,
+        };
+        let combined = if config.capture_combined_output {
+            Some(Arc::new(Mutex::new(Vec::new())))
+        } else {
+            None
+        };
+        let redirected = if config.redirect_stderr_to_stdout {
+            Some(Redirected {
+                stdout: if config.capture_stdout {
+                    Some(Arc::new(Mutex::new(Vec::new())))
+                } else {
+                    None
+                },
+                stdout_tail: config.options.stdout_tail,
+                capture_stderr: config.capture_stderr,
             })
-        });
-        let stdout_join_handle = Self::spawn_standard_stream_handler(
+        } else {
+            None
+        };
+        let shared_buffers: Vec<Arc<Mutex<Vec<u8>>>> = combined
+            .iter()
+            .chain(redirected.iter().flat_map(|redirected| &redirected.stdout))
+            .cloned()
+            .collect();
+        let stdout_relay_sink = Self::relay_sink(
             config.capture_stdout,
-            child_stdout,
-            context.stdout.clone(),
-        );
-        let stderr_join_handle = Self::spawn_standard_stream_handler(
-            config.capture_stderr,
-            child_stderr,
-            context.stderr.clone(),
+            config.options.relay_captured_stdout,
+            config,
+            Self::with_tee(stdout_file, &config.options.stdout_file),
+            Self::parent_sink(config, &config.options.relay_stdout_to, &context.stdout),
         );
+        let (stdout_relay_sink, stderr_relay_sink) = if redirected.is_some() {
+            let shared_sink = stdout_relay_sink.map(SharedWriter::new);
+            (
+                shared_sink
+                    .clone()
+                    .map(|sink| Box::new(sink) as Box<dyn Write + Send>),
+                shared_sink.map(|sink| Box::new(sink) as Box<dyn Write + Send>),
+            )
+        } else {
+            let stderr_parent_sink =
+                Self::parent_sink(config, &config.options.relay_stderr_to, &context.stderr);
+            let stderr_parent_sink = match &config.options.stderr_prefix {
+                Some(prefix) => stderr_parent_sink
+                    .map(|sink| Box::new(PrefixedSink::new(prefix, sink)) as Box<dyn Write + Send>),
+                None => stderr_parent_sink,
+            };
+            (
+                stdout_relay_sink,
+                Self::relay_sink(
+                    config.capture_stderr,
+                    false,
+                    config,
+                    Self::with_tee(stderr_file, &config.options.stderr_file),
+                    stderr_parent_sink,
+                ),
+            )
+        };
+        let shared = SharedStreamState {
+            buffers: shared_buffers,
+            output_limit: OutputLimit::new(config.options.max_output_size),
+            finished,
+        };
+        let stdout_join_handle = child_stdout.map(|child_stdout| {
+            Self::spawn_standard_stream_handler(
+                Collected::when_captured(
+                    config.capture_stdout && redirected.is_none(),
+                    config.options.stdout_tail,
+                ),
+                config.options.read_buffer_size,
+                shared.clone(),
+                LineCallbacks::new(config.options.on_stdout_line.clone()),
+                child_stdout,
+                stdout_relay_sink,
+            )
+        });
+        let stderr_join_handle = child_stderr.map(|child_stderr| {
+            Self::spawn_standard_stream_handler(
+                Collected::when_captured(config.capture_stderr && redirected.is_none(), None),
+                config.options.read_buffer_size,
+                shared,
+                LineCallbacks::new(Vec::new()),
+                child_stderr,
+                stderr_relay_sink,
+            )
+        });
         Waiter {
             stdin: stdin_join_handle,
             stdout: stdout_join_handle,
             stderr: stderr_join_handle,
+            combined,
+            redirected,
+            finished_threads,
         }
     }
 
-    pub(crate) fn join(self) -> io::Result<CollectedOutput> {
+    fn output_stream_error(config: &Config, error: StreamError) -> Error {
+        match error {
+            StreamError::Io(error) => Error::command_io_error(config, error),
+            StreamError::OutputLimitExceeded { limit } => Error::OutputTooLarge {
+                full_command: config.full_command(),
+                limit,
+            },
+        }
+    }
+
+    fn take_shared_buffer(buffer: Arc<Mutex<Vec<u8>>>) -> Vec<u8> {
+        std::mem::take(&mut *buffer.lock().expect("shared output lock poisoned"))
+    }
+
+    /// Waits until all relaying threads are finished.
+    /// Returns early, when an output stream exceeds the [`MaxOutputSize`](crate::MaxOutputSize)
+    /// or when the given deadline (from a [`Timeout`](crate::Timeout)) passes.
+    /// In that case, the remaining threads are detached,
+    /// so that the child process can be killed without waiting for them.
+    /// They terminate on their own once the streams are closed.
+    fn wait_for_threads(&self, config: &Config, deadline: Option<Instant>) -> Result<(), Error> {
+        let thread_count =
+            self.stdin.iter().count() + self.stdout.iter().count() + self.stderr.iter().count();
+        for _ in 0..thread_count {
+            let message = match deadline {
+                Some(deadline) => self
+                    .finished_threads
+                    .recv_timeout(deadline.saturating_duration_since(Instant::now())),
+                None => self
+                    .finished_threads
+                    .recv()
+                    .map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            match message {
+                Ok(Some(limit)) => {
+                    return Err(Error::OutputTooLarge {
+                        full_command: config.full_command(),
+                        limit,
+                    })
+                }
+                Ok(None) => {}
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(Error::Timeout {
+                        full_command: config.full_command(),
+                        timeout: config
+                            .options
+                            .timeout
+                            .map(|timeout| timeout.after)
+                            .unwrap_or_default(),
+                    })
+                }
+                // A relaying thread panicked, that's reported when joining it.
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn join(
+        self,
+        config: &Config,
+        deadline: Option<Instant>,
+    ) -> Result<CollectedOutput, Error> {
+        self.wait_for_threads(config, deadline)?;
         if let Some(stdin) = self.stdin {
-            stdin.join().expect("stdout relaying thread panicked")?;
+            stdin
+                .join()
+                .expect("stdin relaying thread panicked")
+                .map_err(|source| {
+                    if source.kind() == io::ErrorKind::BrokenPipe {
+                        Error::StdinNotConsumed {
+                            full_command: config.full_command(),
+                            source,
+                        }
+                    } else {
+                        Error::command_io_error(config, source)
+                    }
+                })?;
         }
-        Ok(CollectedOutput {
-            stdout: self
-                .stdout
+        let stdout = match self.stdout {
+            Some(stdout) => stdout
                 .join()
-                .expect("stdout relaying thread panicked")?,
-            stderr: self
-                .stderr
+                .expect("stdout relaying thread panicked")
+                .map_err(|error| Self::output_stream_error(config, error))?,
+            None => None,
+        };
+        let stderr = match self.stderr {
+            Some(stderr) => stderr
                 .join()
-                .expect("stderr relaying thread panicked")?,
+                .expect("stderr relaying thread panicked")
+                .map_err(|error| Self::output_stream_error(config, error))?,
+            None => None,
+        };
+        let combined = self.combined.map(Self::take_shared_buffer);
+        let stderr = stderr.map(|stderr| stderr.into_bytes().0);
+        let (stdout, stderr) = match self.redirected {
+            Some(Redirected {
+                stdout: redirected_stdout,
+                stdout_tail,
+                capture_stderr,
+            }) => (
+                redirected_stdout
+                    .map(Self::take_shared_buffer)
+                    .map(|stdout| {
+                        let mut collected = Collected::new(stdout_tail);
+                        collected.extend(&stdout);
+                        collected
+                    }),
+                if capture_stderr {
+                    Some(Vec::new())
+                } else {
+                    None
+                },
+            ),
+            None => (stdout, stderr),
+        };
+        let (stdout, stdout_truncated) = match stdout {
+            Some(stdout) => {
+                let (stdout, truncated) = stdout.into_bytes();
+                (Some(stdout), truncated)
+            }
+            None => (None, false),
+        };
+        Ok(CollectedOutput {
+            stdout,
+            stdout_truncated,
+            stderr,
+            combined,
         })
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub(crate) struct CollectedOutput {
     pub(crate) stdout: Option<Vec<u8>>,
+    pub(crate) stdout_truncated: bool,
     pub(crate) stderr: Option<Vec<u8>>,
+    pub(crate) combined: Option<Vec<u8>>,
 }
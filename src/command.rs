@@ -0,0 +1,215 @@
+//! The [`Command`] builder, for reusing inputs across multiple child processes.
+
+use crate::{
+    child_output::ChildOutput,
+    config::Config,
+    context::Context,
+    error::{panic_on_error, CommandSummary, Error},
+    input::{Args, CurrentDir, Env, Input, LogCommand, Stdin},
+    output::{Output, Status},
+    spawned_child::{SpawnedChild, StdoutLines},
+};
+use std::{
+    ffi::OsStr,
+    io::Write,
+    path::Path,
+    process::{ChildStdout, ExitStatus},
+};
+
+/// A builder for child processes.
+/// [`Command`] accumulates [`Input`]s,
+/// so that you can build a base command once
+/// and clone it for every child process that shares the same inputs:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let echo = Command::new("echo").env("FOO", "bar").log();
+/// let StdoutTrimmed(output) = echo.clone().arg("foo").run_output();
+/// assert_eq!(output, "foo");
+/// let StdoutTrimmed(output) = echo.arg("baz").run_output();
+/// assert_eq!(output, "baz");
+/// ```
+///
+/// Like with [`run!`], inputs are applied in the order in which they are added.
+///
+/// Since [`Command`]s are built at runtime,
+/// they can also be used to run commands that are only known at runtime,
+/// e.g. when they're read from a configuration file:
+///
+/// ```
+/// use cradle::prelude::*;
+/// use std::collections::BTreeMap;
+///
+/// struct CommandDescription {
+///     arguments: Vec<String>,
+///     environment: BTreeMap<String, String>,
+///     stdin: String,
+/// }
+///
+/// let description = CommandDescription {
+///     arguments: vec!["sort".to_string()],
+///     environment: BTreeMap::new(),
+///     stdin: "b\na\n".to_string(),
+/// };
+/// let StdoutUntrimmed(output) = Command::new(description.arguments)
+///     .envs(description.environment)
+///     .stdin(description.stdin)
+///     .run_output();
+/// assert_eq!(output, "a\nb\n");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Command {
+    config: Config,
+}
+
+impl Command {
+    /// Creates a new [`Command`] with the given [`Input`],
+    /// usually the executable.
+    pub fn new<I: Input>(input: I) -> Self {
+        Command {
+            config: Config::default(),
+        }
+        .arg(input)
+    }
+
+    /// Adds the given [`Input`], usually an argument.
+    /// Any other [`Input`], e.g. [`Split`](crate::input::Split)
+    /// or [`Stdin`](crate::input::Stdin), can be added as well.
+    pub fn arg<I: Input>(mut self, input: I) -> Self {
+        input.configure(&mut self.config);
+        self
+    }
+
+    /// Sets an environment variable for the child process.
+    /// See [`Env`].
+    pub fn env<Key, Value>(self, key: Key, value: Value) -> Self
+    where
+        Key: AsRef<OsStr>,
+        Value: AsRef<OsStr>,
+    {
+        self.arg(Env(key, value))
+    }
+
+    /// Adds all items of the given [`IntoIterator`] as arguments.
+    /// See [`Args`].
+    pub fn args<I>(self, args: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Input,
+    {
+        self.arg(Args(args))
+    }
+
+    /// Sets multiple environment variables for the child process.
+    pub fn envs<I, Key, Value>(self, variables: I) -> Self
+    where
+        I: IntoIterator<Item = (Key, Value)>,
+        Key: AsRef<OsStr>,
+        Value: AsRef<OsStr>,
+    {
+        self.args(variables.into_iter().map(|(key, value)| Env(key, value)))
+    }
+
+    /// Writes the given bytes to the child's `stdin`.
+    /// See [`Stdin`].
+    pub fn stdin<T: AsRef<[u8]>>(self, stdin: T) -> Self {
+        self.arg(Stdin(stdin))
+    }
+
+    /// Sets the working directory of the child process.
+    /// See [`CurrentDir`].
+    pub fn current_dir<P: AsRef<Path>>(self, directory: P) -> Self {
+        self.arg(CurrentDir(directory))
+    }
+
+    /// Logs the command before running it.
+    /// See [`LogCommand`].
+    pub fn log(self) -> Self {
+        self.arg(LogCommand)
+    }
+
+    /// Runs the command as a child process.
+    /// It's equivalent to [`Input::run`].
+    #[rustversion::attr(since(1.46), track_caller)]
+    pub fn run(self) {
+        self.run_output()
+    }
+
+    /// Runs the command as a child process.
+    /// It's equivalent to [`Input::run_output`].
+    #[rustversion::attr(since(1.46), track_caller)]
+    pub fn run_output<O>(self) -> O
+    where
+        O: Output,
+    {
+        panic_on_error(self.run_result())
+    }
+
+    /// Runs the command as a child process.
+    /// It's equivalent to [`Input::run_status`].
+    #[rustversion::attr(since(1.46), track_caller)]
+    pub fn run_status(self) -> ExitStatus {
+        let Status(status) = self.run_output();
+        status
+    }
+
+    /// Runs the command as a child process.
+    /// It's equivalent to [`Input::run_result`].
+    pub fn run_result<O>(self) -> Result<O, Error>
+    where
+        O: Output,
+    {
+        self.run_result_with_context(Context::production())
+    }
+
+    /// Runs the command as a child process.
+    /// It's equivalent to [`Input::run_result_into`].
+    pub fn run_result_into<O, E>(self) -> Result<O, E>
+    where
+        O: Output,
+        E: From<Error>,
+    {
+        self.run_result().map_err(E::from)
+    }
+
+    /// Runs the command as a child process.
+    /// It's equivalent to [`Input::run_result_verbose`].
+    pub fn run_result_verbose<O>(self) -> Result<O, (Error, Box<CommandSummary>)>
+    where
+        O: Output,
+    {
+        let summary = Box::new(CommandSummary::new(&self.config));
+        self.run_result().map_err(|error| (error, summary))
+    }
+
+    /// Starts the command as a child process in the background.
+    /// It's equivalent to [`Input::spawn`].
+    pub fn spawn(self) -> Result<SpawnedChild, Error> {
+        SpawnedChild::spawn(Context::production(), self.config)
+    }
+
+    /// Starts the command as a child process and streams its `stdout` line by line.
+    /// It's equivalent to [`Input::stream_stdout_lines`].
+    pub fn stream_stdout_lines(self) -> Result<StdoutLines, Error> {
+        StdoutLines::spawn(Context::production(), self.config)
+    }
+
+    /// Starts the command as a child process and returns its `stdout`.
+    /// It's equivalent to [`Input::spawn_stdout`].
+    pub fn spawn_stdout(self) -> Result<ChildStdout, Error> {
+        SpawnedChild::spawn_stdout(Context::production(), self.config)
+    }
+
+    pub(crate) fn run_result_with_context<Stdout, Stderr, O>(
+        self,
+        context: Context<Stdout, Stderr>,
+    ) -> Result<O, Error>
+    where
+        Stdout: Write + Clone + Send + 'static,
+        Stderr: Write + Clone + Send + 'static,
+        O: Output,
+    {
+        ChildOutput::run_child_process_output(context, self.config)
+    }
+}
@@ -1,6 +1,21 @@
 //! An internal module used for configuring child processes.
 
-use std::{ffi::OsString, path::PathBuf, sync::Arc};
+use crate::{
+    child_output::ChildOutput,
+    error::Error,
+    input::{Retry, Timeout},
+};
+use std::{
+    any::Any,
+    borrow::Cow,
+    ffi::{OsStr, OsString},
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    path::PathBuf,
+    process::Command,
+    sync::{Arc, Mutex, MutexGuard},
+};
 
 /// Used by `Input` implementations to configure how child processes are run.
 /// Usually you don't have to use this type directly.
@@ -8,51 +23,574 @@ use std::{ffi::OsString, path::PathBuf, sync::Arc};
 /// See also the documentation for
 /// [Custom `Input` impls](crate::Input#custom-input-impls) and
 /// [Custom `Output` impls](crate::Output#custom-output-impls).
-#[rustversion::attr(since(1.48), allow(clippy::rc_buffer))]
 #[derive(Debug, Clone)]
 pub struct Config {
     pub(crate) arguments: Vec<OsString>,
+    pub(crate) executables_given: usize,
+    pub(crate) skipped_input: bool,
+    pub(crate) piped_from: Option<Box<Config>>,
     pub(crate) log_command: bool,
-    pub(crate) working_directory: Option<PathBuf>,
-    pub(crate) added_environment_variables: Vec<(OsString, OsString)>,
-    pub(crate) stdin: Option<Arc<Vec<u8>>>,
+    pub(crate) stdin_null: bool,
     pub(crate) capture_stdout: bool,
+    pub(crate) quiet: bool,
+    pub(crate) interactive: bool,
     pub(crate) capture_stderr: bool,
+    pub(crate) capture_combined_output: bool,
+    pub(crate) redirect_stderr_to_stdout: bool,
     pub(crate) error_on_non_zero_exit_code: bool,
+    pub(crate) options: Box<Options>,
+}
+
+/// The settings of a [`Config`] that most commands don't use.
+/// They're boxed to keep [`Config`], and with it [`Error`], small.
+#[derive(Debug, Clone)]
+pub(crate) struct Options {
+    pub(crate) log_command_verbose: bool,
+    pub(crate) log_command_to: Vec<StrCallback>,
+    pub(crate) working_directory: Option<PathBuf>,
+    pub(crate) create_working_directory: bool,
+    pub(crate) process_group: bool,
+    #[cfg(unix)]
+    pub(crate) arg0: Option<OsString>,
+    #[cfg(unix)]
+    pub(crate) umask: Option<u32>,
+    #[cfg(unix)]
+    pub(crate) niceness: Option<i32>,
+    #[cfg(unix)]
+    pub(crate) cpu_time_limit: Option<std::time::Duration>,
+    #[cfg(unix)]
+    pub(crate) memory_limit: Option<usize>,
+    #[cfg(unix)]
+    pub(crate) uid: Option<u32>,
+    #[cfg(unix)]
+    pub(crate) gid: Option<u32>,
+    #[cfg(unix)]
+    pub(crate) closed_fds: Vec<std::os::unix::io::RawFd>,
+    #[cfg(windows)]
+    pub(crate) creation_flags: u32,
+    pub(crate) clear_env: bool,
+    pub(crate) added_environment_variables: Vec<(OsString, OsString)>,
+    pub(crate) removed_environment_variables: Vec<OsString>,
+    pub(crate) path_prefix: Vec<PathBuf>,
+    pub(crate) stdin: Vec<StdinSource>,
+    pub(crate) stdin_handles: Vec<Arc<File>>,
+    pub(crate) stdout_file: Option<OutputFile>,
+    pub(crate) stderr_file: Option<OutputFile>,
+    pub(crate) relay_captured_stdout: bool,
+    pub(crate) on_stdout_line: Vec<StrCallback>,
+    pub(crate) stdout_tail: Option<usize>,
+    pub(crate) field_separator: Option<char>,
+    #[cfg(feature = "encoding")]
+    pub(crate) stdout_encoding: Option<&'static encoding_rs::Encoding>,
+    pub(crate) stderr_prefix: Option<String>,
+    pub(crate) relay_stdout_to: Option<SharedWriter>,
+    pub(crate) stdout_to: Option<Arc<dyn Any + Send + Sync>>,
+    pub(crate) relay_stderr_to: Option<SharedWriter>,
+    pub(crate) elevation: Option<Elevation>,
+    pub(crate) stream_stdout: bool,
+    #[cfg(unix)]
+    pub(crate) pty: bool,
+    pub(crate) read_buffer_size: usize,
+    pub(crate) max_output_size: Option<usize>,
+    pub(crate) ok_exit_codes: Vec<i32>,
+    pub(crate) retry: Option<Retry>,
+    pub(crate) timeout: Option<Timeout>,
+    pub(crate) kill_on_drop: bool,
+    pub(crate) on_spawn: Vec<SpawnCallback>,
+    pub(crate) on_exit: Vec<ExitCallback>,
+    pub(crate) configure_command: Vec<CommandCallback>,
+    pub(crate) input_error: Option<SharedError>,
 }
 
 impl Config {
+    /// Connects the given stages to the beginning of this pipeline.
+    pub(crate) fn pipe_from(&mut self, stages: Box<Config>) {
+        match &mut self.piped_from {
+            Some(first_stage) => first_stage.pipe_from(stages),
+            None => self.piped_from = Some(stages),
+        }
+    }
+
+    /// Takes the first error given with an [`Err`] input, from all stages of the pipeline.
+    pub(crate) fn take_input_error(&self) -> Option<Error> {
+        let source_error = match &self.piped_from {
+            Some(source) => source.take_input_error(),
+            None => None,
+        };
+        source_error.or_else(|| {
+            self.options
+                .input_error
+                .as_ref()
+                .and_then(SharedError::take)
+        })
+    }
+
+    /// Whether the command should not be run at all,
+    /// because the inputs that would have provided the executable
+    /// were skipped with [`When`](crate::When).
+    pub(crate) fn is_skipped(&self) -> bool {
+        self.skipped_input && self.arguments.is_empty() && self.piped_from.is_none()
+    }
+
     pub(crate) fn full_command(&self) -> String {
+        self.full_command_with_elevation(self.options.elevation.as_ref())
+    }
+
+    /// Elevation given for the whole pipeline applies to stages that don't have their own.
+    fn full_command_with_elevation(&self, pipeline_elevation: Option<&Elevation>) -> String {
+        let mut result = String::new();
+        if let Some(source) = &self.piped_from {
+            result.push_str(&source.full_command_with_elevation(pipeline_elevation));
+            result.push_str(" |");
+        }
+        self.push_arguments(&mut result, pipeline_elevation);
+        result
+    }
+
+    /// Like [`Config::full_command`], but also includes the added environment variables
+    /// and the working directory of every stage, e.g. `(cd dir; FOO=bar cmd args)`.
+    pub(crate) fn verbose_command(&self) -> String {
+        self.verbose_command_with_elevation(self.options.elevation.as_ref())
+    }
+
+    fn verbose_command_with_elevation(&self, pipeline_elevation: Option<&Elevation>) -> String {
         let mut result = String::new();
+        if let Some(source) = &self.piped_from {
+            result.push_str(&source.verbose_command_with_elevation(pipeline_elevation));
+            result.push_str(" | ");
+        }
+        let mut stage = String::new();
+        for (key, value) in self.options.added_environment_variables.iter() {
+            if !stage.is_empty() {
+                stage.push(' ');
+            }
+            stage.push_str(&key.to_string_lossy());
+            stage.push('=');
+            stage.push_str(&quote_argument(&value.to_string_lossy()));
+        }
+        self.push_arguments(&mut stage, pipeline_elevation);
+        match &self.options.working_directory {
+            Some(working_directory) => {
+                result.push_str("(cd ");
+                result.push_str(&quote_argument(&working_directory.to_string_lossy()));
+                result.push_str("; ");
+                result.push_str(&stage);
+                result.push(')');
+            }
+            None => result.push_str(&stage),
+        }
+        result
+    }
+
+    /// The program that is prepended to the arguments to run them with elevated privileges,
+    /// e.g. `sudo`.
+    fn elevator(elevation: Option<&Elevation>) -> Option<&OsStr> {
+        match elevation {
+            Some(Elevation::With(elevator)) => Some(elevator),
+            #[cfg(not(windows))]
+            Some(Elevation::Default) => Some(OsStr::new("sudo")),
+            #[cfg(windows)]
+            Some(Elevation::Default) => None,
+            None => None,
+        }
+    }
+
+    /// The arguments that the child process is actually spawned with,
+    /// including the executable.
+    /// These differ from the given arguments for [`Elevated`](crate::Elevated) commands.
+    pub(crate) fn spawned_arguments(&self) -> Vec<OsString> {
+        if self.arguments.is_empty() {
+            return Vec::new();
+        }
+        #[cfg(windows)]
+        if let Some(Elevation::Default) = self.options.elevation {
+            return self.run_as_administrator_arguments();
+        }
+        match Self::elevator(self.options.elevation.as_ref()) {
+            Some(elevator) => std::iter::once(elevator.to_owned())
+                .chain(self.arguments.iter().cloned())
+                .collect(),
+            None => self.arguments.clone(),
+        }
+    }
+
+    /// Runs the arguments through powershell's `Start-Process -Verb RunAs`,
+    /// which shows a UAC prompt.
+    #[cfg(windows)]
+    fn run_as_administrator_arguments(&self) -> Vec<OsString> {
+        let powershell_string = |string: &str| format!("'{}'", string.replace('\'', "''"));
+        let mut script = format!(
+            "$process = Start-Process -FilePath {}",
+            powershell_string(&self.arguments[0].to_string_lossy())
+        );
+        if self.arguments.len() > 1 {
+            let arguments: Vec<String> = self.arguments[1..]
+                .iter()
+                .map(|argument| quote_argument(&argument.to_string_lossy()).into_owned())
+                .collect();
+            script.push_str(" -ArgumentList ");
+            script.push_str(&powershell_string(&arguments.join(" ")));
+        }
+        script.push_str(" -Verb RunAs -Wait -PassThru; exit $process.ExitCode");
+        vec![
+            "powershell".into(),
+            "-NoProfile".into(),
+            "-NonInteractive".into(),
+            "-Command".into(),
+            script.into(),
+        ]
+    }
+
+    fn push_arguments(&self, result: &mut String, pipeline_elevation: Option<&Elevation>) {
+        let elevation = self.options.elevation.as_ref().or(pipeline_elevation);
+        if let (Some(elevator), false) = (Self::elevator(elevation), self.arguments.is_empty()) {
+            if !result.is_empty() {
+                result.push(' ');
+            }
+            result.push_str(&quote_argument(&elevator.to_string_lossy()));
+        }
         for argument in self.arguments.iter() {
-            let argument = argument.to_string_lossy();
             if !result.is_empty() {
                 result.push(' ');
             }
-            let needs_quotes = argument.is_empty() || argument.contains(' ');
-            if needs_quotes {
-                result.push('\'');
+            result.push_str(&quote_argument(&argument.to_string_lossy()));
+        }
+    }
+}
+
+/// Quotes an argument for `sh`, so that logged commands can be pasted into a shell.
+/// Arguments are put in single quotes, embedded single quotes are written as `'\''`.
+#[cfg(not(windows))]
+fn quote_argument(argument: &str) -> Cow<'_, str> {
+    let is_safe = |char: char| {
+        !char.is_ascii() || char.is_ascii_alphanumeric() || "-_./:=@%+,".contains(char)
+    };
+    if !argument.is_empty() && argument.chars().all(is_safe) {
+        return Cow::Borrowed(argument);
+    }
+    let mut result = String::with_capacity(argument.len() + 2);
+    result.push('\'');
+    for char in argument.chars() {
+        if char == '\'' {
+            result.push_str("'\\''");
+        } else {
+            result.push(char);
+        }
+    }
+    result.push('\'');
+    Cow::Owned(result)
+}
+
+/// Quotes an argument following the rules that
+/// `CommandLineToArgvW` uses to parse command lines on windows.
+/// Arguments are put in double quotes, embedded double quotes are escaped with backslashes.
+#[cfg(windows)]
+fn quote_argument(argument: &str) -> Cow<'_, str> {
+    let needs_quotes = argument.is_empty()
+        || argument
+            .chars()
+            .any(|char| char == ' ' || char == '\t' || char == '\n' || char == '"');
+    if !needs_quotes {
+        return Cow::Borrowed(argument);
+    }
+    let mut result = String::with_capacity(argument.len() + 2);
+    result.push('"');
+    let mut backslashes = 0;
+    for char in argument.chars() {
+        match char {
+            '\\' => backslashes += 1,
+            '"' => {
+                result.push_str(&"\\".repeat(backslashes * 2 + 1));
+                backslashes = 0;
             }
-            result.push_str(&argument);
-            if needs_quotes {
-                result.push('\'');
+            _ => {
+                result.push_str(&"\\".repeat(backslashes));
+                backslashes = 0;
             }
         }
-        result
+        if char != '\\' {
+            result.push(char);
+        }
     }
+    result.push_str(&"\\".repeat(backslashes * 2));
+    result.push('"');
+    Cow::Owned(result)
 }
 
 impl Default for Config {
     fn default() -> Self {
         Config {
             arguments: Vec::new(),
+            executables_given: 0,
+            skipped_input: false,
+            piped_from: None,
             log_command: false,
-            working_directory: None,
-            added_environment_variables: Vec::new(),
-            stdin: None,
+            stdin_null: false,
             capture_stdout: false,
+            quiet: false,
+            interactive: false,
             capture_stderr: false,
+            capture_combined_output: false,
+            redirect_stderr_to_stdout: false,
             error_on_non_zero_exit_code: true,
+            options: Box::new(Options::default()),
+        }
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            log_command_verbose: false,
+            log_command_to: Vec::new(),
+            working_directory: None,
+            create_working_directory: false,
+            process_group: false,
+            #[cfg(unix)]
+            arg0: None,
+            #[cfg(unix)]
+            umask: None,
+            #[cfg(unix)]
+            niceness: None,
+            #[cfg(unix)]
+            cpu_time_limit: None,
+            #[cfg(unix)]
+            memory_limit: None,
+            #[cfg(unix)]
+            uid: None,
+            #[cfg(unix)]
+            gid: None,
+            #[cfg(unix)]
+            closed_fds: Vec::new(),
+            #[cfg(windows)]
+            creation_flags: 0,
+            clear_env: false,
+            added_environment_variables: Vec::new(),
+            removed_environment_variables: Vec::new(),
+            path_prefix: Vec::new(),
+            stdin: Vec::new(),
+            stdin_handles: Vec::new(),
+            stdout_file: None,
+            stderr_file: None,
+            relay_captured_stdout: false,
+            on_stdout_line: Vec::new(),
+            stdout_tail: None,
+            field_separator: None,
+            #[cfg(feature = "encoding")]
+            stdout_encoding: None,
+            stderr_prefix: None,
+            relay_stdout_to: None,
+            stdout_to: None,
+            relay_stderr_to: None,
+            elevation: None,
+            stream_stdout: false,
+            #[cfg(unix)]
+            pty: false,
+            read_buffer_size: 8 * 1024,
+            max_output_size: None,
+            ok_exit_codes: Vec::new(),
+            retry: None,
+            timeout: None,
+            kill_on_drop: false,
+            on_spawn: Vec::new(),
+            on_exit: Vec::new(),
+            configure_command: Vec::new(),
+            input_error: None,
+        }
+    }
+}
+
+#[rustversion::attr(since(1.48), allow(clippy::rc_buffer))]
+#[derive(Debug, Clone)]
+pub(crate) enum StdinSource {
+    Bytes(Arc<Vec<u8>>),
+    File(PathBuf),
+    Reader(SharedReader),
+    Callback(StdinCallback),
+}
+
+/// A reader that can be shared between clones of a [`Config`].
+/// The reader can only be consumed once.
+#[derive(Clone)]
+pub(crate) struct SharedReader(Arc<Mutex<Option<Box<dyn Read + Send>>>>);
+
+impl SharedReader {
+    pub(crate) fn new(reader: Box<dyn Read + Send>) -> Self {
+        SharedReader(Arc::new(Mutex::new(Some(reader))))
+    }
+
+    pub(crate) fn take(&self) -> Option<Box<dyn Read + Send>> {
+        match self.0.lock() {
+            Ok(mut reader) => reader.take(),
+            Err(poisoned) => poisoned.into_inner().take(),
+        }
+    }
+}
+
+impl fmt::Debug for SharedReader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedReader")
+    }
+}
+
+/// An error that can be shared between clones of a [`Config`].
+/// The error can only be taken once.
+#[derive(Debug, Clone)]
+pub(crate) struct SharedError(Arc<Mutex<Option<Error>>>);
+
+impl SharedError {
+    pub(crate) fn new(error: Error) -> Self {
+        SharedError(Arc::new(Mutex::new(Some(error))))
+    }
+
+    pub(crate) fn take(&self) -> Option<Error> {
+        match self.0.lock() {
+            Ok(mut error) => error.take(),
+            Err(poisoned) => poisoned.into_inner().take(),
+        }
+    }
+}
+
+/// A callback that can be shared between clones of a [`Config`]
+/// and called from multiple threads.
+pub(crate) struct SharedCallback<F: ?Sized>(Arc<Mutex<Box<F>>>);
+
+impl<F: ?Sized> SharedCallback<F> {
+    pub(crate) fn new(callback: Box<F>) -> Self {
+        SharedCallback(Arc::new(Mutex::new(callback)))
+    }
+
+    /// Callbacks that panicked before can still be called again.
+    pub(crate) fn lock(&self) -> MutexGuard<'_, Box<F>> {
+        match self.0.lock() {
+            Ok(callback) => callback,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl<F: ?Sized> Clone for SharedCallback<F> {
+    fn clone(&self) -> Self {
+        SharedCallback(self.0.clone())
+    }
+}
+
+impl<F: ?Sized> fmt::Debug for SharedCallback<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedCallback")
+    }
+}
+
+/// A callback that writes the child's standard input,
+/// for [`StdinWith`](crate::StdinWith).
+pub(crate) type StdinCallback = SharedCallback<dyn FnMut(&mut dyn Write) -> io::Result<()> + Send>;
+
+/// A callback that is invoked with strings,
+/// e.g. the commands that are being run or lines of output.
+pub(crate) type StrCallback = SharedCallback<dyn FnMut(&str) + Send>;
+
+/// A callback that is invoked with the process id of a spawned child process.
+pub(crate) type SpawnCallback = SharedCallback<dyn FnMut(u32) + Send>;
+
+/// A callback that is invoked with the [`ChildOutput`] of a child process that exited.
+pub(crate) type ExitCallback = SharedCallback<dyn FnMut(&ChildOutput) + Send>;
+
+/// A callback that is invoked with the [`Command`] right before it's spawned.
+pub(crate) type CommandCallback = SharedCallback<dyn FnMut(&mut Command) + Send>;
+
+/// A writer that can be shared between clones of a [`Config`]
+/// and written to from multiple threads.
+#[derive(Clone)]
+pub(crate) struct SharedWriter(Arc<Mutex<Box<dyn Write + Send>>>);
+
+impl SharedWriter {
+    pub(crate) fn new(writer: Box<dyn Write + Send>) -> Self {
+        SharedWriter(Arc::new(Mutex::new(writer)))
+    }
+}
+
+impl Write for SharedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .lock()
+            .expect("shared writer lock poisoned")
+            .write(buf)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.0
+            .lock()
+            .expect("shared writer lock poisoned")
+            .write_all(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.lock().expect("shared writer lock poisoned").flush()
+    }
+}
+
+impl fmt::Debug for SharedWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedWriter")
+    }
+}
+
+/// A writer that can be taken out again after the child process finished,
+/// see [`StdoutTo`](crate::StdoutTo).
+/// Writes after the writer has been taken are discarded.
+pub(crate) struct WriterSlot<W>(pub(crate) Arc<Mutex<Option<W>>>);
+
+impl<W> WriterSlot<W> {
+    pub(crate) fn take(&self) -> Option<W> {
+        match self.0.lock() {
+            Ok(mut writer) => writer.take(),
+            Err(poisoned) => poisoned.into_inner().take(),
+        }
+    }
+}
+
+impl<W: Write> Write for WriterSlot<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.0.lock().expect("writer slot lock poisoned").as_mut() {
+            Some(writer) => writer.write(buf),
+            None => Ok(buf.len()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.0.lock().expect("writer slot lock poisoned").as_mut() {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// How to run commands with elevated privileges.
+#[derive(Debug, Clone)]
+pub(crate) enum Elevation {
+    /// `sudo` on unix, a UAC prompt on windows.
+    Default,
+    /// A custom program that is prepended to the command, e.g. `doas`.
+    With(OsString),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct OutputFile {
+    pub(crate) path: PathBuf,
+    pub(crate) append: bool,
+    /// Whether the output is also relayed, like with `tee`.
+    pub(crate) tee: bool,
+}
+
+impl OutputFile {
+    pub(crate) fn open(&self) -> io::Result<File> {
+        if self.append {
+            OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(&self.path)
+        } else {
+            File::create(&self.path)
         }
     }
 }
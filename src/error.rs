@@ -1,7 +1,15 @@
 //! The [`Error`] type used in the return type of [`run_result!`].
 
 use crate::config::Config;
-use std::{ffi::OsString, fmt::Display, io, process::ExitStatus, string::FromUtf8Error};
+use std::{
+    ffi::OsString,
+    fmt::Display,
+    io,
+    path::{Path, PathBuf},
+    process::ExitStatus,
+    string::FromUtf8Error,
+    time::Duration,
+};
 
 /// Error type returned when an error occurs while using [`run_result!`]
 /// or [`crate::input::Input::run_result`].
@@ -50,14 +58,137 @@ pub enum Error {
         executable: OsString,
         source: io::Error,
     },
+    /// The working directory given with [`CurrentDir`](crate::CurrentDir)
+    /// doesn't exist or is not a directory:
+    ///
+    /// ```
+    /// use cradle::prelude::*;
+    ///
+    /// let result: Result<(), Error> = run_result!("ls", CurrentDir("does-not-exist"));
+    /// match result {
+    ///   Err(Error::WorkingDirectoryNotFound { .. }) => {}
+    ///   _ => panic!(),
+    /// }
+    /// ```
+    WorkingDirectoryNotFound { full_command: String, path: PathBuf },
+    /// [`StdinFromFile`](crate::StdinFromFile), [`StdinNull`](crate::StdinNull)
+    /// or a [`ChildStdout`](std::process::ChildStdout) was combined with other inputs
+    /// that write to the child's `stdin`, like [`Stdin`](crate::Stdin):
+    ///
+    /// ```
+    /// use cradle::prelude::*;
+    /// # let temp_dir = tempfile::TempDir::new().unwrap();
+    /// # std::env::set_current_dir(&temp_dir).unwrap();
+    /// # std::fs::write("file", "foo").unwrap();
+    ///
+    /// let file = std::fs::File::open("file").unwrap();
+    /// let result: Result<(), Error> = run_result!("cat", StdinFromFile(file), Stdin("bar"));
+    /// match result {
+    ///   Err(Error::MultipleStdinSources { .. }) => {}
+    ///   _ => panic!(),
+    /// }
+    /// ```
+    MultipleStdinSources { full_command: String },
+    /// [`StdoutTo`](crate::StdoutTo) was used as the return type,
+    /// but no [`StdoutTo`](crate::StdoutTo) input with a writer of the same type was given:
+    ///
+    /// ```
+    /// use cradle::prelude::*;
+    ///
+    /// let result: Result<StdoutTo<Vec<u8>>, Error> = run_result!(%"echo foo");
+    /// match result {
+    ///   Err(Error::StdoutToNotGiven { .. }) => {}
+    ///   _ => panic!(),
+    /// }
+    /// ```
+    StdoutToNotGiven { full_command: String },
+    /// [`Interactive`](crate::Interactive) was combined with outputs
+    /// that capture `stdout` or `stderr`, like [`StdoutTrimmed`](crate::StdoutTrimmed):
+    ///
+    /// ```
+    /// use cradle::prelude::*;
+    ///
+    /// let result: Result<StdoutTrimmed, Error> = run_result!(%"echo foo", Interactive);
+    /// match result {
+    ///   Err(Error::InteractiveOutputCaptured { .. }) => {}
+    ///   _ => panic!(),
+    /// }
+    /// ```
+    InteractiveOutputCaptured { full_command: String },
+    /// [`Executable`](crate::Executable) was given more than once:
+    ///
+    /// ```
+    /// use cradle::prelude::*;
+    ///
+    /// let result: Result<(), Error> = run_result!(Executable("echo"), Executable("ls"));
+    /// match result {
+    ///   Err(Error::MultipleExecutablesGiven { .. }) => {}
+    ///   _ => panic!(),
+    /// }
+    /// ```
+    MultipleExecutablesGiven { full_command: String },
+    /// The child process stopped reading its `stdin`
+    /// before all input given with e.g. [`Stdin`](crate::Stdin) was written to it:
+    ///
+    /// ```
+    /// use cradle::prelude::*;
+    ///
+    /// let result: Result<(), Error> = run_result!("true", Stdin(vec![b'a'; 1_000_000]));
+    /// match result {
+    ///   Err(Error::StdinNotConsumed { .. }) => {}
+    ///   _ => panic!(),
+    /// }
+    /// ```
+    StdinNotConsumed {
+        full_command: String,
+        source: io::Error,
+    },
+    /// The child process wrote more output than allowed by
+    /// [`MaxOutputSize`](crate::MaxOutputSize):
+    ///
+    /// ```
+    /// use cradle::prelude::*;
+    ///
+    /// let result: Result<StdoutUntrimmed, Error> = run_result!("yes", MaxOutputSize(1000));
+    /// match result {
+    ///   Err(Error::OutputTooLarge { limit: 1000, .. }) => {}
+    ///   _ => panic!(),
+    /// }
+    /// ```
+    OutputTooLarge { full_command: String, limit: usize },
+    /// The child process was still running after the duration given with
+    /// [`Timeout`](crate::Timeout) and was terminated:
+    ///
+    /// ```
+    /// use cradle::prelude::*;
+    /// use std::time::Duration;
+    ///
+    /// let timeout = Timeout {
+    ///     after: Duration::from_millis(100),
+    ///     grace: Duration::from_secs(1),
+    /// };
+    /// let result: Result<(), Error> = run_result!(%"sleep 10", timeout);
+    /// match result {
+    ///   Err(Error::Timeout { .. }) => {}
+    ///   _ => panic!(),
+    /// }
+    /// ```
+    Timeout {
+        full_command: String,
+        timeout: Duration,
+    },
     /// An IO error during execution. A few circumstances in which this can occur are:
     ///
     /// - spawning the child process fails (for another reason than
     ///   [`FileNotFound`](Error::FileNotFound)),
-    /// - writing to `stdin` of the child process fails,
+    /// - writing to `stdin` of the child process fails
+    ///   (for another reason than [`StdinNotConsumed`](Error::StdinNotConsumed)),
     /// - reading from `stdout` or `stderr` of the child process fails,
     /// - writing to the parent's `stdout` or `stderr` fails,
-    /// - the given executable doesn't have the executable flag set.
+    /// - the given executable doesn't have the executable flag set,
+    /// - opening a file given with e.g. [`StdinFile`](crate::StdinFile)
+    ///   or [`StdoutFile`](crate::StdoutFile) fails,
+    /// - creating a directory given with [`CurrentDirCreate`](crate::CurrentDirCreate) fails.
     CommandIoError { message: String, source: io::Error },
     /// The child process exited with a non-zero exit code.
     ///
@@ -72,9 +203,27 @@ pub enum Error {
     /// ```
     ///
     /// This error will be suppressed when [`Status`](crate::Status) is used.
+    ///
+    /// If `stderr` was captured, e.g. with [`Stderr`](crate::Stderr)
+    /// or [`CaptureStderrOnError`](crate::CaptureStderrOnError),
+    /// it's included in `captured_stderr` and (truncated) in the error message:
+    ///
+    /// ```
+    /// use cradle::prelude::*;
+    ///
+    /// # #[cfg(unix)]
+    /// # {
+    /// let result: Result<Stderr, Error> = run_result!(%"sh -c", "echo oops >&2; false");
+    /// assert_eq!(
+    ///     result.unwrap_err().to_string(),
+    ///     "sh -c 'echo oops >&2; false':\n  exited with exit code: 1\n  stderr:\n    oops"
+    /// );
+    /// # }
+    /// ```
     NonZeroExitCode {
         full_command: String,
         exit_status: ExitStatus,
+        captured_stderr: Option<String>,
     },
     /// The child process's `stdout` is being captured,
     /// (e.g. with [`StdoutUntrimmed`](crate::StdoutUntrimmed)),
@@ -92,6 +241,21 @@ pub enum Error {
         full_command: String,
         source: FromUtf8Error,
     },
+    /// The child process's `stdout` and `stderr` are being captured together
+    /// (with [`CombinedOutput`](crate::CombinedOutput)),
+    /// but the process wrote bytes to them that are not
+    /// valid utf-8.
+    InvalidUtf8ToStdoutOrStderr {
+        full_command: String,
+        source: FromUtf8Error,
+    },
+    /// The child process's `stdout` is being captured and deserialized
+    /// (e.g. with [`Toml`](crate::output::Toml) or `Json`),
+    /// but deserialization failed.
+    Deserialization {
+        full_command: String,
+        source: Box<dyn std::error::Error + Send + Sync + 'static>,
+    },
     /// This error is raised when an internal invariant of `cradle` is broken,
     /// and likely indicates a bug.
     Internal {
@@ -102,6 +266,25 @@ pub enum Error {
 }
 
 impl Error {
+    /// Returns the exit code of the child process
+    /// for [`NonZeroExitCode`](Error::NonZeroExitCode) errors,
+    /// and `None` for all other errors.
+    ///
+    /// ```
+    /// use cradle::prelude::*;
+    ///
+    /// let result: Result<(), Error> = run_result!("false");
+    /// assert_eq!(result.unwrap_err().exit_code(), Some(1));
+    /// ```
+    ///
+    /// `None` is also returned when the child process was terminated by a signal.
+    pub fn exit_code(&self) -> Option<i32> {
+        match self {
+            Error::NonZeroExitCode { exit_status, .. } => exit_status.code(),
+            _ => None,
+        }
+    }
+
     pub(crate) fn command_io_error(config: &Config, source: io::Error) -> Error {
         Error::CommandIoError {
             message: format!("{}:\n  {}", config.full_command(), source),
@@ -118,6 +301,65 @@ impl Error {
     }
 }
 
+/// A summary of how a child process was configured,
+/// returned together with errors by [`Input::run_result_verbose`](crate::Input::run_result_verbose):
+///
+/// ```
+/// use cradle::prelude::*;
+/// use std::{ffi::OsString, path::PathBuf};
+///
+/// let result: Result<(), (Error, Box<CommandSummary>)> =
+///     (Split("ls does-not-exist"), CurrentDir("/"), Env("FOO", "foo")).run_result_verbose();
+/// let (_error, summary) = result.unwrap_err();
+/// assert_eq!(summary.full_command, "ls does-not-exist");
+/// assert_eq!(summary.arguments, vec!["ls", "does-not-exist"]);
+/// assert_eq!(summary.current_dir, Some(PathBuf::from("/")));
+/// assert_eq!(
+///     summary.added_env,
+///     vec![(OsString::from("FOO"), OsString::from("foo"))]
+/// );
+/// ```
+///
+/// The environment is described as the changes to the environment of the parent process.
+/// Use [`effective_env`](crate::effective_env) to get the full environment.
+/// For a [`Pipe`](crate::Pipe), all fields except `full_command`
+/// describe the last command of the pipeline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandSummary {
+    /// The full command, as logged by [`LogCommand`](crate::LogCommand).
+    pub full_command: String,
+    /// The arguments passed to the child process, including the executable.
+    pub arguments: Vec<OsString>,
+    /// The working directory, if set with e.g. [`CurrentDir`](crate::CurrentDir).
+    pub current_dir: Option<PathBuf>,
+    /// Whether the environment was cleared with [`ClearEnv`](crate::ClearEnv).
+    pub cleared_env: bool,
+    /// Environment variables set with e.g. [`Env`](crate::Env).
+    pub added_env: Vec<(OsString, OsString)>,
+    /// Environment variables removed with [`EnvRemove`](crate::EnvRemove).
+    pub removed_env: Vec<OsString>,
+    /// Directories prepended to the `PATH` with [`WithPath`](crate::WithPath).
+    pub path_prefix: Vec<PathBuf>,
+}
+
+impl CommandSummary {
+    pub(crate) fn new(config: &Config) -> Self {
+        CommandSummary {
+            full_command: config.full_command(),
+            arguments: config.arguments.clone(),
+            current_dir: config.options.working_directory.clone(),
+            cleared_env: config.options.clear_env,
+            added_env: config.options.added_environment_variables.clone(),
+            removed_env: config.options.removed_environment_variables.clone(),
+            path_prefix: config.options.path_prefix.clone(),
+        }
+    }
+}
+
+pub(crate) fn io_error_with_path(path: &Path, error: io::Error) -> io::Error {
+    io::Error::new(error.kind(), format!("{}: {}", path.display(), error))
+}
+
 #[doc(hidden)]
 #[rustversion::attr(since(1.46), track_caller)]
 pub fn panic_on_error<T>(result: Result<T, Error>) -> T {
@@ -185,19 +427,67 @@ impl Display for Error {
                 }
                 Ok(())
             }
+            WorkingDirectoryNotFound { full_command, path } => write!(
+                f,
+                "{}:\n  working directory not found: {}",
+                full_command,
+                path.display()
+            ),
+            MultipleStdinSources { full_command } => write!(
+                f,
+                "{}:\n  multiple sources for stdin were given",
+                full_command
+            ),
+            InteractiveOutputCaptured { full_command } => write!(
+                f,
+                "{}:\n  Interactive cannot be combined with outputs that capture stdout or stderr",
+                full_command
+            ),
+            MultipleExecutablesGiven { full_command } => write!(
+                f,
+                "{}:\n  Executable cannot be given more than once",
+                full_command
+            ),
+            StdinNotConsumed { full_command, .. } => write!(
+                f,
+                "{}:\n  the child process stopped reading stdin before all input was written",
+                full_command
+            ),
+            OutputTooLarge {
+                full_command,
+                limit,
+            } => write!(
+                f,
+                "{}:\n  captured output exceeded the maximum size of {} bytes",
+                full_command, limit
+            ),
+            Timeout {
+                full_command,
+                timeout,
+            } => write!(f, "{}:\n  timed out after {:?}", full_command, timeout),
+            StdoutToNotGiven { full_command } => write!(
+                f,
+                "{}:\n  StdoutTo was used as the return type, but not given as an input",
+                full_command
+            ),
             CommandIoError { message, .. } => write!(f, "{}", message),
             NonZeroExitCode {
                 full_command,
                 exit_status,
+                captured_stderr,
             } => {
                 if let Some(exit_code) = exit_status.code() {
                     write!(
                         f,
                         "{}:\n  exited with exit code: {}",
                         full_command, exit_code
-                    )
+                    )?;
                 } else {
-                    write!(f, "{}:\n  exited with {}", full_command, exit_status)
+                    write!(f, "{}:\n  exited with {}", full_command, exit_status)?;
+                }
+                match captured_stderr {
+                    Some(stderr) if !stderr.trim().is_empty() => write_captured_stderr(f, stderr),
+                    _ => Ok(()),
                 }
             }
             InvalidUtf8ToStdout { full_command, .. } => {
@@ -206,8 +496,25 @@ impl Display for Error {
             InvalidUtf8ToStderr { full_command, .. } => {
                 write!(f, "{}:\n  invalid utf-8 written to stderr", full_command)
             }
+            InvalidUtf8ToStdoutOrStderr { full_command, .. } => {
+                write!(
+                    f,
+                    "{}:\n  invalid utf-8 written to stdout or stderr",
+                    full_command
+                )
+            }
+            Deserialization {
+                full_command,
+                source,
+            } => {
+                write!(
+                    f,
+                    "{}:\n  could not deserialize stdout: {}",
+                    full_command, source
+                )
+            }
             Internal { .. } => {
-                let snippets = vec![
+                let snippets = [
                     "Congratulations, you've found a bug in cradle! :/",
                     "Please, open an issue on https://github.com/soenkehahn/cradle/issues",
                     "with the following information:",
@@ -218,13 +525,57 @@ impl Display for Error {
     }
 }
 
+/// Maximum number of characters of captured `stderr`
+/// that are included in error messages.
+const MAX_CAPTURED_STDERR_LENGTH: usize = 1000;
+
+fn write_captured_stderr(f: &mut std::fmt::Formatter<'_>, stderr: &str) -> std::fmt::Result {
+    let stderr = stderr.trim_end();
+    let truncated: String = stderr.chars().take(MAX_CAPTURED_STDERR_LENGTH).collect();
+    write!(f, "\n  stderr:")?;
+    for line in truncated.lines() {
+        write!(f, "\n    {}", line)?;
+    }
+    if truncated.len() < stderr.len() {
+        write!(f, "\n    ...")?;
+    }
+    Ok(())
+}
+
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         use Error::*;
         match self {
-            FileNotFound { source, .. } | CommandIoError { source, .. } => Some(source),
-            InvalidUtf8ToStdout { source, .. } | InvalidUtf8ToStderr { source, .. } => Some(source),
-            NoExecutableGiven | NonZeroExitCode { .. } | Internal { .. } => None,
+            FileNotFound { source, .. }
+            | CommandIoError { source, .. }
+            | StdinNotConsumed { source, .. } => Some(source),
+            InvalidUtf8ToStdout { source, .. }
+            | InvalidUtf8ToStderr { source, .. }
+            | InvalidUtf8ToStdoutOrStderr { source, .. } => Some(source),
+            Deserialization { source, .. } => Some(source.as_ref()),
+            NoExecutableGiven
+            | WorkingDirectoryNotFound { .. }
+            | MultipleStdinSources { .. }
+            | StdoutToNotGiven { .. }
+            | InteractiveOutputCaptured { .. }
+            | MultipleExecutablesGiven { .. }
+            | OutputTooLarge { .. }
+            | Timeout { .. }
+            | NonZeroExitCode { .. }
+            | Internal { .. } => None,
+        }
+    }
+}
+
+/// Converts IO errors into [`Error::CommandIoError`],
+/// e.g. for passing in fallible inputs as [`Result`]s,
+/// see [`Input`](crate::Input). The error message is the one of the IO error,
+/// since the error didn't occur while running a command.
+impl From<io::Error> for Error {
+    fn from(source: io::Error) -> Self {
+        Error::CommandIoError {
+            message: source.to_string(),
+            source,
         }
     }
 }
@@ -253,6 +604,29 @@ mod tests {
         assert!(std::error::Error::source(&result.unwrap_err()).is_some());
     }
 
+    mod exit_code {
+        use super::*;
+
+        #[test]
+        fn returns_the_exit_code_of_non_zero_exit_code_errors() {
+            let result: Result<(), crate::Error> = run_result!("false");
+            assert_eq!(result.unwrap_err().exit_code(), Some(1));
+        }
+
+        #[test]
+        fn returns_none_for_other_errors() {
+            let result: Result<(), crate::Error> = run_result!("does-not-exist");
+            assert_eq!(result.unwrap_err().exit_code(), None);
+        }
+
+        #[test]
+        #[cfg(unix)]
+        fn returns_none_for_signals() {
+            let result: Result<(), crate::Error> = run_result!(%"sh -c", "kill -9 $$");
+            assert_eq!(result.unwrap_err().exit_code(), None);
+        }
+    }
+
     mod english_list {
         use super::*;
         use pretty_assertions::assert_eq;
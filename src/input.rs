@@ -2,16 +2,26 @@
 
 use crate::{
     child_output::ChildOutput,
-    config::Config,
+    config::{
+        CommandCallback, Config, Elevation, ExitCallback, OutputFile, SharedError, SharedReader,
+        SharedWriter, SpawnCallback, StdinCallback, StdinSource, StrCallback, WriterSlot,
+    },
     context::Context,
-    error::{panic_on_error, Error},
+    error::{panic_on_error, CommandSummary, Error},
     output::Output,
+    spawned_child::{SpawnedChild, StdoutLines},
 };
 use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
     ffi::{OsStr, OsString},
-    io::Write,
+    fmt,
+    fs::File,
+    io::{Read, Write},
     path::{Path, PathBuf},
-    sync::Arc,
+    process::{ChildStdout, ExitStatus},
+    sync::{Arc, Mutex},
+    time::Duration,
 };
 
 /// All types that are possible arguments to [`run!`], [`run_output!`] or
@@ -33,13 +43,59 @@ use std::{
 /// see the documentation for the individual impls of [`Input`].
 /// Here's a non-exhaustive list of the most commonly used types to get you started:
 ///
-/// - [`String`] and [`&str`],
+/// - [`String`], [`&str`] and [`Cow<str>`](Cow),
+/// - [numbers](#numbers), like [`u16`] or [`f64`],
+/// - [`Args`] for using all items of an [`IntoIterator`] as arguments,
+/// - [`Raw`] for forwarding arguments verbatim,
+/// - [`When`] and [`Option`] for using inputs conditionally,
+/// - [`Result`] for making commands fail with errors from constructing inputs,
+/// - [`BoxedInput`] for storing inputs of different types in the same collection,
+/// - [`Executable`] to explicitly designate the executable,
+/// - `Arg0` for setting `argv[0]` independently of the executable (only on unix),
+/// - [`Elevated`] and [`ElevatedWith`] for running commands with elevated privileges,
 /// - [`Split`] (and its shortcut `%`) to split commands by whitespace,
+///   and [`SplitOs`] for strings that are not valid utf-8,
+/// - [`Shell`] to run commands through the system shell,
+/// - [`NulSeparated`] for arguments separated by `NUL` bytes,
 /// - [`PathBuf`] and [`&Path`],
 /// - multiple sequence types, like [`vectors`], [`slices`] and (since version 1.51) [`arrays`],
-/// - [`CurrentDir`],
-/// - [`Env`] for setting environment variables,
-/// - [`Stdin`], and
+/// - [`CurrentDir`] and [`CurrentDirCreate`],
+/// - [`RelativeToParent`] for path arguments that survive a change of the current directory,
+/// - [`Env`] and [`EnvOpt`] for setting environment variables,
+///   and [`EnvRemove`] and [`ClearEnv`] for removing them,
+/// - [`BTreeMap`] and [`HashMap`] for setting multiple environment variables,
+/// - [`WithPath`] for adding directories to the `PATH`,
+/// - [`Stdin`], [`StdinLine`], [`StdinFile`], [`StdinReader`], [`StdinWith`], [`StdinFromFile`]
+///   and [`StdinNull`],
+/// - [`ChildStdout`] for connecting the output of [`Input::spawn_stdout`],
+/// - [`StdoutFile`] and [`StderrFile`] (and their appending variants),
+/// - [`StdoutTeeFile`] and [`StderrTeeFile`] for writing output to files while relaying it,
+/// - [`RelayStdoutTo`] and [`RelayStderrTo`] for relaying output to your own writers,
+/// - [`StderrToStdout`],
+/// - [`Quiet`] for discarding output instead of relaying it,
+/// - [`Interactive`] for connecting child processes directly to the terminal,
+/// - [`StderrPrefix`] for prefixing relayed `stderr` lines,
+/// - [`CaptureStderrOnError`] for including `stderr` in errors,
+/// - [`StdoutTail`] for only capturing the last lines of `stdout`,
+/// - [`FieldSeparator`] for configuring how [`Fields`](crate::Fields) splits `stdout`,
+/// - `StdoutEncoding` for decoding `stdout` with legacy encodings (requires the `encoding` feature),
+/// - [`OnStdoutLine`] for processing `stdout` line by line while the child is running,
+/// - [`StdoutTo`] for writing `stdout` into your own writer,
+/// - [`ReadBufferSize`] for tuning the throughput of output streams,
+/// - [`MaxOutputSize`] for limiting the size of captured output,
+/// - `CloseFd` for closing inherited file descriptors (only on unix),
+/// - [`Pipe`] for connecting commands,
+/// - [`LogCommandVerbose`] for logging commands with their environment and working directory,
+/// - [`LogCommandTo`] for logging commands with your own function,
+/// - [`OkExitCodes`] for treating non-zero exit codes as success,
+/// - [`Retry`] for re-running failing commands,
+/// - [`Timeout`] for terminating commands that run for too long,
+/// - `CpuTimeLimit` for limiting the CPU time of commands (only on unix),
+/// - `MemoryLimit` for limiting the memory of commands (only on unix),
+/// - [`OnSpawn`] for getting the process id of the child process,
+/// - [`OnExit`] for inspecting child processes after they exited,
+/// - [`KillOnDrop`] for killing child processes started with [`Input::spawn`],
+/// - [`ConfigureCommand`] for configuring the underlying [`std::process::Command`], and
 /// - [`LogCommand`].
 ///
 /// [`String`]: trait.Input.html#impl-Input-for-String
@@ -50,6 +106,22 @@ use std::{
 /// [`slices`]: trait.Input.html#impl-Input-for-%26[T]
 /// [`arrays`]: trait.Input.html#impl-Input-for-[T%3B%20N]
 ///
+/// ## Numbers
+///
+/// Integers and floats can be passed in directly,
+/// without converting them to strings first:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let port: u16 = 8080;
+/// let StdoutTrimmed(output) = run_output!("echo", "--port", port, 1.5);
+/// assert_eq!(output, "--port 8080 1.5");
+/// ```
+///
+/// Every number is passed to the child process as exactly one argument.
+/// Integer literals without a suffix are passed in as [`i32`]s.
+///
 /// ## Tuples
 ///
 /// `cradle` also implements [`Input`] for tuples of types that themselves implement [`Input`].
@@ -104,6 +176,8 @@ use std::{
 /// Here's an example of an `Environment` type, that wraps
 /// [`BTreeMap`](std::collections::BTreeMap) and adds all contained
 /// key-value pairs to the environment of the child process.
+/// (`cradle` already implements [`Input`] for [`BTreeMap`] in the same way,
+/// but it makes for a simple example.)
 ///
 /// ```
 /// use cradle::prelude::*;
@@ -141,7 +215,8 @@ use std::{
 /// ```
 ///
 /// It is not recommended to override [`run`](Input::run),
-/// [`run_output`](Input::run_output) or [`run_result`](Input::run_result).
+/// [`run_output`](Input::run_output) or [`run_result`](Input::run_result),
+/// or their `_with` variants.
 ///
 /// Also note that all fields of the type [`Config`] are private.
 /// That means that when you're writing your own [`Input`] impls,
@@ -192,6 +267,26 @@ pub trait Input: Sized {
         panic_on_error(self.run_result())
     }
 
+    /// `input.run_status()` runs `input` as a child process
+    /// and returns its [`ExitStatus`].
+    /// It's equivalent to `let Status(status) = run_output!(input);`.
+    ///
+    /// ```
+    /// use cradle::prelude::*;
+    ///
+    /// let status = "false".run_status();
+    /// assert_eq!(status.code(), Some(1));
+    /// ```
+    ///
+    /// Like with [`Status`](crate::Status), non-zero exit codes don't cause panics,
+    /// so you can branch on them, like on `$?` in shell scripts.
+    /// Other errors, e.g. [`Error::FileNotFound`], still panic.
+    #[rustversion::attr(since(1.46), track_caller)]
+    fn run_status(self) -> ExitStatus {
+        let crate::output::Status(status) = self.run_output();
+        status
+    }
+
     /// `input.run_result()` runs `input` as a child process.
     /// It's equivalent to `run_result!(input)`.
     ///
@@ -200,11 +295,11 @@ pub trait Input: Sized {
     ///
     /// # fn build() -> Result<(), Error> {
     /// // make sure build tools are installed
-    /// run_result!(%"which make")?;
-    /// run_result!(%"which gcc")?;
-    /// run_result!(%"which ld")?;
-    /// run_result!(%"make build")?;
-    /// # Ok(())
+    /// for tool in &["make", "gcc", "ld"] {
+    ///     let result: Result<(), Error> = ("which", *tool).run_result();
+    ///     result?;
+    /// }
+    /// Split("make build").run_result()
     /// # }
     /// ```
     fn run_result<O>(self) -> Result<O, crate::error::Error>
@@ -214,6 +309,361 @@ pub trait Input: Sized {
         let context = Context::production();
         run_result_with_context(context, self)
     }
+
+    /// `input.run_result_into()` runs `input` as a child process, like
+    /// [`Input::run_result`].
+    /// But errors are converted into your own error type with [`From`],
+    /// so it can be used directly in functions with a unified error type:
+    ///
+    /// ```
+    /// use cradle::prelude::*;
+    ///
+    /// #[derive(Debug)]
+    /// enum AppError {
+    ///     Cradle(cradle::Error),
+    /// }
+    ///
+    /// impl From<cradle::Error> for AppError {
+    ///     fn from(error: cradle::Error) -> Self {
+    ///         AppError::Cradle(error)
+    ///     }
+    /// }
+    ///
+    /// let result: Result<(), AppError> = "false".run_result_into();
+    /// match result {
+    ///     Err(AppError::Cradle(error)) => assert_eq!(error.exit_code(), Some(1)),
+    ///     Ok(()) => panic!(),
+    /// }
+    /// ```
+    ///
+    /// When using `?`, the error type can't be inferred,
+    /// so it has to be given with a turbofish:
+    ///
+    /// ```
+    /// # use cradle::prelude::*;
+    /// # #[derive(Debug)]
+    /// # enum AppError {
+    /// #     Cradle(cradle::Error),
+    /// # }
+    /// # impl From<cradle::Error> for AppError {
+    /// #     fn from(error: cradle::Error) -> Self {
+    /// #         AppError::Cradle(error)
+    /// #     }
+    /// # }
+    /// fn build() -> Result<(), AppError> {
+    ///     Split("echo build").run_result_into::<(), AppError>()?;
+    ///     Ok(())
+    /// }
+    /// build().unwrap();
+    /// ```
+    fn run_result_into<O, E>(self) -> Result<O, E>
+    where
+        O: Output,
+        E: From<crate::error::Error>,
+    {
+        self.run_result().map_err(E::from)
+    }
+
+    /// `input.run_result_verbose()` runs `input` as a child process, like
+    /// [`Input::run_result`].
+    /// But in case of an error, it also returns a [`CommandSummary`]
+    /// that describes how the child process was configured:
+    ///
+    /// ```
+    /// use cradle::prelude::*;
+    ///
+    /// let result: Result<(), (Error, Box<CommandSummary>)> =
+    ///     ("false", CurrentDir("/")).run_result_verbose();
+    /// if let Err((error, summary)) = result {
+    ///     eprintln!("{}", error);
+    ///     eprintln!("working directory: {:?}", summary.current_dir);
+    /// }
+    /// ```
+    ///
+    /// This is useful for tools that want to report failing commands in detail.
+    fn run_result_verbose<O>(self) -> Result<O, (crate::error::Error, Box<CommandSummary>)>
+    where
+        O: Output,
+    {
+        run_result_verbose_with_context(Context::production(), self)
+    }
+
+    /// `input.run_with(extra)` runs `input` as a child process,
+    /// with `extra` as additional [`Input`].
+    /// It's equivalent to `run!(input, extra)`.
+    ///
+    /// ```
+    /// # let temp_dir = tempfile::TempDir::new().unwrap();
+    /// # std::env::set_current_dir(&temp_dir).unwrap();
+    /// use cradle::prelude::*;
+    ///
+    /// std::fs::create_dir("dir").unwrap();
+    /// ("touch", "foo").run_with(CurrentDir("dir"));
+    /// assert!(std::path::Path::new("dir/foo").exists());
+    /// ```
+    #[rustversion::attr(since(1.46), track_caller)]
+    fn run_with<I>(self, extra: I)
+    where
+        I: Input,
+    {
+        (self, extra).run()
+    }
+
+    /// `input.run_output_with(extra)` runs `input` as a child process,
+    /// with `extra` as additional [`Input`].
+    /// It's equivalent to `run_output!(input, extra)`.
+    ///
+    /// ```
+    /// use cradle::prelude::*;
+    ///
+    /// let StdoutTrimmed(output) = ("echo", "foo").run_output_with("bar");
+    /// assert_eq!(output, "foo bar");
+    /// ```
+    #[rustversion::attr(since(1.46), track_caller)]
+    fn run_output_with<I, O>(self, extra: I) -> O
+    where
+        I: Input,
+        O: Output,
+    {
+        (self, extra).run_output()
+    }
+
+    /// `input.run_result_with(extra)` runs `input` as a child process,
+    /// with `extra` as additional [`Input`].
+    /// It's equivalent to `run_result!(input, extra)`.
+    ///
+    /// ```
+    /// use cradle::prelude::*;
+    ///
+    /// # fn test() -> Result<(), Error> {
+    /// let StdoutTrimmed(output) = ("echo", "foo").run_result_with(Env("FOO", "bar"))?;
+    /// assert_eq!(output, "foo");
+    /// # Ok(())
+    /// # }
+    /// # test().unwrap();
+    /// ```
+    fn run_result_with<I, O>(self, extra: I) -> Result<O, crate::error::Error>
+    where
+        I: Input,
+        O: Output,
+    {
+        (self, extra).run_result()
+    }
+
+    /// `input.spawn()` starts `input` as a child process in the background,
+    /// without waiting for it to finish.
+    /// It returns a [`SpawnedChild`] that can be used to wait for or kill
+    /// the child process later:
+    ///
+    /// ```
+    /// use cradle::prelude::*;
+    ///
+    /// # fn test() -> Result<(), Error> {
+    /// let mut server = ("sleep", "10").spawn()?;
+    /// // ... interact with the server ...
+    /// server.kill()?;
+    /// server.wait()?;
+    /// # Ok(())
+    /// # }
+    /// # test().unwrap();
+    /// ```
+    ///
+    /// The output of the child process is relayed to the parent process.
+    /// [`Retry`] and [`Timeout`] have no effect on spawned child processes.
+    fn spawn(self) -> Result<SpawnedChild, crate::error::Error> {
+        spawn_with_context(Context::production(), self)
+    }
+
+    /// `input.stream_stdout_lines()` starts `input` as a child process
+    /// and returns an iterator over the lines it writes to `stdout`.
+    /// The lines are yielded while the child process is running,
+    /// so this is useful for processing long-running commands:
+    ///
+    /// ```
+    /// use cradle::prelude::*;
+    ///
+    /// # fn test() -> Result<(), Error> {
+    /// let lines = ("echo", "foo\nbar").stream_stdout_lines()?;
+    /// let lines: Vec<String> = lines.collect::<Result<_, _>>().unwrap();
+    /// assert_eq!(lines, vec!["foo", "bar"]);
+    /// # Ok(())
+    /// # }
+    /// # test().unwrap();
+    /// ```
+    ///
+    /// The child process is killed when the returned [`StdoutLines`] are dropped.
+    /// See [`StdoutLines`] for more details.
+    fn stream_stdout_lines(self) -> Result<StdoutLines, crate::error::Error> {
+        stream_stdout_lines_with_context(Context::production(), self)
+    }
+
+    /// `input.spawn_stdout()` starts `input` as a child process in the background
+    /// and returns the reading end of its `stdout`.
+    /// [`ChildStdout`] can be used as an input to connect it to the `stdin`
+    /// of another child process, without buffering the output in the parent process:
+    ///
+    /// ```
+    /// use cradle::prelude::*;
+    ///
+    /// # fn test() -> Result<(), Error> {
+    /// let stdout = ("echo", "foo\nbar").spawn_stdout()?;
+    /// let StdoutTrimmed(output) = run_output!(%"grep bar", stdout);
+    /// assert_eq!(output, "bar");
+    /// # Ok(())
+    /// # }
+    /// # test().unwrap();
+    /// ```
+    ///
+    /// This allows to build pipelines manually, where every command
+    /// can be configured separately (e.g. with [`CurrentDir`] or [`Env`]).
+    /// The exit status of the child process is ignored,
+    /// use [`Pipe`] if you need to handle failures of all commands in a pipeline.
+    /// The child process is not killed when it's still running after the returned
+    /// [`ChildStdout`] is dropped, not even when using [`KillOnDrop`].
+    fn spawn_stdout(self) -> Result<ChildStdout, crate::error::Error> {
+        spawn_stdout_with_context(Context::production(), self)
+    }
+}
+
+/// Returns the arguments that `cradle` would pass to the child process
+/// for the given [`Input`], including the executable,
+/// without running anything:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let args = preview_args((Split("echo foo"), vec!["bar", "baz"], CurrentDir("/")));
+/// assert_eq!(args, vec!["echo", "foo", "bar", "baz"]);
+/// ```
+///
+/// This is useful for debugging and for testing custom [`Input`] impls.
+/// Note that for a [`Pipe`] only the arguments of the last command are returned.
+pub fn preview_args<I: Input>(input: I) -> Vec<OsString> {
+    let mut config = Config::default();
+    input.configure(&mut config);
+    config.arguments
+}
+
+/// Returns the environment variables that `cradle` would run the child process with
+/// for the given [`Input`], without running anything:
+///
+/// ```
+/// use cradle::prelude::*;
+/// use std::ffi::OsString;
+///
+/// let env = effective_env((ClearEnv, Env("FOO", "foo"), Env("BAR", "bar"), EnvRemove("BAR")));
+/// assert_eq!(env.len(), 1);
+/// assert_eq!(env.get(&OsString::from("FOO")), Some(&OsString::from("foo")));
+/// ```
+///
+/// This applies [`Env`], [`EnvOpt`], [`EnvRemove`], [`ClearEnv`] and [`WithPath`]
+/// to a snapshot of the environment of the parent process,
+/// the same way that it's done when spawning the child process.
+/// Like with [`preview_args`], for a [`Pipe`] only the environment of the last command is returned.
+pub fn effective_env<I: Input>(input: I) -> BTreeMap<OsString, OsString> {
+    let mut config = Config::default();
+    input.configure(&mut config);
+    let mut env: BTreeMap<OsString, OsString> = if config.options.clear_env {
+        BTreeMap::new()
+    } else {
+        std::env::vars_os().collect()
+    };
+    for key in &config.options.removed_environment_variables {
+        remove_env_var(&mut env, key);
+    }
+    for (key, value) in &config.options.added_environment_variables {
+        remove_env_var(&mut env, key);
+        env.insert(key.clone(), value.clone());
+    }
+    // Errors when joining the `PATH` would make spawning fail,
+    // so there's no meaningful environment to return for them.
+    if let Ok(Some(path)) = ChildOutput::path_with_prefix(&config, &config) {
+        remove_env_var(&mut env, OsStr::new("PATH"));
+        env.insert(OsString::from("PATH"), path);
+    }
+    env
+}
+
+/// Removes an environment variable, ignoring case on windows.
+fn remove_env_var(env: &mut BTreeMap<OsString, OsString>, key: &OsStr) {
+    let matching: Vec<OsString> = env
+        .keys()
+        .filter(|existing| {
+            if cfg!(windows) {
+                existing
+                    .to_string_lossy()
+                    .eq_ignore_ascii_case(&key.to_string_lossy())
+            } else {
+                existing.as_os_str() == key
+            }
+        })
+        .cloned()
+        .collect();
+    for key in matching {
+        env.remove(&key);
+    }
+}
+
+/// Searches the `PATH` of the parent process for the given executable,
+/// without spawning any child processes:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// # #[cfg(unix)]
+/// assert!(which("sh").is_some());
+/// assert_eq!(which("does-not-exist"), None);
+/// ```
+///
+/// This is useful for checking whether a tool is installed,
+/// before running it results in an [`Error::FileNotFound`].
+/// Names that contain a path separator (e.g. `./script` or `/bin/sh`)
+/// are not searched for in the `PATH`, but checked directly.
+/// On unix, only files that have an executable bit set are returned.
+/// On windows, names without an extension are tried with all extensions in `PATHEXT`.
+pub fn which<S: AsRef<OsStr>>(name: S) -> Option<PathBuf> {
+    let name = Path::new(name.as_ref());
+    if name.components().count() > 1 {
+        return executable_candidates(name)
+            .into_iter()
+            .find(|path| is_executable(path));
+    }
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .flat_map(|directory| executable_candidates(&directory.join(name)))
+        .find(|path| is_executable(path))
+}
+
+fn executable_candidates(path: &Path) -> Vec<PathBuf> {
+    if cfg!(windows) && path.extension().is_none() {
+        let extensions = std::env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".into());
+        extensions
+            .split(';')
+            .filter(|extension| !extension.is_empty())
+            .map(|extension| {
+                let mut candidate = path.as_os_str().to_owned();
+                candidate.push(extension);
+                PathBuf::from(candidate)
+            })
+            .collect()
+    } else {
+        vec![path.to_path_buf()]
+    }
+}
+
+fn is_executable(path: &Path) -> bool {
+    match std::fs::metadata(path) {
+        Ok(metadata) if metadata.is_file() => {
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                metadata.permissions().mode() & 0o111 != 0
+            }
+            #[cfg(not(unix))]
+            true
+        }
+        _ => false,
+    }
 }
 
 pub(crate) fn run_result_with_context<Stdout, Stderr, I, O>(
@@ -226,9 +676,64 @@ where
     I: Input,
     O: Output,
 {
+    ChildOutput::run_child_process_output(context, configure(input))
+}
+
+pub(crate) fn run_result_verbose_with_context<Stdout, Stderr, I, O>(
+    context: Context<Stdout, Stderr>,
+    input: I,
+) -> Result<O, (Error, Box<CommandSummary>)>
+where
+    Stdout: Write + Clone + Send + 'static,
+    Stderr: Write + Clone + Send + 'static,
+    I: Input,
+    O: Output,
+{
+    let config = configure(input);
+    let summary = Box::new(CommandSummary::new(&config));
+    ChildOutput::run_child_process_output(context, config).map_err(|error| (error, summary))
+}
+
+pub(crate) fn spawn_with_context<Stdout, Stderr, I>(
+    context: Context<Stdout, Stderr>,
+    input: I,
+) -> Result<SpawnedChild, Error>
+where
+    Stdout: Write + Clone + Send + 'static,
+    Stderr: Write + Clone + Send + 'static,
+    I: Input,
+{
+    SpawnedChild::spawn(context, configure(input))
+}
+
+pub(crate) fn spawn_stdout_with_context<Stdout, Stderr, I>(
+    context: Context<Stdout, Stderr>,
+    input: I,
+) -> Result<ChildStdout, Error>
+where
+    Stdout: Write + Clone + Send + 'static,
+    Stderr: Write + Clone + Send + 'static,
+    I: Input,
+{
+    SpawnedChild::spawn_stdout(context, configure(input))
+}
+
+pub(crate) fn stream_stdout_lines_with_context<Stdout, Stderr, I>(
+    context: Context<Stdout, Stderr>,
+    input: I,
+) -> Result<StdoutLines, Error>
+where
+    Stdout: Write + Clone + Send + 'static,
+    Stderr: Write + Clone + Send + 'static,
+    I: Input,
+{
+    StdoutLines::spawn(context, configure(input))
+}
+
+fn configure<I: Input>(input: I) -> Config {
     let mut config = Config::default();
     input.configure(&mut config);
-    ChildOutput::run_child_process_output(context, config)
+    config
 }
 
 #[cfg(test)]
@@ -321,90 +826,342 @@ impl Input for String {
     }
 }
 
-/// Splits the contained string by whitespace (using [`split_whitespace`])
-/// and uses the resulting words as separate arguments.
+/// Arguments of type [`Cow<str>`](Cow) are passed to the child process as arguments,
+/// no matter whether they're borrowed or owned:
 ///
 /// ```
 /// use cradle::prelude::*;
+/// use std::borrow::Cow;
 ///
-/// let StdoutTrimmed(output) = run_output!(Split("echo foo"));
+/// let argument: Cow<str> = Cow::Borrowed("foo");
+/// let StdoutTrimmed(output) = run_output!("echo", argument);
 /// assert_eq!(output, "foo");
 /// ```
-///
-/// Since this is such a common case, `cradle` also provides a syntactic shortcut
-/// for [`Split`], the `%` symbol:
+impl Input for Cow<'_, str> {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        match self {
+            Cow::Borrowed(string) => string.configure(config),
+            Cow::Owned(string) => string.configure(config),
+        }
+    }
+}
+
+/// Arguments of type [`Cow<OsStr>`](Cow) are passed to the child process as arguments,
+/// no matter whether they're borrowed or owned:
 ///
 /// ```
 /// use cradle::prelude::*;
+/// use std::{borrow::Cow, ffi::OsStr};
 ///
-/// let StdoutTrimmed(output) = run_output!(%"echo foo");
+/// let argument: Cow<OsStr> = Cow::Borrowed(OsStr::new("foo"));
+/// let StdoutTrimmed(output) = run_output!("echo", argument);
 /// assert_eq!(output, "foo");
 /// ```
-///
-/// [`split_whitespace`]: str::split_whitespace
-#[derive(Debug, PartialEq, Clone)]
-pub struct Split(pub &'static str);
-
-impl Input for crate::input::Split {
+impl Input for Cow<'_, OsStr> {
     #[doc(hidden)]
     fn configure(self, config: &mut Config) {
-        for argument in self.0.split_whitespace() {
-            argument.configure(config);
-        }
+        self.into_owned().configure(config);
     }
 }
 
-/// Allows to use [`split`] to split your argument into words:
+macro_rules! number_impl {
+    ($($number:ty),+) => {
+        $(
+            /// Numbers are passed to the child process as single arguments,
+            /// using their [`Display`](std::fmt::Display) representation.
+            /// They are never split by whitespace.
+            /// See [`Input`](trait.Input.html#numbers) for an example.
+            impl Input for $number {
+                #[doc(hidden)]
+                fn configure(self, config: &mut Config) {
+                    self.to_string().configure(config);
+                }
+            }
+        )+
+    };
+}
+
+number_impl!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+/// Explicitly designates the executable of the child process.
+/// The executable is always passed as the first argument,
+/// independently of where [`Executable`] appears in the inputs:
 ///
 /// ```
 /// use cradle::prelude::*;
 ///
-/// let StdoutTrimmed(output) = run_output!("echo foo".split(' '));
+/// let StdoutTrimmed(output) = run_output!("foo", Executable("echo"));
 /// assert_eq!(output, "foo");
 /// ```
 ///
-/// Arguments to [`split`] must be of type [`char`].
-///
-/// [`split`]: str::split
-impl Input for std::str::Split<'static, char> {
+/// This is useful in wrappers around `cradle`,
+/// where executables and arguments may come from different places.
+/// Passing [`Executable`] multiple times results in an
+/// [`Error::MultipleExecutablesGiven`].
+#[derive(Debug, Clone)]
+pub struct Executable<T: AsRef<OsStr>>(pub T);
+
+impl<T> Input for Executable<T>
+where
+    T: AsRef<OsStr>,
+{
     #[doc(hidden)]
     fn configure(self, config: &mut Config) {
-        for word in self {
-            word.configure(config);
-        }
+        config.arguments.insert(0, self.0.as_ref().to_owned());
+        config.executables_given += 1;
     }
 }
 
-/// Allows to use [`split_whitespace`] to split your argument into words:
+/// Splits the contained string by whitespace (using [`split_whitespace`])
+/// and uses the resulting words as separate arguments.
 ///
 /// ```
 /// use cradle::prelude::*;
 ///
-/// let StdoutTrimmed(output) = run_output!("echo foo".split_whitespace());
+/// let StdoutTrimmed(output) = run_output!(Split("echo foo"));
 /// assert_eq!(output, "foo");
 /// ```
 ///
-/// [`split_whitespace`]: str::split_whitespace
-impl Input for std::str::SplitWhitespace<'static> {
-    #[doc(hidden)]
-    fn configure(self, config: &mut Config) {
-        for word in self {
-            word.configure(config);
-        }
-    }
-}
-
-/// Allows to use [`split_ascii_whitespace`] to split your argument into words:
+/// Since this is such a common case, `cradle` also provides a syntactic shortcut
+/// for [`Split`], the `%` symbol:
 ///
 /// ```
 /// use cradle::prelude::*;
 ///
-/// let StdoutTrimmed(output) = run_output!("echo foo".split_ascii_whitespace());
+/// let StdoutTrimmed(output) = run_output!(%"echo foo");
 /// assert_eq!(output, "foo");
 /// ```
 ///
-/// [`split_ascii_whitespace`]: str::split_ascii_whitespace
-impl Input for std::str::SplitAsciiWhitespace<'static> {
+/// [`Split`] works for all types that implement [`AsRef<str>`],
+/// so for example also for [`String`]s that are built at runtime:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let word = "foo";
+/// let StdoutTrimmed(output) = run_output!(Split(format!("echo {}", word)));
+/// assert_eq!(output, "foo");
+/// ```
+///
+/// [`split_whitespace`]: str::split_whitespace
+#[derive(Debug, PartialEq, Clone)]
+pub struct Split<T: AsRef<str>>(pub T);
+
+impl<T> Input for crate::input::Split<T>
+where
+    T: AsRef<str>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        for argument in self.0.as_ref().split_whitespace() {
+            argument.configure(config);
+        }
+    }
+}
+
+/// Like [`Split`], but for [`OsStr`]s that may not be valid utf-8.
+/// The contained string is split on ASCII whitespace,
+/// and the resulting words are used as separate arguments.
+/// Everything else, including invalid utf-8, is passed to the child process unchanged.
+///
+/// ```
+/// use cradle::prelude::*;
+/// use std::ffi::OsString;
+///
+/// let command = OsString::from("echo foo");
+/// let StdoutTrimmed(output) = run_output!(SplitOs(command));
+/// assert_eq!(output, "foo");
+/// ```
+///
+/// On unix the string is split on ASCII whitespace bytes,
+/// on windows on ASCII whitespace UTF-16 code units.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SplitOs<T: AsRef<OsStr>>(pub T);
+
+impl<T> Input for SplitOs<T>
+where
+    T: AsRef<OsStr>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        for argument in split_os_whitespace(self.0.as_ref()) {
+            argument.configure(config);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn split_os_whitespace(string: &OsStr) -> Vec<OsString> {
+    use std::os::unix::ffi::OsStrExt;
+    string
+        .as_bytes()
+        .split(u8::is_ascii_whitespace)
+        .filter(|word| !word.is_empty())
+        .map(|word| OsStr::from_bytes(word).to_owned())
+        .collect()
+}
+
+#[cfg(windows)]
+fn split_os_whitespace(string: &OsStr) -> Vec<OsString> {
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+    let wide: Vec<u16> = string.encode_wide().collect();
+    wide.split(|unit| *unit < 0x80 && (*unit as u8).is_ascii_whitespace())
+        .filter(|word| !word.is_empty())
+        .map(OsString::from_wide)
+        .collect()
+}
+
+#[cfg(not(any(unix, windows)))]
+fn split_os_whitespace(string: &OsStr) -> Vec<OsString> {
+    string
+        .to_string_lossy()
+        .split_ascii_whitespace()
+        .map(OsString::from)
+        .collect()
+}
+
+/// Splits the given bytes on `NUL` bytes (`\0`)
+/// and uses the resulting parts as separate arguments.
+/// This is useful for passing on file names from e.g. `find -print0`,
+/// which may contain whitespace or newlines:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let files = b"foo bar\0baz\nqux\0";
+/// let Lines(lines) = run_output!("printf", "%s\\n", NulSeparated(&files[..]));
+/// assert_eq!(lines, vec!["foo bar", "baz", "qux"]);
+/// ```
+///
+/// A trailing `NUL` byte doesn't result in an additional empty argument.
+/// On unix the parts are passed to the child process unchanged.
+/// On other platforms, invalid utf-8 is replaced with `U+FFFD`.
+///
+/// See also [`NulSeparatedLines`](crate::NulSeparatedLines)
+/// for splitting the output of child processes on `NUL` bytes.
+#[derive(Debug, PartialEq, Clone)]
+pub struct NulSeparated<T: AsRef<[u8]>>(pub T);
+
+impl<T> Input for NulSeparated<T>
+where
+    T: AsRef<[u8]>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        for part in split_nul_separated(self.0.as_ref()) {
+            bytes_to_os_string(part).configure(config);
+        }
+    }
+}
+
+pub(crate) fn split_nul_separated(bytes: &[u8]) -> Vec<&[u8]> {
+    let mut parts: Vec<&[u8]> = bytes.split(|byte| *byte == 0).collect();
+    if parts.last().map(|last| last.is_empty()) == Some(true) {
+        parts.pop();
+    }
+    parts
+}
+
+#[cfg(unix)]
+pub(crate) fn bytes_to_os_string(bytes: &[u8]) -> OsString {
+    use std::os::unix::ffi::OsStrExt;
+    OsStr::from_bytes(bytes).to_owned()
+}
+
+#[cfg(not(unix))]
+pub(crate) fn bytes_to_os_string(bytes: &[u8]) -> OsString {
+    OsString::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// Runs the given command line through the system shell,
+/// `sh -c` on unix and `cmd /C` on windows.
+/// This allows to use shell features like globbing, `&&` or pipes:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// # #[cfg(unix)]
+/// # {
+/// let StdoutTrimmed(output) = run_output!(Shell("echo foo && echo bar | tr a-z A-Z"));
+/// assert_eq!(output, "foo\nBAR");
+/// # }
+/// ```
+///
+/// Unlike [`Split`], which never invokes a shell,
+/// [`Shell`] is interpreted by the shell, including all of its quoting rules.
+/// So never pass untrusted input to [`Shell`],
+/// since that allows to run arbitrary commands.
+/// Pass untrusted input as separate arguments without [`Shell`] instead.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Shell<T: AsRef<OsStr>>(pub T);
+
+impl<T> Input for Shell<T>
+where
+    T: AsRef<OsStr>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        if cfg!(windows) {
+            ("cmd", "/C").configure(config);
+        } else {
+            ("sh", "-c").configure(config);
+        }
+        self.0.as_ref().configure(config);
+    }
+}
+
+/// Allows to use [`split`] to split your argument into words:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let StdoutTrimmed(output) = run_output!("echo foo".split(' '));
+/// assert_eq!(output, "foo");
+/// ```
+///
+/// Arguments to [`split`] must be of type [`char`].
+///
+/// [`split`]: str::split
+impl Input for std::str::Split<'static, char> {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        for word in self {
+            word.configure(config);
+        }
+    }
+}
+
+/// Allows to use [`split_whitespace`] to split your argument into words:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let StdoutTrimmed(output) = run_output!("echo foo".split_whitespace());
+/// assert_eq!(output, "foo");
+/// ```
+///
+/// [`split_whitespace`]: str::split_whitespace
+impl Input for std::str::SplitWhitespace<'static> {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        for word in self {
+            word.configure(config);
+        }
+    }
+}
+
+/// Allows to use [`split_ascii_whitespace`] to split your argument into words:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let StdoutTrimmed(output) = run_output!("echo foo".split_ascii_whitespace());
+/// assert_eq!(output, "foo");
+/// ```
+///
+/// [`split_ascii_whitespace`]: str::split_ascii_whitespace
+impl Input for std::str::SplitAsciiWhitespace<'static> {
     #[doc(hidden)]
     fn configure(self, config: &mut Config) {
         for word in self {
@@ -477,6 +1234,32 @@ where
     }
 }
 
+/// Same as the implementation for [`Vec<T>`],
+/// so [`Vec`]s that have already been turned into iterators can be used directly:
+///
+/// ```
+/// use cradle::prelude::*;
+/// use std::ffi::OsString;
+///
+/// let args: Vec<OsString> = vec!["foo bar".into(), "baz".into()];
+/// let StdoutTrimmed(output) = run_output!("echo", args.into_iter());
+/// assert_eq!(output, "foo bar baz");
+/// ```
+///
+/// Every element is used as exactly one argument, without splitting it by whitespace.
+/// For other iterators, see [`Args`].
+impl<T> Input for std::vec::IntoIter<T>
+where
+    T: Input,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        for t in self {
+            t.configure(config);
+        }
+    }
+}
+
 /// Similar to the implementation for [`Vec<T>`].
 /// All elements of the array will be used as arguments.
 ///
@@ -522,142 +1305,1819 @@ where
     }
 }
 
-/// Passing in [`LogCommand`] as an argument to `cradle` will cause it
-/// to log the commands (including all arguments) to `stderr`.
-/// (This is similar `bash`'s `-x` option.)
+/// All items of the given [`IntoIterator`] are used as arguments to the child process.
+/// This works for collections that don't implement [`Input`] directly,
+/// and for iterators, without collecting them into a [`Vec`] first:
 ///
 /// ```
 /// use cradle::prelude::*;
+/// use std::{collections::BTreeSet, path::PathBuf};
 ///
-/// run!(LogCommand, %"echo foo");
-/// // writes '+ echo foo' to stderr
+/// let files: BTreeSet<&str> = vec!["foo", "bar"].into_iter().collect();
+/// let StdoutTrimmed(output) = run_output!("echo", Args(files.iter()));
+/// assert_eq!(output, "bar foo");
+///
+/// let StdoutTrimmed(output) = run_output!("echo", Args((1..4).map(|i| i.to_string())));
+/// assert_eq!(output, "1 2 3");
+///
+/// let paths = vec![PathBuf::from("foo"), PathBuf::from("bar")];
+/// let StdoutTrimmed(output) = run_output!("echo", Args(paths.iter().map(|p| p.as_os_str())));
+/// assert_eq!(output, "foo bar");
 /// ```
-#[derive(Debug, Clone, Copy)]
-pub struct LogCommand;
+///
+/// Like with [`Vec<T>`], items are not split by whitespace,
+/// so every item (e.g. an [`OsString`] or [`&OsStr`](OsStr)) results in exactly one argument.
+#[derive(Debug, Clone)]
+pub struct Args<I>(pub I)
+where
+    I: IntoIterator,
+    I::Item: Input;
 
-impl Input for LogCommand {
+impl<I> Input for Args<I>
+where
+    I: IntoIterator,
+    I::Item: Input,
+{
     #[doc(hidden)]
     fn configure(self, config: &mut Config) {
-        config.log_command = true;
+        for item in self.0 {
+            item.configure(config);
+        }
     }
 }
 
-/// By default child processes inherit the current directory from their
-/// parent. You can override this with [`CurrentDir`]:
+/// Passes all given arguments to the child process verbatim,
+/// without splitting or otherwise interpreting them.
+/// This is the way to forward arbitrary arguments, e.g. the arguments
+/// that a wrapper program received itself:
 ///
 /// ```
 /// use cradle::prelude::*;
+/// use std::ffi::OsString;
 ///
-/// # #[cfg(target_os = "linux")]
-/// # {
-/// let StdoutTrimmed(output) = run_output!("pwd", CurrentDir("/tmp"));
-/// assert_eq!(output, "/tmp");
-/// # }
+/// // e.g. `std::env::args_os().skip(1).collect()`
+/// let user_supplied_args: Vec<OsString> = vec!["foo bar".into(), "%baz".into()];
+/// let Lines(output) = run_output!("printf", "%s\\n", Raw(user_supplied_args));
+/// assert_eq!(output, vec!["foo bar", "%baz"]);
 /// ```
 ///
-/// Paths that are relative to the parent's current directory are allowed.
-#[derive(Debug, Clone)]
-pub struct CurrentDir<T: AsRef<Path>>(pub T);
+/// Since [`Raw`] contains [`OsString`]s, arguments don't have to be valid utf-8.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Raw(pub Vec<OsString>);
 
-impl<T> Input for CurrentDir<T>
-where
-    T: AsRef<Path>,
-{
+impl Input for Raw {
     #[doc(hidden)]
     fn configure(self, config: &mut Config) {
-        config.working_directory = Some(self.0.as_ref().to_owned());
+        config.arguments.extend(self.0);
     }
 }
 
-/// Arguments of type [`PathBuf`] are passed to the child process
-/// as arguments.
+/// Only uses the given [`Input`] if the condition is `true`.
+/// This allows to include inputs conditionally, without duplicating the command:
 ///
 /// ```
 /// use cradle::prelude::*;
-/// use std::path::PathBuf;
 ///
-/// let current_dir: PathBuf = std::env::current_dir().unwrap();
-/// run!("ls", current_dir);
+/// let verbose = false;
+/// let StdoutTrimmed(output) = run_output!(When(verbose, LogCommand), %"echo foo");
+/// assert_eq!(output, "foo");
 /// ```
-impl Input for PathBuf {
+///
+/// If the condition is `false`, the inner input is ignored,
+/// as if it hadn't been given.
+/// If that means that no executable is given,
+/// the command is not run at all and the run succeeds with an empty output:
+/// captured `stdout` and `stderr` are empty and the exit status is successful.
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let (StdoutUntrimmed(stdout), Status(status)) = run_output!(When(false, Split("echo foo")));
+/// assert_eq!(stdout, "");
+/// assert!(status.success());
+/// ```
+///
+/// This only applies to [`run!`], [`run_output!`], [`run_result!`]
+/// and the corresponding methods on [`Input`].
+/// Other functions, like [`Input::spawn`], return an [`Error::NoExecutableGiven`] instead.
+#[derive(Debug, Clone)]
+pub struct When<I: Input>(pub bool, pub I);
+
+impl<I> Input for When<I>
+where
+    I: Input,
+{
     #[doc(hidden)]
     fn configure(self, config: &mut Config) {
-        self.into_os_string().configure(config);
+        let When(condition, input) = self;
+        if condition {
+            input.configure(config);
+        } else {
+            config.skipped_input = true;
+        }
     }
 }
 
-/// Arguments of type [`&Path`] are passed to the child process
-/// as arguments.
+/// [`Some`] uses the wrapped [`Input`], [`None`] is ignored.
+/// This allows to pass in optional arguments directly:
 ///
 /// ```
-/// # let temp_dir = tempfile::TempDir::new().unwrap();
-/// # std::env::set_current_dir(&temp_dir).unwrap();
 /// use cradle::prelude::*;
-/// use std::path::Path;
 ///
-/// let file: &Path = Path::new("./foo");
-/// run!("touch", file);
+/// let flag: Option<&str> = None;
+/// let StdoutTrimmed(output) = run_output!(%"echo foo", flag, Some("bar"));
+/// assert_eq!(output, "foo bar");
 /// ```
 ///
-/// [`&Path`]: std::path::Path
-impl Input for &Path {
+/// Like with [`When`], skipping inputs with [`None`] is the same as not passing them in.
+///
+/// The type of the wrapped [`Input`] has to be known, independently
+/// of the output type that [`run_output!`] infers from its usage.
+/// So a literal [`None`] needs a type annotation, e.g. `None::<&str>`.
+impl<T> Input for Option<T>
+where
+    T: Input,
+{
     #[doc(hidden)]
     fn configure(self, config: &mut Config) {
-        self.as_os_str().to_os_string().configure(config);
+        if let Some(input) = self {
+            input.configure(config);
+        }
     }
 }
 
-/// Writes the given byte slice to the child's standard input.
+/// [`Ok`] uses the wrapped [`Input`].
+/// [`Err`] makes the command fail with the given error before it's started.
+/// This allows to pass in inputs that may fail to be constructed,
+/// without unwrapping them first:
 ///
 /// ```
 /// use cradle::prelude::*;
 ///
-/// # #[cfg(target_os = "linux")]
-/// # {
-/// let StdoutUntrimmed(output) = run_output!("sort", Stdin("foo\nbar\n"));
-/// assert_eq!(output, "bar\nfoo\n");
-/// # }
+/// let executable: Result<StdoutTrimmed, Error> = run_result!(%"which does-not-exist");
+/// let executable = executable.map(|StdoutTrimmed(path)| path);
+/// let result: Result<(), Error> = run_result!(executable, "--help");
+/// match result {
+///   Err(Error::NonZeroExitCode { full_command, .. }) => {
+///     assert_eq!(full_command, "which does-not-exist");
+///   }
+///   _ => panic!(),
+/// }
 /// ```
 ///
-/// If `Stdin` is used multiple times, all given bytes slices will be written
-/// to the child's standard input in order.
-#[derive(Debug, Clone)]
-pub struct Stdin<T: AsRef<[u8]>>(pub T);
-
-impl<T> Input for Stdin<T>
+/// Since [`Error`] implements `From<std::io::Error>`, fallible IO operations
+/// can be used directly, resulting in an [`Error::CommandIoError`]:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let result: Result<(), Error> = run_result!("ls", std::fs::canonicalize("does-not-exist"));
+/// assert!(result.is_err());
+/// ```
+///
+/// If multiple [`Err`] inputs are given, the first one is returned.
+/// With [`run!`] and [`run_output!`], the error results in a panic, as usual.
+impl<T, E> Input for Result<T, E>
 where
-    T: AsRef<[u8]>,
+    T: Input,
+    E: Into<Error>,
 {
     #[doc(hidden)]
     fn configure(self, config: &mut Config) {
-        match config.stdin.as_mut() {
-            Some(arc) => {
-                Arc::make_mut(arc).extend_from_slice(self.0.as_ref());
-            }
-            None => {
-                config.stdin = Some(Arc::new(self.0.as_ref().to_vec()));
+        match self {
+            Ok(input) => input.configure(config),
+            Err(error) => {
+                if config.options.input_error.is_none() {
+                    config.options.input_error = Some(SharedError::new(error.into()));
+                }
             }
         }
     }
 }
 
-/// Adds an environment variable to the environment of the child process.
+/// Wraps any [`Input`] in a [`Box`], so that inputs of different types
+/// can be stored in the same collection:
 ///
 /// ```
 /// use cradle::prelude::*;
 ///
-/// let StdoutUntrimmed(output) = run_output!("env", Env("FOO", "bar"));
-/// assert!(output.contains("FOO=bar\n"));
+/// let mut inputs = vec![BoxedInput::new("echo"), BoxedInput::new("foo")];
+/// inputs.push(BoxedInput::new(Env("FOO", "bar")));
+/// inputs.push(BoxedInput::new(CurrentDir("/")));
+/// let StdoutTrimmed(output) = run_output!(inputs);
+/// assert_eq!(output, "foo");
 /// ```
 ///
-/// Child processes inherit the environment of the parent process.
-/// [`Env`] only adds environment variables to that inherited environment.
-/// If the environment variable is also set in the parent process,
-/// it is overwritten by [`Env`].
-#[derive(Debug, Clone)]
-pub struct Env<Key, Value>(pub Key, pub Value)
-where
-    Key: AsRef<OsStr>,
+/// This is useful when building commands dynamically,
+/// where neither tuples nor [`Vec`]s of a single input type are flexible enough.
+pub struct BoxedInput(Box<dyn DynInput>);
+
+impl BoxedInput {
+    /// Boxes the given [`Input`].
+    pub fn new<I>(input: I) -> Self
+    where
+        I: Input + 'static,
+    {
+        BoxedInput(Box::new(input))
+    }
+}
+
+impl fmt::Debug for BoxedInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BoxedInput")
+    }
+}
+
+impl Input for BoxedInput {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        self.0.configure_boxed(config);
+    }
+}
+
+/// An object-safe version of [`Input`], used by [`BoxedInput`].
+trait DynInput {
+    fn configure_boxed(self: Box<Self>, config: &mut Config);
+}
+
+impl<I> DynInput for I
+where
+    I: Input,
+{
+    fn configure_boxed(self: Box<Self>, config: &mut Config) {
+        (*self).configure(config);
+    }
+}
+
+/// Passing in [`LogCommand`] as an argument to `cradle` will cause it
+/// to log the commands (including all arguments) to `stderr`.
+/// (This is similar `bash`'s `-x` option.)
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// run!(LogCommand, %"echo foo");
+/// // writes '+ echo foo' to stderr
+/// ```
+///
+/// Arguments are quoted where necessary, so that logged commands
+/// can be pasted into a shell: with `sh` quoting rules on unix,
+/// and with the quoting rules of `CommandLineToArgvW` on windows.
+#[derive(Debug, Clone, Copy)]
+pub struct LogCommand;
+
+impl Input for LogCommand {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.log_command = true;
+    }
+}
+
+/// Like [`LogCommand`], but the logged commands also include
+/// the environment variables added with [`Env`]
+/// and the working directory set with [`CurrentDir`],
+/// so that failing commands can be reproduced exactly:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// run!(LogCommandVerbose, CurrentDir("/"), Env("FOO", "bar"), %"echo foo");
+/// // writes '+ (cd /; FOO=bar echo foo)' to stderr
+/// ```
+///
+/// Like with [`LogCommand`], the logged commands can be pasted into `sh`.
+/// Removed or cleared environment variables are not included.
+#[derive(Debug, Clone, Copy)]
+pub struct LogCommandVerbose;
+
+impl Input for LogCommandVerbose {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.log_command_verbose = true;
+    }
+}
+
+/// Like [`LogCommand`], but instead of writing the commands to `stderr`,
+/// passes them to the given function.
+/// This can be used to integrate command logging with logging libraries:
+///
+/// ```
+/// use cradle::prelude::*;
+/// use std::sync::mpsc::channel;
+///
+/// let (sender, receiver) = channel();
+/// run!(%"echo foo", LogCommandTo(move |command| sender.send(command.to_string()).unwrap()));
+/// assert_eq!(receiver.recv().unwrap(), "echo foo");
+/// ```
+///
+/// The function receives the command without the `+ ` prefix
+/// that [`LogCommand`] writes.
+#[derive(Debug, Clone)]
+pub struct LogCommandTo<F: FnMut(&str) + Send + 'static>(pub F);
+
+impl<F> Input for LogCommandTo<F>
+where
+    F: FnMut(&str) + Send + 'static,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config
+            .options
+            .log_command_to
+            .push(StrCallback::new(Box::new(self.0)));
+    }
+}
+
+/// By default child processes inherit the current directory from their
+/// parent. You can override this with [`CurrentDir`]:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// # #[cfg(target_os = "linux")]
+/// # {
+/// let StdoutTrimmed(output) = run_output!("pwd", CurrentDir("/tmp"));
+/// assert_eq!(output, "/tmp");
+/// # }
+/// ```
+///
+/// Paths that are relative to the parent's current directory are allowed.
+#[derive(Debug, Clone)]
+pub struct CurrentDir<T: AsRef<Path>>(pub T);
+
+impl<T> Input for CurrentDir<T>
+where
+    T: AsRef<Path>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.working_directory = Some(self.0.as_ref().to_owned());
+        config.options.create_working_directory = false;
+    }
+}
+
+/// Like [`CurrentDir`], but creates the directory (and all its parent directories)
+/// before spawning the child process, if it doesn't exist yet:
+///
+/// ```
+/// # let temp_dir = tempfile::TempDir::new().unwrap();
+/// # std::env::set_current_dir(&temp_dir).unwrap();
+/// use cradle::prelude::*;
+/// use std::path::Path;
+///
+/// run!(%"touch file", CurrentDirCreate("foo/bar"));
+/// assert!(Path::new("foo/bar/file").exists());
+/// ```
+///
+/// If creating the directory fails, an [`Error::CommandIoError`] is returned.
+/// The directory is created even if the child process fails afterwards.
+#[derive(Debug, Clone)]
+pub struct CurrentDirCreate<T: AsRef<Path>>(pub T);
+
+impl<T> Input for CurrentDirCreate<T>
+where
+    T: AsRef<Path>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.working_directory = Some(self.0.as_ref().to_owned());
+        config.options.create_working_directory = true;
+    }
+}
+
+/// Passes the given path to the child process as an argument,
+/// after resolving it against the parent's current directory.
+/// Relative paths would otherwise be interpreted relative to the child's
+/// working directory, which differs when using [`CurrentDir`]:
+///
+/// ```
+/// # let temp_dir = tempfile::TempDir::new().unwrap();
+/// # std::env::set_current_dir(&temp_dir).unwrap();
+/// use cradle::prelude::*;
+///
+/// std::fs::create_dir("dir").unwrap();
+/// std::fs::write("file", "foo").unwrap();
+/// let StdoutUntrimmed(output) = run_output!("cat", RelativeToParent("file"), CurrentDir("dir"));
+/// assert_eq!(output, "foo");
+/// ```
+///
+/// Absolute paths are passed through unchanged.
+/// The path doesn't have to exist, so this also works for files
+/// that the child process creates.
+/// Note that the path is not canonicalized, i.e. symlinks and `..` components are kept.
+#[derive(Debug, Clone)]
+pub struct RelativeToParent<T: AsRef<Path>>(pub T);
+
+impl<T> Input for RelativeToParent<T>
+where
+    T: AsRef<Path>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        let path = self.0.as_ref();
+        match std::env::current_dir() {
+            Ok(current_dir) => current_dir.join(path).configure(config),
+            // Without a current directory, relative paths can't be resolved.
+            Err(_) => path.configure(config),
+        }
+    }
+}
+
+/// Spawns the child process in a new process group.
+/// On unix, the process group ID will be the same as the child's process ID.
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// run!(%"sleep 0.1", ProcessGroup);
+/// ```
+///
+/// This is useful for spawning long-running helper processes,
+/// since signals sent to the parent's process group (e.g. `SIGINT`
+/// when pressing `Ctrl-C` in a terminal) won't be delivered to the child.
+/// It also allows to send signals to the child and all its descendants at once.
+///
+/// On platforms other than unix, [`ProcessGroup`] has no effect.
+///
+/// Only works on rust version `1.64` and up.
+#[rustversion::since(1.64)]
+#[derive(Debug, Clone, Copy)]
+pub struct ProcessGroup;
+
+#[rustversion::since(1.64)]
+impl Input for ProcessGroup {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.process_group = true;
+    }
+}
+
+/// Sets the first element of the arguments that the child process receives
+/// (`argv[0]`), independently of the executable that is run:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let StdoutTrimmed(output) = run_output!("sh", Arg0("foo"), "-c", "echo $0");
+/// assert_eq!(output, "foo");
+/// ```
+///
+/// By default, `argv[0]` is the executable as given.
+/// Some programs, like multi-call binaries (e.g. `busybox`) or login shells,
+/// behave differently depending on `argv[0]`.
+/// [`Arg0`] doesn't change the other arguments,
+/// so the first argument after the executable is still `argv[1]`.
+/// It's also not included in logged commands, e.g. with [`LogCommand`].
+///
+/// [`Arg0`] is only available on unix and only works on rust version `1.45` and up.
+#[cfg(unix)]
+#[rustversion::since(1.45)]
+#[derive(Debug, Clone)]
+pub struct Arg0<T: AsRef<OsStr>>(pub T);
+
+#[cfg(unix)]
+#[rustversion::since(1.45)]
+impl<T> Input for Arg0<T>
+where
+    T: AsRef<OsStr>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.arg0 = Some(self.0.as_ref().to_os_string());
+    }
+}
+
+/// Sets the [umask](https://man7.org/linux/man-pages/man2/umask.2.html)
+/// of the child process, which controls the permissions of files
+/// that the child process creates:
+///
+/// ```
+/// # let temp_dir = tempfile::TempDir::new().unwrap();
+/// # std::env::set_current_dir(&temp_dir).unwrap();
+/// use cradle::prelude::*;
+/// use std::os::unix::fs::PermissionsExt;
+///
+/// run!(%"touch secret", Umask(0o077));
+/// let mode = std::fs::metadata("secret").unwrap().permissions().mode();
+/// assert_eq!(mode & 0o777, 0o600);
+/// ```
+///
+/// The umask of the parent process is not modified.
+///
+/// [`Umask`] is only available on unix.
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy)]
+pub struct Umask(pub u32);
+
+#[cfg(unix)]
+impl Input for Umask {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.umask = Some(self.0);
+    }
+}
+
+/// Runs the child process with an adjusted scheduling priority,
+/// like the `nice` command.
+/// The given value is added to the niceness of the child process,
+/// so positive values lower its priority:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let StdoutTrimmed(parent_niceness) = run_output!("nice");
+/// let StdoutTrimmed(child_niceness) = run_output!("nice", Nice(5));
+/// assert_eq!(
+///     child_niceness.parse::<i32>().unwrap(),
+///     (parent_niceness.parse::<i32>().unwrap() + 5).min(19)
+/// );
+/// ```
+///
+/// Increasing the niceness doesn't require any privileges.
+/// Decreasing it (with negative values) usually does.
+/// Without the necessary privileges spawning the child process will fail
+/// with an [`Error::CommandIoError`].
+///
+/// The niceness of the parent process is not modified.
+///
+/// [`Nice`] is only available on unix.
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy)]
+pub struct Nice(pub i32);
+
+#[cfg(unix)]
+impl Input for Nice {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.niceness = Some(self.0);
+    }
+}
+
+/// Limits the CPU time that the child process can use,
+/// by setting its `RLIMIT_CPU` resource limit.
+/// The limit is enforced by the kernel, which sends `SIGXCPU` to the child process
+/// once it used up the given CPU time.
+/// That terminates the child process by default,
+/// which can be detected with the [`Signal`](crate::Signal) output:
+///
+/// ```
+/// use cradle::prelude::*;
+/// use std::time::Duration;
+///
+/// let Signal(signal) = run_output!(
+///     %"sh -c",
+///     "while true; do :; done",
+///     CpuTimeLimit(Duration::from_secs(1))
+/// );
+/// assert_eq!(signal, Some(libc::SIGXCPU));
+/// ```
+///
+/// Without [`Signal`](crate::Signal) or [`Status`](crate::Status),
+/// exceeding the limit results in an [`Error::NonZeroExitCode`].
+/// The limit is rounded up to whole seconds.
+/// Child processes that handle `SIGXCPU` are killed with `SIGKILL`
+/// one second later.
+///
+/// Unlike [`Timeout`], this limits the CPU time and not the wall-clock time,
+/// so child processes that are sleeping or waiting for input don't use up the limit.
+///
+/// [`CpuTimeLimit`] is only available on unix.
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy)]
+pub struct CpuTimeLimit(pub Duration);
+
+#[cfg(unix)]
+impl Input for CpuTimeLimit {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.cpu_time_limit = Some(self.0);
+    }
+}
+
+/// Limits the address space of the child process to the given number of bytes,
+/// by setting its `RLIMIT_AS` resource limit.
+/// This prevents buggy child processes from using up all the memory of the machine:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let StdoutTrimmed(output) = run_output!(
+///     %"sh -c",
+///     "ulimit -v",
+///     MemoryLimit(256 * 1024 * 1024)
+/// );
+/// assert_eq!(output, "262144");
+/// ```
+///
+/// The limit is enforced by the kernel by failing allocations,
+/// so exceeding it doesn't produce a dedicated cradle error.
+/// Instead, child processes usually report an allocation failure
+/// and exit with a non-zero exit code, or abort, e.g. with `SIGABRT` or `SIGSEGV`.
+/// Note that the limit applies to the virtual address space,
+/// which is usually much larger than the resident memory of a process.
+///
+/// Together with [`CpuTimeLimit`] and [`Timeout`]
+/// this can be used to restrict the resources that child processes can use.
+///
+/// [`MemoryLimit`] is only available on unix.
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryLimit(pub usize);
+
+#[cfg(unix)]
+impl Input for MemoryLimit {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.memory_limit = Some(self.0);
+    }
+}
+
+/// Sets the user ID of the child process.
+/// This can be used by privileged processes to run child processes
+/// as an unprivileged user:
+///
+/// ```no_run
+/// use cradle::prelude::*;
+///
+/// // runs `whoami` as the user with ID 1000
+/// run!("whoami", Uid(1000), Gid(1000));
+/// ```
+///
+/// Spawning the child process will fail with an [`Error::CommandIoError`]
+/// if the parent process doesn't have the permission to set the user ID.
+///
+/// When used together with [`Gid`], the group ID is set before
+/// the user ID, independently of the order in which they are given.
+///
+/// [`Uid`] is only available on unix.
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy)]
+pub struct Uid(pub u32);
+
+#[cfg(unix)]
+impl Input for Uid {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.uid = Some(self.0);
+    }
+}
+
+/// Sets the group ID of the child process.
+/// See [`Uid`] for an example.
+///
+/// [`Gid`] is only available on unix.
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy)]
+pub struct Gid(pub u32);
+
+#[cfg(unix)]
+impl Input for Gid {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.gid = Some(self.0);
+    }
+}
+
+/// Runs the child process with elevated privileges.
+/// On unix, the command is run through `sudo`:
+///
+/// ```no_run
+/// use cradle::prelude::*;
+///
+/// run!(%"apt-get install -y ripgrep", Elevated);
+/// ```
+///
+/// [`Elevated`] can appear anywhere in the inputs.
+/// When used for a whole [`Pipe`], all of its commands are elevated.
+/// Logged commands and errors include the `sudo`,
+/// but [`preview_args`] and [`CommandSummary`] don't.
+/// To use another program than `sudo`, e.g. `doas`, see [`ElevatedWith`].
+///
+/// Note that `sudo` resets the environment by default (`env_reset` in `sudoers`),
+/// so variables set with [`Env`], [`EnvRemove`] or [`WithPath`]
+/// usually don't reach the elevated command.
+///
+/// On windows, the child process is started through powershell's
+/// `Start-Process -Verb RunAs`, which shows a UAC prompt.
+/// The elevated process runs in its own console window,
+/// so its output cannot be captured or relayed,
+/// and it doesn't receive input given with e.g. [`Stdin`].
+/// The exit code of the elevated process is still reported as usual.
+#[derive(Debug, Clone, Copy)]
+pub struct Elevated;
+
+impl Input for Elevated {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.elevation = Some(Elevation::Default);
+    }
+}
+
+/// Like [`Elevated`], but uses the given program to elevate privileges,
+/// instead of `sudo`:
+///
+/// ```no_run
+/// use cradle::prelude::*;
+///
+/// run!(%"apt-get install -y ripgrep", ElevatedWith("doas"));
+/// ```
+///
+/// The given program is prepended to the command on all platforms,
+/// so on windows this can be used with tools like `gsudo`,
+/// that don't have the restrictions of the UAC prompt.
+#[derive(Debug, Clone)]
+pub struct ElevatedWith<T: AsRef<OsStr>>(pub T);
+
+impl<T> Input for ElevatedWith<T>
+where
+    T: AsRef<OsStr>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.elevation = Some(Elevation::With(self.0.as_ref().to_owned()));
+    }
+}
+
+/// Closes the given file descriptor in the child process,
+/// so that the child process doesn't inherit it from the parent:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// // duplicated file descriptors are inherited by child processes
+/// let fd = unsafe { libc::dup(2) };
+/// assert!(run_output!(%"sh -c", format!("true >&{}", fd)));
+/// assert!(!run_output!(%"sh -c", format!("true >&{}", fd), CloseFd(fd)));
+/// # unsafe { libc::close(fd) };
+/// ```
+///
+/// The file descriptors are closed after forking, right before the executable is run,
+/// so the file descriptors of the parent process are not affected.
+/// File descriptors that are not open are ignored.
+/// Closing the standard streams (`0`, `1` and `2`) is possible,
+/// but not recommended, since cradle uses them to communicate with the child process.
+///
+/// This is implemented with [`pre_exec`](std::os::unix::process::CommandExt::pre_exec),
+/// so like all code that runs there, it is restricted to async-signal-safe functions
+/// (i.e. `close`).
+/// Keep that in mind when combining it with your own `pre_exec` closures
+/// in [`ConfigureCommand`].
+///
+/// [`CloseFd`] is only available on unix.
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy)]
+pub struct CloseFd(pub std::os::unix::io::RawFd);
+
+#[cfg(unix)]
+impl Input for CloseFd {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.closed_fds.push(self.0);
+    }
+}
+
+/// Connects the child's `stdout` and `stderr` to
+/// [pseudo-terminals](https://man7.org/linux/man-pages/man7/pty.7.html)
+/// instead of pipes.
+/// Many programs only emit colors or progress output when they're writing to a terminal,
+/// so this allows to capture that output faithfully:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let StdoutTrimmed(output) = run_output!(%"sh -c", "test -t 1 && echo terminal", Pty);
+/// assert_eq!(output, "terminal");
+/// ```
+///
+/// `stdout` and `stderr` get separate pseudo-terminals,
+/// so they can still be captured separately,
+/// e.g. with [`StdoutUntrimmed`](crate::output::StdoutUntrimmed)
+/// and [`Stderr`](crate::output::Stderr).
+/// Line endings are not translated into `\r\n`, as terminals would usually do.
+/// The child's `stdin` is not affected.
+/// In a [`Pipe`], only the `stdout` of the last command is connected to a pseudo-terminal.
+///
+/// [`Pty`] is only available on unix.
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy)]
+pub struct Pty;
+
+#[cfg(unix)]
+impl Input for Pty {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.pty = true;
+    }
+}
+
+/// Sets [process creation flags](https://docs.microsoft.com/en-us/windows/win32/procthread/process-creation-flags)
+/// for the child process.
+/// This is most commonly used by GUI applications
+/// to prevent console windows from popping up for child processes:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// run!(%"cmd /c echo foo", CreationFlags(CreationFlags::CREATE_NO_WINDOW));
+/// ```
+///
+/// When [`CreationFlags`] is given multiple times, the flags are combined.
+///
+/// [`CreationFlags`] is only available on windows.
+#[cfg(windows)]
+#[derive(Debug, Clone, Copy)]
+pub struct CreationFlags(pub u32);
+
+#[cfg(windows)]
+impl CreationFlags {
+    /// Runs the child process without a console window.
+    pub const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+}
+
+#[cfg(windows)]
+impl Input for CreationFlags {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.creation_flags |= self.0;
+    }
+}
+
+/// Arguments of type [`PathBuf`] are passed to the child process
+/// as arguments.
+///
+/// ```
+/// use cradle::prelude::*;
+/// use std::path::PathBuf;
+///
+/// let current_dir: PathBuf = std::env::current_dir().unwrap();
+/// run!("ls", current_dir);
+/// ```
+impl Input for PathBuf {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        self.into_os_string().configure(config);
+    }
+}
+
+/// Arguments of type [`&Path`] are passed to the child process
+/// as arguments.
+///
+/// ```
+/// # let temp_dir = tempfile::TempDir::new().unwrap();
+/// # std::env::set_current_dir(&temp_dir).unwrap();
+/// use cradle::prelude::*;
+/// use std::path::Path;
+///
+/// let file: &Path = Path::new("./foo");
+/// run!("touch", file);
+/// ```
+///
+/// [`&Path`]: std::path::Path
+impl Input for &Path {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        self.as_os_str().to_os_string().configure(config);
+    }
+}
+
+/// Writes the given byte slice to the child's standard input.
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// # #[cfg(target_os = "linux")]
+/// # {
+/// let StdoutUntrimmed(output) = run_output!("sort", Stdin("foo\nbar\n"));
+/// assert_eq!(output, "bar\nfoo\n");
+/// # }
+/// ```
+///
+/// If `Stdin` is used multiple times, all given bytes slices will be written
+/// to the child's standard input in order.
+#[derive(Debug, Clone)]
+pub struct Stdin<T: AsRef<[u8]>>(pub T);
+
+impl<T> Input for Stdin<T>
+where
+    T: AsRef<[u8]>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        match config.options.stdin.last_mut() {
+            Some(StdinSource::Bytes(arc)) => {
+                Arc::make_mut(arc).extend_from_slice(self.0.as_ref());
+            }
+            _ => {
+                config
+                    .options
+                    .stdin
+                    .push(StdinSource::Bytes(Arc::new(self.0.as_ref().to_vec())));
+            }
+        }
+    }
+}
+
+/// Like [`Stdin`], but appends a newline if the given bytes
+/// don't already end with one.
+/// This is useful for sending single lines to interactive programs,
+/// which often wait for a newline before processing their input:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// # #[cfg(unix)]
+/// # {
+/// let StdoutUntrimmed(output) = run_output!("cat", StdinLine("foo"), StdinLine("bar\n"));
+/// assert_eq!(output, "foo\nbar\n");
+/// # }
+/// ```
+///
+/// If [`StdinLine`] is used multiple times,
+/// each of the given byte slices gets its own trailing newline.
+#[derive(Debug, Clone)]
+pub struct StdinLine<T: AsRef<[u8]>>(pub T);
+
+impl<T> Input for StdinLine<T>
+where
+    T: AsRef<[u8]>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        let bytes = self.0.as_ref();
+        Stdin(bytes).configure(config);
+        if !bytes.ends_with(b"\n") {
+            Stdin("\n").configure(config);
+        }
+    }
+}
+
+/// Streams the contents of the file at the given path
+/// to the child's standard input:
+///
+/// ```
+/// # let temp_dir = tempfile::TempDir::new().unwrap();
+/// # std::env::set_current_dir(&temp_dir).unwrap();
+/// use cradle::prelude::*;
+///
+/// # #[cfg(target_os = "linux")]
+/// # {
+/// std::fs::write("file", "foo\nbar\n").unwrap();
+/// let StdoutUntrimmed(output) = run_output!("sort", StdinFile("file"));
+/// assert_eq!(output, "bar\nfoo\n");
+/// # }
+/// ```
+///
+/// Unlike [`Stdin`], the file is not read into memory up front,
+/// but written to the child in chunks while it is running.
+/// So this can be used for big files.
+///
+/// [`StdinFile`] can be combined with [`Stdin`] and other [`StdinFile`]s.
+/// All of them will be written to the child's standard input in order.
+///
+/// If the file cannot be opened or read,
+/// an [`Error::CommandIoError`] will be raised.
+#[derive(Debug, Clone)]
+pub struct StdinFile<T: AsRef<Path>>(pub T);
+
+impl<T> Input for StdinFile<T>
+where
+    T: AsRef<Path>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config
+            .options
+            .stdin
+            .push(StdinSource::File(self.0.as_ref().to_owned()));
+    }
+}
+
+/// Streams everything that can be read from the given [`Read`]er
+/// into the child's standard input:
+///
+/// ```
+/// use cradle::prelude::*;
+/// use std::io::Cursor;
+///
+/// let StdoutUntrimmed(output) = run_output!("cat", StdinReader(Cursor::new("foo")));
+/// assert_eq!(output, "foo");
+/// ```
+///
+/// Like [`StdinFile`], this doesn't read everything into memory up front,
+/// so it can be used with e.g. sockets, decompressors or big buffered files.
+/// [`StdinReader`] can be combined with [`Stdin`] and [`StdinFile`].
+/// All of them will be written to the child's standard input in order.
+///
+/// The reader is consumed when the child process is run.
+/// If reading fails, an [`Error::CommandIoError`] will be raised.
+#[derive(Debug, Clone)]
+pub struct StdinReader<R: Read + Send + 'static>(pub R);
+
+impl<R> Input for StdinReader<R>
+where
+    R: Read + Send + 'static,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config
+            .options
+            .stdin
+            .push(StdinSource::Reader(SharedReader::new(Box::new(self.0))));
+    }
+}
+
+/// Calls the given function with the child's standard input,
+/// so that input can be generated while the child process is running:
+///
+/// ```
+/// use cradle::prelude::*;
+/// use std::io::Write;
+///
+/// let StdoutTrimmed(output) = run_output!(
+///     %"wc -l",
+///     StdinWith(|stdin: &mut dyn Write| {
+///         for i in 0..1000 {
+///             writeln!(stdin, "record {}", i)?;
+///         }
+///         Ok(())
+///     })
+/// );
+/// assert_eq!(output, "1000");
+/// ```
+///
+/// Like [`StdinReader`], this doesn't put the whole input into memory up front,
+/// so it can be used for generating big inputs.
+/// [`StdinWith`] can be combined with [`Stdin`], [`StdinFile`] and [`StdinReader`].
+/// All of them will be written to the child's standard input in order.
+///
+/// The function is called on a separate thread, while the child process is running.
+/// It's called again for every attempt with [`Retry`].
+/// If the function returns an error, an [`Error::CommandIoError`] will be raised.
+/// If the child process stops reading its standard input,
+/// writing fails and the function should return that error,
+/// which results in an [`Error::StdinNotConsumed`].
+#[derive(Debug, Clone)]
+pub struct StdinWith<F: FnMut(&mut dyn Write) -> std::io::Result<()> + Send + 'static>(pub F);
+
+impl<F> Input for StdinWith<F>
+where
+    F: FnMut(&mut dyn Write) -> std::io::Result<()> + Send + 'static,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config
+            .options
+            .stdin
+            .push(StdinSource::Callback(StdinCallback::new(Box::new(self.0))));
+    }
+}
+
+/// Connects the given [`File`] directly to the child's standard input.
+/// Unlike [`StdinFile`], no data is copied through the parent process,
+/// so this is faster and doesn't need an additional thread:
+///
+/// ```
+/// # let temp_dir = tempfile::TempDir::new().unwrap();
+/// # std::env::set_current_dir(&temp_dir).unwrap();
+/// use cradle::prelude::*;
+/// use std::fs::{self, File};
+///
+/// fs::write("file", "foo").unwrap();
+/// let StdoutUntrimmed(output) = run_output!("cat", StdinFromFile(File::open("file").unwrap()));
+/// assert_eq!(output, "foo");
+/// ```
+///
+/// Since the file becomes the child's standard input,
+/// [`StdinFromFile`] cannot be combined with other inputs that write to the child's
+/// standard input, like [`Stdin`], [`StdinFile`] or [`StdinReader`].
+/// Doing so results in an [`Error::MultipleStdinSources`].
+///
+/// With [`Retry`], the file is rewound to its initial position before every attempt,
+/// so that every attempt reads the same input.
+#[derive(Debug)]
+pub struct StdinFromFile(pub File);
+
+impl Input for StdinFromFile {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.stdin_handles.push(Arc::new(self.0));
+    }
+}
+
+/// Connects the `stdout` of a child process started with
+/// [`Input::spawn_stdout`] (or with [`std::process::Command`])
+/// directly to the child's standard input:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// # fn test() -> Result<(), Error> {
+/// let stdout = Split("echo foo").spawn_stdout()?;
+/// let StdoutTrimmed(output) = run_output!("tr", "a-z", "A-Z", stdout);
+/// assert_eq!(output, "FOO");
+/// # Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+///
+/// Like with [`StdinFromFile`], no data is copied through the parent process
+/// and it cannot be combined with other inputs that write to the child's standard input.
+impl Input for ChildStdout {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config
+            .options
+            .stdin_handles
+            .push(Arc::new(child_stdout_into_file(self)));
+    }
+}
+
+#[cfg(unix)]
+fn child_stdout_into_file(stdout: ChildStdout) -> File {
+    use std::os::unix::io::{FromRawFd, IntoRawFd};
+    // The file descriptor is owned by `stdout`, which is consumed here.
+    unsafe { File::from_raw_fd(stdout.into_raw_fd()) }
+}
+
+#[cfg(windows)]
+fn child_stdout_into_file(stdout: ChildStdout) -> File {
+    use std::os::windows::io::{FromRawHandle, IntoRawHandle};
+    // The handle is owned by `stdout`, which is consumed here.
+    unsafe { File::from_raw_handle(stdout.into_raw_handle()) }
+}
+
+/// Connects the child's standard input to the null device
+/// (`/dev/null` on unix, `NUL` on windows):
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let StdoutUntrimmed(output) = run_output!("cat", StdinNull);
+/// assert_eq!(output, "");
+/// ```
+///
+/// By default, the child's standard input is a pipe that `cradle` closes
+/// after writing all given [`Stdin`] inputs, so programs that read from
+/// their standard input see an empty input in both cases.
+/// But with [`StdinNull`], the child's standard input is not a pipe,
+/// which makes a difference for programs that inspect the type of their standard input,
+/// or that `poll` it.
+/// [`StdinNull`] cannot be combined with other inputs for the child's standard input,
+/// like [`Stdin`] or [`StdinFromFile`].
+/// Doing so results in an [`Error::MultipleStdinSources`].
+#[derive(Debug, Clone, Copy)]
+pub struct StdinNull;
+
+impl Input for StdinNull {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.stdin_null = true;
+    }
+}
+
+/// Writes the child's `stdout` to the file at the given path,
+/// instead of relaying it to the parent's `stdout`:
+///
+/// ```
+/// # let temp_dir = tempfile::TempDir::new().unwrap();
+/// # std::env::set_current_dir(&temp_dir).unwrap();
+/// use cradle::prelude::*;
+///
+/// run!(%"echo foo", StdoutFile("file"));
+/// assert_eq!(std::fs::read_to_string("file").unwrap(), "foo\n");
+/// ```
+///
+/// The file is created if it doesn't exist, and truncated if it does.
+/// The output is written to the file while the child process is running,
+/// so this can be used for big outputs without buffering them in memory.
+///
+/// If the file cannot be created,
+/// an [`Error::CommandIoError`] is raised before the child process is spawned.
+#[derive(Debug, Clone)]
+pub struct StdoutFile<T: AsRef<Path>>(pub T);
+
+impl<T> Input for StdoutFile<T>
+where
+    T: AsRef<Path>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.stdout_file = Some(OutputFile {
+            path: self.0.as_ref().to_owned(),
+            append: false,
+            tee: false,
+        });
+    }
+}
+
+/// Like [`StdoutFile`], but appends to the file instead of truncating it:
+///
+/// ```
+/// # let temp_dir = tempfile::TempDir::new().unwrap();
+/// # std::env::set_current_dir(&temp_dir).unwrap();
+/// use cradle::prelude::*;
+///
+/// run!(%"echo foo", StdoutAppendFile("log"));
+/// run!(%"echo bar", StdoutAppendFile("log"));
+/// assert_eq!(std::fs::read_to_string("log").unwrap(), "foo\nbar\n");
+/// ```
+///
+/// The file is created if it doesn't exist.
+#[derive(Debug, Clone)]
+pub struct StdoutAppendFile<T: AsRef<Path>>(pub T);
+
+impl<T> Input for StdoutAppendFile<T>
+where
+    T: AsRef<Path>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.stdout_file = Some(OutputFile {
+            path: self.0.as_ref().to_owned(),
+            append: true,
+            tee: false,
+        });
+    }
+}
+
+/// Writes the child's `stderr` to the file at the given path,
+/// instead of relaying it to the parent's `stderr`.
+/// This works like [`StdoutFile`], but for `stderr`:
+///
+/// ```
+/// # let temp_dir = tempfile::TempDir::new().unwrap();
+/// # std::env::set_current_dir(&temp_dir).unwrap();
+/// use cradle::prelude::*;
+///
+/// let Status(_) = run_output!(%"ls does-not-exist", StderrFile("errors"));
+/// assert!(std::fs::read_to_string("errors").unwrap().contains("does-not-exist"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct StderrFile<T: AsRef<Path>>(pub T);
+
+impl<T> Input for StderrFile<T>
+where
+    T: AsRef<Path>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.stderr_file = Some(OutputFile {
+            path: self.0.as_ref().to_owned(),
+            append: false,
+            tee: false,
+        });
+    }
+}
+
+/// Like [`StderrFile`], but appends to the file instead of truncating it.
+/// See also [`StdoutAppendFile`].
+#[derive(Debug, Clone)]
+pub struct StderrAppendFile<T: AsRef<Path>>(pub T);
+
+impl<T> Input for StderrAppendFile<T>
+where
+    T: AsRef<Path>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.stderr_file = Some(OutputFile {
+            path: self.0.as_ref().to_owned(),
+            append: true,
+            tee: false,
+        });
+    }
+}
+
+/// Writes the child's `stdout` to the file at the given path,
+/// while still relaying it to the parent's `stdout`,
+/// like `| tee file` in a shell:
+///
+/// ```
+/// # let temp_dir = tempfile::TempDir::new().unwrap();
+/// # std::env::set_current_dir(&temp_dir).unwrap();
+/// use cradle::prelude::*;
+///
+/// run!(%"echo foo", StdoutTeeFile("log"));
+/// assert_eq!(std::fs::read_to_string("log").unwrap(), "foo\n");
+/// ```
+///
+/// The file is created if it doesn't exist, and truncated if it does.
+/// Like with [`StdoutFile`], the output is written while the child process is running,
+/// without buffering it in memory.
+/// If writing to the file fails, an [`Error::CommandIoError`] is raised.
+///
+/// Output that is captured, e.g. with [`StdoutTrimmed`](crate::output::StdoutTrimmed),
+/// is still written to the file, but not relayed.
+/// See also [`StdoutTee`](crate::output::StdoutTee) for capturing and relaying output.
+#[derive(Debug, Clone)]
+pub struct StdoutTeeFile<T: AsRef<Path>>(pub T);
+
+impl<T> Input for StdoutTeeFile<T>
+where
+    T: AsRef<Path>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.stdout_file = Some(OutputFile {
+            path: self.0.as_ref().to_owned(),
+            append: false,
+            tee: true,
+        });
+    }
+}
+
+/// Like [`StdoutTeeFile`], but for `stderr`:
+///
+/// ```
+/// # let temp_dir = tempfile::TempDir::new().unwrap();
+/// # std::env::set_current_dir(&temp_dir).unwrap();
+/// use cradle::prelude::*;
+///
+/// let Status(_) = run_output!(%"ls does-not-exist", StderrTeeFile("errors"));
+/// assert!(std::fs::read_to_string("errors").unwrap().contains("does-not-exist"));
+/// ```
+///
+/// [`StderrPrefix`] only applies to the relayed output, not to the file.
+#[derive(Debug, Clone)]
+pub struct StderrTeeFile<T: AsRef<Path>>(pub T);
+
+impl<T> Input for StderrTeeFile<T>
+where
+    T: AsRef<Path>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.stderr_file = Some(OutputFile {
+            path: self.0.as_ref().to_owned(),
+            append: false,
+            tee: true,
+        });
+    }
+}
+
+/// Relays the child's `stdout` to the given writer,
+/// instead of the parent's `stdout`.
+/// The output is written while the child process is running,
+/// so this is useful e.g. for forwarding output through a logging library:
+///
+/// ```
+/// use cradle::prelude::*;
+/// use std::{
+///     io::{self, Write},
+///     sync::{Arc, Mutex},
+/// };
+///
+/// #[derive(Clone, Default)]
+/// struct Log(Arc<Mutex<Vec<u8>>>);
+///
+/// impl Write for Log {
+///     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+///         self.0.lock().unwrap().write(buf)
+///     }
+///
+///     fn flush(&mut self) -> io::Result<()> {
+///         Ok(())
+///     }
+/// }
+///
+/// let log = Log::default();
+/// run!(%"echo foo", RelayStdoutTo(log.clone()));
+/// assert_eq!(*log.0.lock().unwrap(), b"foo\n");
+/// ```
+///
+/// [`RelayStdoutTo`] only replaces the destination of relayed output,
+/// so it doesn't have an effect when `stdout` is captured,
+/// e.g. with [`StdoutUntrimmed`](crate::output::StdoutUntrimmed),
+/// or written to a file with [`StdoutFile`].
+/// With [`StdoutTee`](crate::output::StdoutTee), the output is captured and also
+/// written to the given writer.
+/// If given multiple times, the last writer is used.
+#[derive(Debug)]
+pub struct RelayStdoutTo<W: Write + Send + 'static>(pub W);
+
+impl<W> Input for RelayStdoutTo<W>
+where
+    W: Write + Send + 'static,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.relay_stdout_to = Some(SharedWriter::new(Box::new(self.0)));
+    }
+}
+
+/// Writes the child's `stdout` into the given writer, while the child process is running.
+/// When [`StdoutTo`] is also used as the return type,
+/// the writer is handed back after the child process finished.
+/// This is useful e.g. for checksumming output on the fly,
+/// without buffering it in memory:
+///
+/// ```
+/// use cradle::prelude::*;
+/// use std::io::{self, Write};
+///
+/// #[derive(Debug, Default)]
+/// struct CountBytes(usize);
+///
+/// impl Write for CountBytes {
+///     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+///         self.0 += buf.len();
+///         Ok(buf.len())
+///     }
+///
+///     fn flush(&mut self) -> io::Result<()> {
+///         Ok(())
+///     }
+/// }
+///
+/// let StdoutTo(CountBytes(count)) = run_output!(%"echo foo", StdoutTo(CountBytes::default()));
+/// assert_eq!(count, 4);
+/// ```
+///
+/// The output is written like with [`RelayStdoutTo`],
+/// so the writer doesn't receive anything when `stdout` is captured otherwise,
+/// e.g. with [`StdoutUntrimmed`](crate::output::StdoutUntrimmed).
+/// Using [`StdoutTo`] as the return type without passing it in as an input
+/// results in an [`Error::StdoutToNotGiven`].
+#[derive(Debug)]
+pub struct StdoutTo<W: Write + Send + 'static>(pub W);
+
+impl<W> Input for StdoutTo<W>
+where
+    W: Write + Send + 'static,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        let slot = Arc::new(Mutex::new(Some(self.0)));
+        config.options.relay_stdout_to =
+            Some(SharedWriter::new(Box::new(WriterSlot(slot.clone()))));
+        config.options.stdout_to = Some(slot);
+    }
+}
+
+/// Like [`RelayStdoutTo`], but relays the child's `stderr` to the given writer,
+/// instead of the parent's `stderr`:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// run!(%"sh -c", "echo foo >&2", RelayStderrTo(std::io::sink()));
+/// // writes nothing to the parent's stderr
+/// ```
+///
+/// Commands logged with [`LogCommand`] are still written to the parent's `stderr`.
+#[derive(Debug)]
+pub struct RelayStderrTo<W: Write + Send + 'static>(pub W);
+
+impl<W> Input for RelayStderrTo<W>
+where
+    W: Write + Send + 'static,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.relay_stderr_to = Some(SharedWriter::new(Box::new(self.0)));
+    }
+}
+
+/// Discards the output of the child process,
+/// instead of relaying it to the parent's `stdout` and `stderr`.
+/// This is useful for commands whose errors you want to handle yourself,
+/// without their output cluttering the terminal:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let result: Result<(), Error> = run_result!(%"ls does-not-exist", Quiet);
+/// if result.is_err() {
+///     eprintln!("does-not-exist is missing");
+/// }
+/// ```
+///
+/// Unlike capturing output with e.g. [`Stderr`](crate::output::Stderr),
+/// the output is dropped as soon as it arrives, so it's not buffered in memory.
+/// Capturing outputs still work as usual together with [`Quiet`],
+/// but [`StdoutTee`](crate::output::StdoutTee) will not relay anything.
+/// Output that is explicitly redirected, e.g. with [`StdoutFile`] or [`RelayStderrTo`],
+/// is not affected.
+/// When used for a whole [`Pipe`], all of its commands are silenced.
+#[derive(Debug, Clone, Copy)]
+pub struct Quiet;
+
+impl Input for Quiet {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.quiet = true;
+    }
+}
+
+/// Connects the child's `stdin`, `stdout` and `stderr` directly
+/// to the parent's standard streams, instead of relaying them through pipes.
+/// This is needed for interactive child processes that use the terminal,
+/// like editors, pagers or `ssh`:
+///
+/// ```no_run
+/// use cradle::prelude::*;
+///
+/// let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+/// run!(editor, "notes.txt", Interactive);
+/// ```
+///
+/// Since the output doesn't go through `cradle`, it can't be captured.
+/// Combining [`Interactive`] with outputs like
+/// [`StdoutTrimmed`](crate::output::StdoutTrimmed) or [`Stderr`](crate::output::Stderr)
+/// results in an [`Error::InteractiveOutputCaptured`].
+/// Outputs that don't capture anything, like [`Status`](crate::output::Status), work as usual.
+/// For the same reason, inputs that redirect output,
+/// e.g. [`StdoutFile`] or [`RelayStdoutTo`], don't receive anything.
+/// Input given with e.g. [`Stdin`] is still written to the child's `stdin`.
+/// When used for a whole [`Pipe`], the first command inherits `stdin`,
+/// the last command inherits `stdout`, and all commands inherit `stderr`.
+#[derive(Debug, Clone, Copy)]
+pub struct Interactive;
+
+impl Input for Interactive {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.interactive = true;
+    }
+}
+
+/// Redirects the child's `stderr` into its `stdout`,
+/// like `2>&1` in a shell.
+/// This is useful for capturing everything a child process writes
+/// in a single [`StdoutUntrimmed`](crate::output::StdoutUntrimmed):
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let StdoutTrimmed(output) = run_output!(%"sh -c", "echo foo >&2", StderrToStdout);
+/// assert_eq!(output, "foo");
+/// ```
+///
+/// Both streams are merged in the parent process,
+/// in the order in which the output arrives.
+/// When [`StderrToStdout`] is used, [`Stderr`](crate::output::Stderr)
+/// will always be empty, and [`StderrFile`] has no effect.
+#[derive(Debug, Clone, Copy)]
+pub struct StderrToStdout;
+
+impl Input for StderrToStdout {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.redirect_stderr_to_stdout = true;
+    }
+}
+
+/// Prepends the given prefix to every line that the child process
+/// writes to `stderr`, when relaying it to the parent's `stderr`.
+/// This is useful for telling apart the output of multiple child processes:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// run!(%"sh -c", "echo foo >&2", StderrPrefix("[worker-1] "));
+/// // writes '[worker-1] foo' to stderr
+/// ```
+///
+/// Incomplete lines are buffered until they're complete,
+/// or until the child process exits.
+/// [`StderrPrefix`] doesn't affect captured `stderr`
+/// (e.g. with [`Stderr`](crate::output::Stderr)),
+/// [`StderrFile`] or [`StderrToStdout`].
+/// If given multiple times, the last prefix is used.
+#[derive(Debug, Clone)]
+pub struct StderrPrefix<T: AsRef<str>>(pub T);
+
+impl<T> Input for StderrPrefix<T>
+where
+    T: AsRef<str>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.stderr_prefix = Some(self.0.as_ref().to_string());
+    }
+}
+
+/// Captures the child's `stderr` for error messages only.
+/// If the child process exits with a non-zero exit code,
+/// the captured `stderr` is included in the returned
+/// [`Error::NonZeroExitCode`](crate::Error::NonZeroExitCode):
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// # #[cfg(unix)]
+/// # {
+/// let result: Result<(), Error> =
+///     run_result!(%"sh -c", "echo oops >&2; false", CaptureStderrOnError);
+/// assert_eq!(
+///     result.unwrap_err().to_string(),
+///     "sh -c 'echo oops >&2; false':\n  exited with exit code: 1\n  stderr:\n    oops"
+/// );
+/// # }
+/// ```
+///
+/// On success, the captured `stderr` is discarded,
+/// so -- unlike with [`Stderr`](crate::output::Stderr) --
+/// the return type stays the same.
+/// Like other ways of capturing `stderr`,
+/// this stops `stderr` from being relayed to the parent's `stderr`.
+#[derive(Debug, Clone, Copy)]
+pub struct CaptureStderrOnError;
+
+impl Input for CaptureStderrOnError {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.capture_stderr = true;
+    }
+}
+
+/// Only keeps the last `n` lines of the child's captured `stdout`,
+/// for example when using [`StdoutUntrimmed`](crate::output::StdoutUntrimmed):
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let StdoutUntrimmed(output) = run_output!("echo", "foo\nbar\nbaz", StdoutTail(2));
+/// assert_eq!(output, "bar\nbaz\n");
+/// ```
+///
+/// Earlier lines are dropped while the child process is running,
+/// so memory usage stays bounded for commands with a lot of output.
+/// [`StdoutTail`] doesn't affect relaying,
+/// so combined with [`StdoutTee`](crate::output::StdoutTee)
+/// all of the output is still relayed to the parent's `stdout`.
+/// Use [`StdoutTailed`](crate::output::StdoutTailed) to find out whether lines were dropped.
+#[derive(Debug, Clone, Copy)]
+pub struct StdoutTail(pub usize);
+
+impl Input for StdoutTail {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.stdout_tail = Some(self.0);
+    }
+}
+
+/// Configures the encoding that [`StdoutEncoded`](crate::output::StdoutEncoded)
+/// uses to decode the child's `stdout`:
+///
+/// ```
+/// # #[cfg(feature = "encoding")]
+/// # {
+/// use cradle::prelude::*;
+///
+/// let StdoutEncoded { stdout, .. } = run_output!(
+///     %"printf caf\\351",
+///     StdoutEncoding(encoding_rs::WINDOWS_1252)
+/// );
+/// assert_eq!(stdout, "café");
+/// # }
+/// ```
+///
+/// This is only available with the `encoding` feature enabled.
+#[cfg(feature = "encoding")]
+#[derive(Debug, Clone, Copy)]
+pub struct StdoutEncoding(pub &'static encoding_rs::Encoding);
+
+#[cfg(feature = "encoding")]
+impl Input for StdoutEncoding {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.stdout_encoding = Some(self.0);
+    }
+}
+
+/// Limits the number of bytes that are captured from the child process.
+/// Once the child process writes more than that,
+/// it's killed and an [`Error::OutputTooLarge`] is returned:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let result: Result<StdoutUntrimmed, Error> = run_result!("yes", MaxOutputSize(1000));
+/// assert_eq!(
+///     result.unwrap_err().to_string(),
+///     "yes:\n  captured output exceeded the maximum size of 1000 bytes"
+/// );
+/// ```
+///
+/// This protects the parent process from running out of memory
+/// when capturing the output of buggy or untrusted programs.
+/// The limit applies to all captured output streams of a child process together,
+/// e.g. to `stdout` and `stderr` when using [`CapturedOutput`](crate::CapturedOutput).
+/// Output that is only relayed (and not captured) doesn't count towards the limit.
+#[derive(Debug, Clone, Copy)]
+pub struct MaxOutputSize(pub usize);
+
+impl Input for MaxOutputSize {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.max_output_size = Some(self.0);
+    }
+}
+
+/// Configures the separator that [`Fields`](crate::Fields) uses
+/// to split the child's `stdout`:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let Fields(fields) = run_output!("echo", "foo:bar:baz", FieldSeparator(':'));
+/// assert_eq!(fields, vec!["foo", "bar", "baz"]);
+/// ```
+///
+/// Without [`FieldSeparator`], [`Fields`](crate::Fields) splits on whitespace.
+#[derive(Debug, Clone, Copy)]
+pub struct FieldSeparator(pub char);
+
+impl Input for FieldSeparator {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.field_separator = Some(self.0);
+    }
+}
+
+/// Calls the given function for every line that the child process
+/// writes to `stdout`, as soon as the line is complete:
+///
+/// ```
+/// use cradle::prelude::*;
+/// use std::sync::mpsc::channel;
+///
+/// let (sender, receiver) = channel();
+/// run!(
+///     "echo",
+///     "foo\nbar",
+///     OnStdoutLine(move |line: &str| sender.send(line.to_string()).unwrap())
+/// );
+/// assert_eq!(receiver.iter().collect::<Vec<_>>(), vec!["foo", "bar"]);
+/// ```
+///
+/// This is useful e.g. for showing progress while a long-running command is running.
+/// Lines are passed to the function without line endings (`\n` or `\r\n`),
+/// and invalid utf-8 is replaced with `U+FFFD`.
+/// A final line without a trailing newline is also passed to the function.
+///
+/// [`OnStdoutLine`] doesn't affect relaying or capturing `stdout`.
+/// When used with a [`Pipe`], only the `stdout` of the last command is passed to the function.
+#[derive(Debug, Clone)]
+pub struct OnStdoutLine<F: FnMut(&str) + Send + 'static>(pub F);
+
+impl<F> Input for OnStdoutLine<F>
+where
+    F: FnMut(&str) + Send + 'static,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config
+            .options
+            .on_stdout_line
+            .push(StrCallback::new(Box::new(self.0)));
+    }
+}
+
+/// Sets the size of the buffers that are used to read
+/// the child's `stdout` and `stderr`, in bytes.
+/// The default is 8 KiB.
+/// Larger buffers can improve throughput for child processes
+/// that write a lot of output:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let StdoutTrimmed(output) = run_output!(%"echo foo", ReadBufferSize(64 * 1024));
+/// assert_eq!(output, "foo");
+/// ```
+///
+/// One buffer of the given size is allocated for every output stream
+/// of the child process, independently of how much the child writes.
+/// A size of `0` is treated as `1`.
+#[derive(Debug, Clone, Copy)]
+pub struct ReadBufferSize(pub usize);
+
+impl Input for ReadBufferSize {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.read_buffer_size = self.0;
+    }
+}
+
+/// Adds an environment variable to the environment of the child process.
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let StdoutUntrimmed(output) = run_output!("env", Env("FOO", "bar"));
+/// assert!(output.contains("FOO=bar\n"));
+/// ```
+///
+/// Child processes inherit the environment of the parent process.
+/// [`Env`] only adds environment variables to that inherited environment.
+/// If the environment variable is also set in the parent process,
+/// it is overwritten by [`Env`].
+#[derive(Debug, Clone)]
+pub struct Env<Key, Value>(pub Key, pub Value)
+where
+    Key: AsRef<OsStr>,
     Value: AsRef<OsStr>;
 
 impl<Key, Value> Input for Env<Key, Value>
@@ -668,8 +3128,541 @@ where
     #[doc(hidden)]
     fn configure(self, config: &mut Config) {
         let Self(key, value) = self;
+        let key = key.as_ref().to_os_string();
+        config
+            .options
+            .removed_environment_variables
+            .retain(|removed| removed != &key);
+        config
+            .options
+            .added_environment_variables
+            .push((key, value.as_ref().to_os_string()));
+    }
+}
+
+/// Like [`Env`], but only adds the environment variable if the given value is `Some`.
+/// If it's `None`, [`EnvOpt`] does nothing:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let foo: Option<&str> = Some("foo");
+/// let bar: Option<&str> = None;
+/// let StdoutUntrimmed(output) = run_output!("env", EnvOpt("FOO", foo), EnvOpt("BAR", bar));
+/// assert!(output.contains("FOO=foo\n"));
+/// assert!(!output.contains("BAR="));
+/// ```
+///
+/// Note that `None` doesn't remove variables that are inherited from the parent process,
+/// or that were added before. Use [`EnvRemove`] for that.
+#[derive(Debug, Clone)]
+pub struct EnvOpt<Key, Value>(pub Key, pub Option<Value>)
+where
+    Key: AsRef<OsStr>,
+    Value: AsRef<OsStr>;
+
+impl<Key, Value> Input for EnvOpt<Key, Value>
+where
+    Key: AsRef<OsStr>,
+    Value: AsRef<OsStr>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        if let EnvOpt(key, Some(value)) = self {
+            Env(key, value).configure(config);
+        }
+    }
+}
+
+/// All entries of a [`BTreeMap`] are added to the environment of the child process,
+/// like with [`Env`]:
+///
+/// ```
+/// use cradle::prelude::*;
+/// use std::collections::BTreeMap;
+///
+/// let mut env_vars = BTreeMap::new();
+/// env_vars.insert("FOO", "foo");
+/// env_vars.insert("BAR", "bar");
+/// let StdoutUntrimmed(output) = run_output!("env", env_vars);
+/// assert!(output.contains("FOO=foo\n"));
+/// assert!(output.contains("BAR=bar\n"));
+/// ```
+///
+/// Note that unlike sequences like [`Vec`], maps are not used as arguments.
+impl<Key, Value> Input for BTreeMap<Key, Value>
+where
+    Key: AsRef<OsStr>,
+    Value: AsRef<OsStr>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        for (key, value) in self {
+            Env(key, value).configure(config);
+        }
+    }
+}
+
+/// All entries of a [`HashMap`] are added to the environment of the child process,
+/// like with [`Env`]:
+///
+/// ```
+/// use cradle::prelude::*;
+/// use std::collections::HashMap;
+///
+/// let mut env_vars = HashMap::new();
+/// env_vars.insert("FOO", "foo");
+/// let StdoutUntrimmed(output) = run_output!("env", env_vars);
+/// assert!(output.contains("FOO=foo\n"));
+/// ```
+///
+/// Note that unlike sequences like [`Vec`], maps are not used as arguments.
+impl<Key, Value, S> Input for HashMap<Key, Value, S>
+where
+    Key: AsRef<OsStr>,
+    Value: AsRef<OsStr>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        for (key, value) in self {
+            Env(key, value).configure(config);
+        }
+    }
+}
+
+/// Removes an environment variable from the environment of the child process.
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// std::env::set_var("FOO", "foo");
+/// let StdoutUntrimmed(output) = run_output!("env", EnvRemove("FOO"));
+/// assert!(!output.contains("FOO="));
+/// ```
+///
+/// This is useful to prevent child processes from inheriting
+/// environment variables from the parent process.
+///
+/// [`EnvRemove`] and [`Env`] are applied in order.
+/// So if a variable is first added with [`Env`] and then removed with [`EnvRemove`],
+/// it won't be set in the child process, and vice versa.
+#[derive(Debug, Clone)]
+pub struct EnvRemove<Key>(pub Key)
+where
+    Key: AsRef<OsStr>;
+
+impl<Key> Input for EnvRemove<Key>
+where
+    Key: AsRef<OsStr>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        let key = self.0.as_ref().to_os_string();
         config
+            .options
             .added_environment_variables
-            .push((key.as_ref().to_os_string(), value.as_ref().to_os_string()));
+            .retain(|(added, _)| added != &key);
+        config.options.removed_environment_variables.push(key);
+    }
+}
+
+/// Starts the child process with an empty environment,
+/// instead of inheriting the environment of the parent process.
+/// Variables can then be added explicitly with [`Env`]:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// # #[cfg(unix)]
+/// # {
+/// let StdoutUntrimmed(output) = run_output!("/usr/bin/env", ClearEnv, Env("FOO", "bar"));
+/// assert_eq!(output, "FOO=bar\n");
+/// # }
+/// ```
+///
+/// Like [`EnvRemove`], [`ClearEnv`] is applied in order,
+/// so it also removes variables that were added with [`Env`] before it.
+///
+/// Note that clearing the environment also clears `PATH`.
+/// Without `PATH` the executable may not be found,
+/// so it's recommended to either use an absolute path to the executable
+/// or to add `PATH` back with [`Env`].
+#[derive(Debug, Clone, Copy)]
+pub struct ClearEnv;
+
+impl Input for ClearEnv {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.clear_env = true;
+        config.options.added_environment_variables.clear();
+        config.options.removed_environment_variables.clear();
+    }
+}
+
+/// Prepends the given directories to the `PATH` of the child process,
+/// without modifying the environment of the parent process.
+/// This is useful e.g. for running helper executables
+/// that are bundled with your application:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// # #[cfg(unix)]
+/// # {
+/// let StdoutTrimmed(path) = run_output!(%"sh -c", "echo $PATH", WithPath(vec!["/foo/bin"]));
+/// assert!(path.starts_with("/foo/bin:"));
+/// # }
+/// ```
+///
+/// The new `PATH` is computed when the child process is spawned.
+/// It starts with the given directories (in order) and is followed by
+/// the child's `PATH` as it would be without [`WithPath`].
+/// So for example `PATH`s that are set with [`Env`] are respected,
+/// and with [`ClearEnv`] the `PATH` will only contain the given directories.
+/// The directories are joined with the platform's path separator.
+/// Joining fails with an [`Error::CommandIoError`]
+/// if one of the directories contains the path separator.
+#[derive(Debug, Clone)]
+pub struct WithPath<T: AsRef<Path>>(pub Vec<T>);
+
+impl<T> Input for WithPath<T>
+where
+    T: AsRef<Path>,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config
+            .options
+            .path_prefix
+            .extend(self.0.iter().map(|directory| directory.as_ref().to_owned()));
+    }
+}
+
+/// Connects the stdout of one command to the stdin of another command,
+/// like a pipe (`|`) in a shell.
+/// Both commands are spawned at the same time,
+/// and the output of the first command is passed to the second command
+/// directly, without buffering it in the parent process:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let StdoutTrimmed(output) = run_output!(Pipe(("echo", "foo\nbar"), ("grep", "bar")));
+/// assert_eq!(output, "bar");
+/// ```
+///
+/// All [`Output`]s -- like [`StdoutTrimmed`] or [`Status`] -- reflect the last command.
+/// Inputs given after the [`Pipe`] configure the last command as well,
+/// with the exception of [`Stdin`] and [`StdinFile`],
+/// which are written to the first command.
+/// Pipes can be nested to create longer pipelines.
+///
+/// If any command in the pipeline fails, the whole pipeline fails,
+/// and the error message contains the full pipeline.
+/// If multiple commands fail, the first failing command determines the error:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let result: Result<(), cradle::Error> = run_result!(Pipe("false", "cat"));
+/// assert_eq!(
+///     result.unwrap_err().to_string(),
+///     "false | cat:\n  exited with exit code: 1"
+/// );
+/// ```
+///
+/// [`StdoutTrimmed`]: crate::output::StdoutTrimmed
+/// [`Status`]: crate::output::Status
+#[derive(Debug, Clone)]
+pub struct Pipe<A, B>(pub A, pub B)
+where
+    A: Input,
+    B: Input;
+
+impl<A, B> Input for Pipe<A, B>
+where
+    A: Input,
+    B: Input,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        let mut source = Config::default();
+        self.0.configure(&mut source);
+        if let Some(previous_stages) = config.piped_from.take() {
+            source.pipe_from(previous_stages);
+        }
+        config.piped_from = Some(Box::new(source));
+        self.1.configure(config);
+    }
+}
+
+/// Treats the given exit codes as success,
+/// so that they don't result in an [`Error::NonZeroExitCode`].
+/// This is useful for commands like `grep`,
+/// that use non-zero exit codes for results that aren't errors:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// # #[cfg(unix)]
+/// # {
+/// let StdoutUntrimmed(output) = run_output!(%"grep foo", Stdin("bar\n"), OkExitCodes(vec![1]));
+/// assert_eq!(output, "");
+/// # }
+/// ```
+///
+/// Exit code `0` is always treated as success.
+/// If [`OkExitCodes`] is given multiple times, all given exit codes are allowed.
+/// Outputs like [`Status`](crate::Status) or [`i32`] still return the actual exit status.
+/// When used for a whole [`Pipe`], the exit codes are allowed for all of its commands.
+#[derive(Debug, Clone)]
+pub struct OkExitCodes(pub Vec<i32>);
+
+impl Input for OkExitCodes {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.ok_exit_codes.extend(self.0);
+    }
+}
+
+/// Re-runs the child process if it fails,
+/// up to `attempts` times in total,
+/// sleeping for `delay` between the attempts:
+///
+/// ```
+/// use cradle::prelude::*;
+/// use std::time::Duration;
+///
+/// run!(
+///     %"echo foo",
+///     Retry {
+///         attempts: 3,
+///         delay: Duration::from_millis(100),
+///     }
+/// );
+/// ```
+///
+/// This is useful for commands that fail sporadically, e.g. because they depend on the network.
+///
+/// Commands are retried when they exit with a non-zero exit code
+/// (see [`Error::NonZeroExitCode`]) or on I/O errors
+/// (see [`Error::CommandIoError`]).
+/// Missing executables (see [`Error::FileNotFound`]) are not retried.
+/// If all attempts fail, the error from the last attempt is returned.
+///
+/// Output of failed attempts that is not captured is relayed as usual.
+/// Note that a [`StdinReader`] is consumed by the first attempt,
+/// so later attempts don't receive its contents.
+#[derive(Debug, Clone, Copy)]
+pub struct Retry {
+    /// The maximum number of times the command is run.
+    pub attempts: u32,
+    /// How long to wait between attempts.
+    pub delay: Duration,
+}
+
+impl Input for Retry {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.retry = Some(self);
+    }
+}
+
+/// Terminates the child process if it's still running `after` the given duration
+/// and returns [`Error::Timeout`]:
+///
+/// ```
+/// use cradle::prelude::*;
+/// use std::time::Duration;
+///
+/// let result: Result<(), Error> = run_result!(
+///     %"sleep 10",
+///     Timeout {
+///         after: Duration::from_millis(100),
+///         grace: Duration::from_secs(1),
+///     }
+/// );
+/// match result {
+///   Err(Error::Timeout { .. }) => {}
+///   _ => panic!(),
+/// }
+/// ```
+///
+/// On unix, the child process is sent `SIGTERM` first,
+/// so that it gets the chance to shut down cleanly.
+/// If it's still running after the `grace` period, it's killed with `SIGKILL`.
+/// On windows, the child process is killed right away with `TerminateProcess`.
+/// For a [`Pipe`], all commands are terminated.
+///
+/// Processes started by the child process are not terminated.
+/// If they keep the child's output streams open,
+/// cradle stops waiting for the output after `after + grace`
+/// and returns an [`Error::Timeout`] as well,
+/// even if the child process itself exited in time.
+///
+/// The timeout applies to every attempt separately when used with [`Retry`].
+/// Timed out commands are not retried.
+#[derive(Debug, Clone, Copy)]
+pub struct Timeout {
+    /// How long the child process is allowed to run.
+    pub after: Duration,
+    /// How long to wait for the child process to exit after `SIGTERM`,
+    /// before killing it with `SIGKILL`.
+    pub grace: Duration,
+}
+
+impl Input for Timeout {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.timeout = Some(self);
+    }
+}
+
+/// Calls the given function with the [`std::process::Command`]
+/// that is used to spawn the child process, right before it's spawned.
+/// This is an escape hatch that allows to use features of [`std::process::Command`]
+/// (or of platform-specific extension traits like `CommandExt`)
+/// that `cradle` doesn't provide:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let StdoutTrimmed(output) = run_output!(
+///     "echo",
+///     ConfigureCommand(|command: &mut std::process::Command| {
+///         command.arg("foo");
+///     })
+/// );
+/// assert_eq!(output, "foo");
+/// ```
+///
+/// The function is called after `cradle` has configured the command,
+/// so it can override any of `cradle`'s settings.
+/// Be aware that this makes it easy to break `cradle`'s assumptions,
+/// e.g. by changing how `stdout` is connected.
+///
+/// The function is called once for every spawned child process,
+/// so multiple times when used with e.g. [`Retry`].
+#[derive(Debug, Clone)]
+pub struct ConfigureCommand<F: FnMut(&mut std::process::Command) + Send + 'static>(pub F);
+
+impl<F> Input for ConfigureCommand<F>
+where
+    F: FnMut(&mut std::process::Command) + Send + 'static,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config
+            .options
+            .configure_command
+            .push(CommandCallback::new(Box::new(self.0)));
+    }
+}
+
+/// Kills a child process that was started with [`Input::spawn`]
+/// when the returned [`SpawnedChild`] is dropped before being waited for:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// {
+///     let _server = ("sleep", "10", KillOnDrop).spawn().unwrap();
+///     // ... run tests against the server ...
+/// }
+/// // `sleep` has been killed here
+/// ```
+///
+/// This is useful e.g. in integration tests that start a service under test,
+/// to make sure the service doesn't outlive the test, even if the test panics.
+/// The child process is killed (with `SIGKILL` on unix) and reaped.
+///
+/// [`KillOnDrop`] has no effect on child processes that are run synchronously,
+/// e.g. with [`run!`].
+#[derive(Debug, Clone, Copy)]
+pub struct KillOnDrop;
+
+impl Input for KillOnDrop {
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config.options.kill_on_drop = true;
+    }
+}
+
+/// Calls the given function with the process id of the child process,
+/// right after it has been spawned and before waiting for it to finish:
+///
+/// ```
+/// use cradle::prelude::*;
+/// use std::sync::mpsc::channel;
+///
+/// let (sender, receiver) = channel();
+/// run!("true", OnSpawn(move |pid| sender.send(pid).unwrap()));
+/// let pid: u32 = receiver.recv().unwrap();
+/// # assert!(pid > 0);
+/// ```
+///
+/// The function is called on the thread that runs the child process,
+/// so the process id is available synchronously.
+/// This can be useful e.g. for writing pidfiles
+/// or for registering the child process with a supervisor.
+///
+/// When used with a [`Pipe`], the function is called for the command
+/// that the [`OnSpawn`] is part of.
+#[derive(Debug, Clone)]
+pub struct OnSpawn<F: FnMut(u32) + Send + 'static>(pub F);
+
+impl<F> Input for OnSpawn<F>
+where
+    F: FnMut(u32) + Send + 'static,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config
+            .options
+            .on_spawn
+            .push(SpawnCallback::new(Box::new(self.0)));
+    }
+}
+
+/// Calls the given function with the [`ChildOutput`](crate::ChildOutput)
+/// of the child process, after it has exited,
+/// but before it's converted into the requested [`Output`]:
+///
+/// ```
+/// use cradle::prelude::*;
+/// use std::sync::mpsc::channel;
+///
+/// let (sender, receiver) = channel();
+/// let on_exit = OnExit(move |output: &ChildOutput| {
+///     sender.send((output.exit_status(), output.duration())).unwrap();
+/// });
+/// let StdoutTrimmed(_) = run_output!(%"echo foo", on_exit);
+/// let (exit_status, duration) = receiver.recv().unwrap();
+/// assert!(exit_status.success());
+/// # let _ = duration;
+/// ```
+///
+/// The function is called independently of the [`Output`] type,
+/// and also for child processes that exit with a non-zero exit code
+/// or that time out with [`Timeout`].
+/// If the output couldn't be collected, e.g. because of [`MaxOutputSize`],
+/// the function is called without the captured output before the error is returned.
+/// So this can be used e.g. for recording metrics for all commands in a central place.
+/// It's called once for every attempt with [`Retry`].
+/// For a [`Pipe`], the function is called once with the output of the last command.
+/// It's not called for child processes started with [`Input::spawn`].
+#[derive(Debug, Clone)]
+pub struct OnExit<F: FnMut(&ChildOutput) + Send + 'static>(pub F);
+
+impl<F> Input for OnExit<F>
+where
+    F: FnMut(&ChildOutput) + Send + 'static,
+{
+    #[doc(hidden)]
+    fn configure(self, config: &mut Config) {
+        config
+            .options
+            .on_exit
+            .push(ExitCallback::new(Box::new(self.0)));
     }
 }
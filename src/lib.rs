@@ -99,9 +99,9 @@
 //! assert_eq!(output, "foo");
 //! ```
 //!
-//! Wrapping an argument of type `&str` in [`Split`] will cause `cradle` to first
-//! split it by whitespace and then use the resulting words as if they were passed
-//! into [`run_output!`] as separate arguments.
+//! Wrapping a string argument (e.g. of type `&str` or [`String`]) in [`Split`]
+//! will cause `cradle` to first split it by whitespace and then use the resulting
+//! words as if they were passed into [`run_output!`] as separate arguments.
 //!
 //! And -- since this is such a common case -- `cradle` provides a syntactic shortcut
 //! for [`Split`], the `%` symbol:
@@ -167,11 +167,12 @@
 //! use cradle::prelude::*;
 //!
 //! fn build() -> Result<(), Error> {
-//!     run_result!(%"which make")?;
-//!     run_result!(%"which gcc")?;
-//!     run_result!(%"which ld")?;
-//!     run_result!(%"make build")?;
-//!     Ok(())
+//!     // make sure build tools are installed
+//!     for tool in &["make", "gcc", "ld"] {
+//!         let result: Result<(), Error> = run_result!("which", *tool);
+//!         result?;
+//!     }
+//!     run_result!(%"make build")
 //! }
 //! ```
 //!
@@ -229,6 +230,7 @@
 
 pub mod child_output;
 mod collected_output;
+pub mod command;
 pub mod config;
 mod context;
 pub mod error;
@@ -236,6 +238,9 @@ pub mod input;
 mod macros;
 pub mod output;
 pub mod prelude;
+#[cfg(unix)]
+mod pty;
+pub mod spawned_child;
 
 include!("common_re_exports.rs.snippet");
 
@@ -309,6 +314,14 @@ mod tests {
         test_executable("test_executables_helper")
     }
 
+    fn quoted(argument: &str) -> String {
+        if cfg!(windows) {
+            format!("\"{}\"", argument)
+        } else {
+            format!("'{}'", argument)
+        }
+    }
+
     #[test]
     fn allows_to_execute_a_command() {
         in_temporary_directory(|| {
@@ -514,11 +527,104 @@ mod tests {
                 assert_eq!(
                     result.unwrap_err().to_string(),
                     format!(
-                        "{} 'invalid utf-8 stdout':\n  invalid utf-8 written to stdout",
-                        test_helper.display()
+                        "{} {}:\n  invalid utf-8 written to stdout",
+                        test_helper.display(),
+                        quoted("invalid utf-8 stdout")
+                    )
+                );
+            }
+        }
+
+        mod captured_stderr {
+            use super::*;
+            use pretty_assertions::assert_eq;
+
+            #[test]
+            fn is_included_in_non_zero_exit_code_errors() {
+                let result: Result<Stderr, Error> =
+                    run_result!(%"sh -c", "echo foo >&2; echo bar >&2; exit 42");
+                assert_eq!(
+                    result.unwrap_err().to_string(),
+                    format!(
+                        "sh -c {}:\n  exited with exit code: 42\n  stderr:\n    foo\n    bar",
+                        quoted("echo foo >&2; echo bar >&2; exit 42")
                     )
                 );
             }
+
+            #[test]
+            fn can_be_matched_against() {
+                let result: Result<Stderr, Error> = run_result!(%"sh -c", "echo foo >&2; false");
+                match result {
+                    Err(Error::NonZeroExitCode {
+                        captured_stderr, ..
+                    }) => assert_eq!(captured_stderr, Some("foo\n".to_string())),
+                    _ => panic!("should match Error::NonZeroExitCode"),
+                }
+            }
+
+            #[test]
+            fn is_none_when_stderr_is_not_captured() {
+                let result: Result<(), Error> = run_result_with_context_unit(
+                    Context::test(),
+                    (Split("sh -c"), "echo foo >&2; false"),
+                );
+                match result {
+                    Err(Error::NonZeroExitCode {
+                        captured_stderr, ..
+                    }) => assert_eq!(captured_stderr, None),
+                    _ => panic!("should match Error::NonZeroExitCode"),
+                }
+            }
+
+            #[test]
+            fn can_be_captured_for_errors_only() {
+                let result: Result<(), Error> = run_result_with_context_unit(
+                    Context::test(),
+                    (Split("sh -c"), "echo foo >&2; false", CaptureStderrOnError),
+                );
+                match result {
+                    Err(Error::NonZeroExitCode {
+                        captured_stderr, ..
+                    }) => assert_eq!(captured_stderr, Some("foo\n".to_string())),
+                    _ => panic!("should match Error::NonZeroExitCode"),
+                }
+            }
+
+            #[test]
+            fn capture_stderr_on_error_does_not_relay_stderr() {
+                let context = Context::test();
+                let result: Result<(), Error> = run_result_with_context_unit(
+                    context.clone(),
+                    (Split("sh -c"), "echo foo >&2", CaptureStderrOnError),
+                );
+                result.unwrap();
+                assert_eq!(context.stderr(), "");
+            }
+
+            #[test]
+            fn capture_stderr_on_error_can_be_combined_with_stderr() {
+                let Stderr(stderr) = run_output!(%"sh -c", "echo foo >&2", CaptureStderrOnError);
+                assert_eq!(stderr, "foo\n");
+            }
+
+            #[test]
+            fn empty_stderr_is_not_included_in_the_message() {
+                let result: Result<Stderr, Error> = run_result!("false");
+                assert_eq!(
+                    result.unwrap_err().to_string(),
+                    "false:\n  exited with exit code: 1"
+                );
+            }
+
+            #[test]
+            fn long_stderr_is_truncated_in_the_message() {
+                let result: Result<Stderr, Error> = run_result!(%"sh -c", "seq 1 1000 >&2; false");
+                let message = result.unwrap_err().to_string();
+                assert!(message.contains("\n    1\n    2\n"), "{}", message);
+                assert!(message.ends_with("\n    ..."), "{}", message);
+                assert!(!message.contains("\n    1000"), "{}", message);
+            }
         }
 
         mod whitespace_in_executable_note {
@@ -686,6 +792,61 @@ mod tests {
             let StdoutTrimmed(output) = run_output!(vec![vec!["echo"], vec!["foo", "bar"]]);
             assert_eq!(output, "foo bar");
         }
+
+        #[test]
+        fn args_from_iterators() {
+            let StdoutTrimmed(output) =
+                run_output!("echo", Args(vec!["foo", "bar"].into_iter().rev()));
+            assert_eq!(output, "bar foo");
+        }
+
+        #[test]
+        fn args_from_collections() {
+            let args: std::collections::VecDeque<String> =
+                vec!["foo".to_string(), "bar".to_string()].into();
+            let StdoutTrimmed(output) = run_output!("echo", Args(&args));
+            assert_eq!(output, "foo bar");
+        }
+
+        #[test]
+        fn args_of_non_strings() {
+            let context = Context::test();
+            let StdoutTrimmed(stdout) = run_result_with_context(
+                context.clone(),
+                (Args(Some(LogCommand)), Split("echo foo")),
+            )
+            .unwrap();
+            assert_eq!(stdout, "foo");
+            assert_eq!(context.stderr(), "+ echo foo\n");
+        }
+
+        #[test]
+        fn elements_in_args_are_not_split_by_whitespace() {
+            in_temporary_directory(|| {
+                run!("touch", Args(vec!["foo bar"].into_iter()));
+                assert!(PathBuf::from("foo bar").exists());
+            });
+        }
+
+        #[test]
+        fn vector_iterators() {
+            let StdoutTrimmed(output) = run_output!("echo", vec!["foo", "bar"].into_iter());
+            assert_eq!(output, "foo bar");
+        }
+
+        #[test]
+        fn os_strings_from_iterators_are_not_split_by_whitespace() {
+            in_temporary_directory(|| {
+                let args: Vec<OsString> = vec!["foo bar".into(), "baz".into()];
+                run!("touch", args.into_iter());
+                assert!(PathBuf::from("foo bar").exists());
+                assert!(PathBuf::from("baz").exists());
+                let paths = [PathBuf::from("a b"), PathBuf::from("c")];
+                run!("touch", Args(paths.iter().map(|path| path.as_os_str())));
+                assert!(PathBuf::from("a b").exists());
+                assert!(PathBuf::from("c").exists());
+            });
+        }
     }
 
     mod strings {
@@ -720,644 +881,5571 @@ mod tests {
                 assert!(PathBuf::from("filename with spaces").exists());
             });
         }
-    }
-
-    mod os_strings {
-        use super::*;
 
         #[test]
-        fn works_for_os_string() {
-            run!(OsString::from("true"));
+        fn works_for_cow_str() {
+            use std::borrow::Cow;
+            let borrowed: Cow<str> = Cow::Borrowed("echo");
+            let owned: Cow<str> = Cow::Owned("foo".to_string());
+            let StdoutTrimmed(output) = run_output!(borrowed, owned);
+            assert_eq!(output, "foo");
         }
 
         #[test]
-        fn works_for_os_str() {
-            run!(OsStr::new("true"));
+        fn does_not_split_cow_strs() {
+            use std::borrow::Cow;
+            in_temporary_directory(|| {
+                let argument: Cow<str> = Cow::Borrowed("filename with spaces");
+                run!("touch", argument);
+                assert!(PathBuf::from("filename with spaces").exists());
+            });
         }
     }
 
-    mod stdout {
+    mod raw {
         use super::*;
-        use std::{thread, time::Duration};
-
-        #[test]
-        fn relays_stdout_by_default() {
-            let context = Context::test();
-            run_result_with_context_unit(context.clone(), Split("echo foo")).unwrap();
-            assert_eq!(context.stdout(), "foo\n");
-        }
 
         #[test]
-        fn relays_stdout_for_non_zero_exit_codes() {
-            let context = Context::test();
-            let _ = run_result_with_context_unit(
-                context.clone(),
-                (test_helper(), "output foo and exit with 42"),
+        fn passes_arguments_verbatim() {
+            let Lines(output) = run_output!(
+                "printf",
+                "[%s]\\n",
+                Raw(vec!["foo bar".into(), "".into(), "  ".into()])
             );
-            assert_eq!(context.stdout(), "foo\n");
+            assert_eq!(output, vec!["[foo bar]", "[]", "[  ]"]);
         }
 
         #[test]
-        fn streams_stdout() {
-            in_temporary_directory(|| {
-                let context = Context::test();
-                let context_clone = context.clone();
-                let thread = thread::spawn(|| {
-                    run_result_with_context_unit(
-                        context_clone,
-                        (test_helper(), "stream chunk then wait for file"),
-                    )
-                    .unwrap();
-                });
-                while (context.stdout()) != "foo\n" {
-                    thread::sleep(Duration::from_secs_f32(0.05));
-                }
-                run!(%"touch file");
-                thread.join().unwrap();
-            });
+        fn can_be_used_for_the_executable() {
+            let StdoutTrimmed(output) = run_output!(Raw(vec!["echo".into(), "foo".into()]));
+            assert_eq!(output, "foo");
         }
 
         #[test]
-        fn does_not_relay_stdout_when_collecting_into_string() {
-            let context = Context::test();
-            let StdoutTrimmed(_) =
-                run_result_with_context(context.clone(), Split("echo foo")).unwrap();
-            assert_eq!(context.stdout(), "");
-        }
+        #[cfg(unix)]
+        fn allows_invalid_utf8() {
+            use std::os::unix::ffi::OsStringExt;
 
-        #[test]
-        fn does_not_relay_stdout_when_collecting_into_result_of_string() {
-            let context = Context::test();
-            let _: Result<StdoutTrimmed, Error> =
-                run_result_with_context(context.clone(), Split("echo foo"));
-            assert_eq!(context.stdout(), "");
+            let argument = OsString::from_vec(vec![b'f', 0xff]);
+            let StdoutBytes(output) = run_output!("echo", Raw(vec![argument]));
+            assert_eq!(output, vec![b'f', 0xff, b'\n']);
         }
     }
 
-    mod stderr {
+    mod when {
         use super::*;
-        use pretty_assertions::assert_eq;
-        use std::{thread, time::Duration};
 
         #[test]
-        fn relays_stderr_by_default() {
+        fn uses_the_input_when_the_condition_is_true() {
             let context = Context::test();
-            run_result_with_context_unit(context.clone(), (test_helper(), "write to stderr"))
+            run_result_with_context_unit(context.clone(), (When(true, LogCommand), "true"))
                 .unwrap();
-            assert_eq!(context.stderr(), "foo\n");
+            assert_eq!(context.stderr(), "+ true\n");
         }
 
         #[test]
-        fn relays_stderr_for_non_zero_exit_codes() {
+        fn ignores_the_input_when_the_condition_is_false() {
             let context = Context::test();
-            let _: Result<(), Error> = run_result_with_context(
-                context.clone(),
-                (test_helper(), "write to stderr and exit with 42"),
+            run_result_with_context_unit(context.clone(), (When(false, LogCommand), "true"))
+                .unwrap();
+            assert_eq!(context.stderr(), "");
+        }
+
+        #[test]
+        fn works_for_arguments() {
+            let StdoutTrimmed(output) =
+                run_output!("echo", "foo", When(false, "bar"), When(true, "baz"));
+            assert_eq!(output, "foo baz");
+        }
+
+        #[test]
+        fn works_for_environment_variables() {
+            let StdoutTrimmed(output) = run_output!(
+                test_helper(),
+                "echo",
+                "FOO",
+                When(true, Env("FOO", "foo")),
+                When(false, Env("FOO", "bar")),
             );
-            assert_eq!(context.stderr(), "foo\n");
+            assert_eq!(output, "foo");
         }
 
         #[test]
-        fn streams_stderr() {
-            in_temporary_directory(|| {
-                let context = Context::test();
-                let context_clone = context.clone();
-                let thread = thread::spawn(|| {
-                    run_result_with_context_unit(
-                        context_clone,
-                        (test_helper(), "stream chunk to stderr then wait for file"),
-                    )
-                    .unwrap();
-                });
-                loop {
-                    let expected = "foo\n";
-                    let stderr = context.stderr();
-                    if stderr == expected {
-                        break;
-                    }
-                    assert!(
-                        stderr.len() <= expected.len(),
-                        "expected: {}, got: {}",
-                        expected,
-                        stderr
-                    );
-                    thread::sleep(Duration::from_secs_f32(0.05));
-                }
-                run!(%"touch file");
-                thread.join().unwrap();
-            });
+        fn skipping_the_executable_does_not_run_anything() {
+            let context = Context::test();
+            run_result_with_context_unit(
+                context.clone(),
+                (When(false, Split("echo foo")), LogCommand),
+            )
+            .unwrap();
+            assert_eq!(context.stdout(), "");
+            assert_eq!(context.stderr(), "");
         }
 
         #[test]
-        fn capture_stderr() {
-            let Stderr(stderr) = run_output!(test_helper(), "write to stderr");
-            assert_eq!(stderr, "foo\n");
+        fn skipping_the_executable_results_in_empty_successful_outputs() {
+            let (StdoutUntrimmed(stdout), Stderr(stderr), Status(status)) =
+                run_output!(When(false, Split("echo foo")));
+            assert_eq!(stdout, "");
+            assert_eq!(stderr, "");
+            assert!(status.success());
+            let CombinedOutput(output) = run_output!(When(false, Split("echo foo")));
+            assert_eq!(output, "");
         }
 
         #[test]
-        fn assumes_stderr_is_utf_8() {
-            let result: Result<Stderr, Error> = run_result!(test_helper(), "invalid utf-8 stderr");
-            assert_eq!(
-                result.unwrap_err().to_string(),
-                format!(
-                    "{} 'invalid utf-8 stderr':\n  invalid utf-8 written to stderr",
-                    test_helper().display(),
-                )
-            );
+        fn skipping_failing_commands_succeeds() {
+            let result: Result<(), Error> = run_result!(When(false, "false"));
+            assert!(result.is_ok());
         }
 
         #[test]
-        #[cfg(not(windows))]
-        fn does_allow_invalid_utf_8_to_stderr_when_not_captured() {
-            run!(test_helper(), "invalid utf-8 stderr");
+        fn inputs_without_an_executable_still_produce_no_executable_given() {
+            let result: Result<(), Error> = run_result!(When(true, LogCommand));
+            match result {
+                Err(Error::NoExecutableGiven) => {}
+                _ => panic!("should match Error::NoExecutableGiven"),
+            }
         }
 
         #[test]
-        fn does_not_relay_stderr_when_catpuring() {
-            let context = Context::test();
-            let Stderr(_) =
-                run_result_with_context(context.clone(), (test_helper(), "write to stderr"))
-                    .unwrap();
-            assert_eq!(context.stderr(), "");
+        fn spawning_skipped_commands_produces_no_executable_given() {
+            let result = When(false, "true").spawn();
+            match result {
+                Err(Error::NoExecutableGiven) => {}
+                _ => panic!("should match Error::NoExecutableGiven"),
+            }
         }
     }
 
-    mod log_commands {
+    mod numbers {
         use super::*;
 
         #[test]
-        fn logs_simple_commands() {
-            let context = Context::test();
-            run_result_with_context_unit(context.clone(), (LogCommand, "true")).unwrap();
-            assert_eq!(context.stderr(), "+ true\n");
+        fn integers_are_passed_as_arguments() {
+            let StdoutTrimmed(output) = run_output!("echo", 8080_u16, -42_i64, 7_usize);
+            assert_eq!(output, "8080 -42 7");
         }
 
         #[test]
-        fn logs_commands_with_arguments() {
-            let context = Context::test();
-            run_result_with_context_unit(context.clone(), (LogCommand, Split("echo foo"))).unwrap();
-            assert_eq!(context.stderr(), "+ echo foo\n");
+        fn integer_literals_without_suffix() {
+            let StdoutTrimmed(output) = run_output!("echo", 42);
+            assert_eq!(output, "42");
         }
 
         #[test]
-        fn quotes_arguments_with_spaces() {
-            let context = Context::test();
-            run_result_with_context_unit(context.clone(), (LogCommand, "echo", "foo bar")).unwrap();
-            assert_eq!(context.stderr(), "+ echo 'foo bar'\n");
+        fn floats_are_passed_as_arguments() {
+            let StdoutTrimmed(output) = run_output!("echo", 1.5_f64, 0.25_f32);
+            assert_eq!(output, "1.5 0.25");
         }
 
         #[test]
-        fn quotes_empty_arguments() {
-            let context = Context::test();
-            run_result_with_context_unit(context.clone(), (LogCommand, "echo", "")).unwrap();
-            assert_eq!(context.stderr(), "+ echo ''\n");
+        fn every_number_is_a_single_argument() {
+            let args = preview_args(("echo", 1_u8, 2.0_f64, vec![3_i32, 4]));
+            assert_eq!(args, vec!["echo", "1", "2", "3", "4"]);
         }
 
         #[test]
-        #[cfg(unix)]
-        fn arguments_with_invalid_utf8_will_be_logged_with_lossy_conversion() {
-            use std::{ffi::OsStr, os::unix::prelude::OsStrExt, path::Path};
-            let context = Context::test();
-            let argument_with_invalid_utf8: &OsStr =
-                OsStrExt::from_bytes(&[102, 111, 111, 0x80, 98, 97, 114]);
-            let argument_with_invalid_utf8: &Path = argument_with_invalid_utf8.as_ref();
-            run_result_with_context_unit(
-                context.clone(),
-                (LogCommand, "echo", argument_with_invalid_utf8),
-            )
+        fn works_for_references() {
+            let port: u16 = 8080;
+            let StdoutTrimmed(output) = run_output!("echo", &port);
+            assert_eq!(output, "8080");
+        }
+    }
+
+    mod option {
+        use super::*;
+
+        #[test]
+        fn uses_the_input_for_some() {
+            let StdoutTrimmed(output) = run_output!("echo", "foo", Some("bar"));
+            assert_eq!(output, "foo bar");
+        }
+
+        #[test]
+        fn ignores_none() {
+            let StdoutTrimmed(output) = run_output!("echo", "foo", None::<&str>);
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn works_for_environment_variables() {
+            let StdoutTrimmed(output) = run_output!(
+                test_helper(),
+                "echo",
+                "FOO",
+                Some(Env("FOO", "foo")),
+                None::<Env<&str, &str>>,
+            );
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn works_inside_of_tuples() {
+            let flags = (Some("bar"), None::<String>, Some("baz".to_string()));
+            let StdoutTrimmed(output) = run_output!("echo", "foo", flags);
+            assert_eq!(output, "foo bar baz");
+        }
+    }
+
+    mod result {
+        use super::*;
+
+        fn error() -> Result<&'static str, Error> {
+            Err(Error::NoExecutableGiven)
+        }
+
+        #[test]
+        fn uses_the_input_for_ok() {
+            let StdoutTrimmed(output) = run_output!("echo", "foo", Ok::<_, Error>("bar"));
+            assert_eq!(output, "foo bar");
+        }
+
+        #[test]
+        fn returns_the_error_for_err() {
+            let result: Result<(), Error> = run_result!("echo", error());
+            match result {
+                Err(Error::NoExecutableGiven) => {}
+                result => panic!("expected NoExecutableGiven, got: {:?}", result),
+            }
+        }
+
+        #[test]
+        fn does_not_run_the_command_for_err() {
+            in_temporary_directory(|| {
+                let result: Result<(), Error> = run_result!(%"touch file", error());
+                assert!(result.is_err());
+                assert!(!PathBuf::from("file").exists());
+            });
+        }
+
+        #[test]
+        fn does_not_log_the_command_for_err() {
+            let context = Context::test();
+            let result =
+                run_result_with_context_unit(context.clone(), (LogCommand, "true", error()));
+            assert!(result.is_err());
+            assert_eq!(context.stderr(), "");
+        }
+
+        #[test]
+        fn returns_the_first_error() {
+            let result: Result<(), Error> = run_result!(
+                "echo",
+                error(),
+                Err::<&str, _>(Error::MultipleExecutablesGiven {
+                    full_command: "foo".to_string()
+                })
+            );
+            match result {
+                Err(Error::NoExecutableGiven) => {}
+                result => panic!("expected NoExecutableGiven, got: {:?}", result),
+            }
+        }
+
+        #[test]
+        fn works_for_pipeline_stages() {
+            let result: Result<(), Error> = run_result!(Pipe(("echo", error()), "cat"));
+            match result {
+                Err(Error::NoExecutableGiven) => {}
+                result => panic!("expected NoExecutableGiven, got: {:?}", result),
+            }
+        }
+
+        #[test]
+        fn is_not_retried() {
+            in_temporary_directory(|| {
+                let Status(exit_status) = run_output!("false");
+                let result: Result<(), Error> = run_result!(
+                    %"touch file",
+                    Err::<&str, _>(Error::NonZeroExitCode {
+                        full_command: "foo".to_string(),
+                        exit_status,
+                        captured_stderr: None,
+                    }),
+                    Retry {
+                        attempts: 3,
+                        delay: std::time::Duration::from_millis(0),
+                    },
+                );
+                assert!(result.is_err());
+                assert!(!PathBuf::from("file").exists());
+            });
+        }
+
+        #[test]
+        fn works_with_spawn() {
+            let result = ("true", error()).spawn();
+            match result {
+                Err(Error::NoExecutableGiven) => {}
+                result => panic!("expected NoExecutableGiven, got: {:?}", result.is_ok()),
+            }
+        }
+
+        #[test]
+        fn accepts_io_errors() {
+            in_temporary_directory(|| {
+                let result: Result<(), Error> =
+                    run_result!("ls", std::fs::canonicalize("does-not-exist"));
+                match result {
+                    Err(Error::CommandIoError { message, source }) => {
+                        assert_eq!(source.kind(), std::io::ErrorKind::NotFound);
+                        assert_eq!(message, source.to_string());
+                    }
+                    result => panic!("expected CommandIoError, got: {:?}", result),
+                }
+            });
+        }
+    }
+
+    mod boxed_input {
+        use super::*;
+
+        #[test]
+        fn allows_to_collect_inputs_of_different_types() {
+            let context = Context::test();
+            let inputs = vec![
+                BoxedInput::new(test_helper()),
+                BoxedInput::new("echo"),
+                BoxedInput::new(String::from("FOO")),
+                BoxedInput::new(Env("FOO", "foo")),
+                BoxedInput::new(LogCommand),
+            ];
+            let StdoutTrimmed(output) = run_result_with_context(context.clone(), inputs).unwrap();
+            assert_eq!(output, "foo");
+            assert!(context.stderr().ends_with(" echo FOO\n"));
+        }
+
+        #[test]
+        fn applies_inputs_in_order() {
+            let inputs = vec![
+                BoxedInput::new(Split("echo foo")),
+                BoxedInput::new(When(false, "bar")),
+                BoxedInput::new("baz"),
+            ];
+            let StdoutTrimmed(output) = run_output!(inputs);
+            assert_eq!(output, "foo baz");
+        }
+
+        #[test]
+        fn can_be_nested() {
+            let StdoutTrimmed(output) =
+                run_output!(BoxedInput::new(vec![BoxedInput::new(Split("echo foo"))]));
+            assert_eq!(output, "foo");
+        }
+    }
+
+    mod relay_to {
+        use super::*;
+        use pretty_assertions::assert_eq;
+        use std::{
+            io,
+            sync::{Arc, Mutex},
+            thread,
+            time::Duration,
+        };
+
+        #[derive(Clone, Default)]
+        struct Writer(Arc<Mutex<Vec<u8>>>);
+
+        impl Writer {
+            fn contents(&self) -> String {
+                String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+            }
+        }
+
+        impl io::Write for Writer {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        #[test]
+        fn relays_stdout_to_the_given_writer() {
+            let context = Context::test();
+            let writer = Writer::default();
+            run_result_with_context_unit(
+                context.clone(),
+                (Split("echo foo"), RelayStdoutTo(writer.clone())),
+            )
+            .unwrap();
+            assert_eq!(writer.contents(), "foo\n");
+            assert_eq!(context.stdout(), "");
+        }
+
+        #[test]
+        fn relays_stderr_to_the_given_writer() {
+            let context = Context::test();
+            let writer = Writer::default();
+            run_result_with_context_unit(
+                context.clone(),
+                (
+                    test_helper(),
+                    "write to stderr",
+                    RelayStderrTo(writer.clone()),
+                ),
+            )
+            .unwrap();
+            assert_eq!(writer.contents(), "foo\n");
+            assert_eq!(context.stderr(), "");
+        }
+
+        #[test]
+        fn streams_output_while_the_child_is_running() {
+            in_temporary_directory(|| {
+                let writer = Writer::default();
+                let writer_clone = writer.clone();
+                let thread = thread::spawn(|| {
+                    run!(
+                        test_helper(),
+                        "stream chunk then wait for file",
+                        RelayStdoutTo(writer_clone)
+                    );
+                });
+                while writer.contents() != "foo\n" {
+                    thread::sleep(Duration::from_secs_f32(0.05));
+                }
+                run!(%"touch file");
+                thread.join().unwrap();
+            });
+        }
+
+        #[test]
+        fn does_not_receive_captured_output() {
+            let writer = Writer::default();
+            let StdoutTrimmed(output) = run_output!(%"echo foo", RelayStdoutTo(writer.clone()));
+            assert_eq!(output, "foo");
+            assert_eq!(writer.contents(), "");
+        }
+
+        #[test]
+        fn receives_teed_output() {
+            let writer = Writer::default();
+            let StdoutTee(output) = run_output!(%"echo foo", RelayStdoutTo(writer.clone()));
+            assert_eq!(output, "foo\n");
+            assert_eq!(writer.contents(), "foo\n");
+        }
+
+        #[test]
+        fn receives_redirected_stderr() {
+            let writer = Writer::default();
+            run!(
+                test_helper(),
+                "write to stderr",
+                StderrToStdout,
+                RelayStdoutTo(writer.clone())
+            );
+            assert_eq!(writer.contents(), "foo\n");
+        }
+
+        #[test]
+        fn does_not_receive_logged_commands() {
+            let context = Context::test();
+            let writer = Writer::default();
+            run_result_with_context_unit(
+                context.clone(),
+                (LogCommand, "true", RelayStderrTo(writer.clone())),
+            )
+            .unwrap();
+            assert_eq!(writer.contents(), "");
+            assert_eq!(context.stderr(), "+ true\n");
+        }
+    }
+
+    mod stdout_to {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn writes_stdout_into_the_given_writer_and_returns_it() {
+            let context = Context::test();
+            let StdoutTo(writer): StdoutTo<Vec<u8>> =
+                run_result_with_context(context.clone(), (Split("echo foo"), StdoutTo(Vec::new())))
+                    .unwrap();
+            assert_eq!(writer, b"foo\n");
+            assert_eq!(context.stdout(), "");
+        }
+
+        #[test]
+        fn can_be_used_only_as_an_input() {
+            let context = Context::test();
+            run_result_with_context_unit(
+                context.clone(),
+                (Split("echo foo"), StdoutTo(Vec::new())),
+            )
+            .unwrap();
+            assert_eq!(context.stdout(), "");
+        }
+
+        #[test]
+        fn works_for_large_outputs() {
+            let StdoutTo(writer): StdoutTo<Vec<u8>> =
+                run_output!("cat", Stdin(vec![b'a'; 1_000_000]), StdoutTo(Vec::new()));
+            assert_eq!(writer.len(), 1_000_000);
+        }
+
+        #[test]
+        fn can_be_combined_with_other_outputs() {
+            let (StdoutTo(writer), Status(status)): (StdoutTo<Vec<u8>>, Status) =
+                run_output!(%"echo foo", StdoutTo(Vec::new()));
+            assert_eq!(writer, b"foo\n");
+            assert!(status.success());
+        }
+
+        #[test]
+        fn missing_inputs_produce_errors() {
+            let result: Result<StdoutTo<Vec<u8>>, Error> = run_result!(%"echo foo");
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "echo foo:\n  StdoutTo was used as the return type, but not given as an input"
+            );
+        }
+
+        #[test]
+        fn writers_of_other_types_produce_errors() {
+            let result: Result<StdoutTo<Vec<u8>>, Error> =
+                run_result!(%"echo foo", StdoutTo(std::io::sink()));
+            match result {
+                Err(Error::StdoutToNotGiven { .. }) => {}
+                _ => panic!("should match Error::StdoutToNotGiven"),
+            }
+        }
+    }
+
+    mod os_strings {
+        use super::*;
+
+        #[test]
+        fn works_for_os_string() {
+            run!(OsString::from("true"));
+        }
+
+        #[test]
+        fn works_for_os_str() {
+            run!(OsStr::new("true"));
+        }
+
+        #[test]
+        fn works_for_cow_os_str() {
+            use std::borrow::Cow;
+            let borrowed: Cow<OsStr> = Cow::Borrowed(OsStr::new("echo"));
+            let owned: Cow<OsStr> = Cow::Owned(OsString::from("foo bar"));
+            let StdoutTrimmed(output) = run_output!(borrowed, owned);
+            assert_eq!(output, "foo bar");
+        }
+    }
+
+    mod stdout {
+        use super::*;
+        use std::{thread, time::Duration};
+
+        #[test]
+        fn relays_stdout_by_default() {
+            let context = Context::test();
+            run_result_with_context_unit(context.clone(), Split("echo foo")).unwrap();
+            assert_eq!(context.stdout(), "foo\n");
+        }
+
+        #[test]
+        fn relays_stdout_for_non_zero_exit_codes() {
+            let context = Context::test();
+            let _ = run_result_with_context_unit(
+                context.clone(),
+                (test_helper(), "output foo and exit with 42"),
+            );
+            assert_eq!(context.stdout(), "foo\n");
+        }
+
+        #[test]
+        fn streams_stdout() {
+            in_temporary_directory(|| {
+                let context = Context::test();
+                let context_clone = context.clone();
+                let thread = thread::spawn(|| {
+                    run_result_with_context_unit(
+                        context_clone,
+                        (test_helper(), "stream chunk then wait for file"),
+                    )
+                    .unwrap();
+                });
+                while (context.stdout()) != "foo\n" {
+                    thread::sleep(Duration::from_secs_f32(0.05));
+                }
+                run!(%"touch file");
+                thread.join().unwrap();
+            });
+        }
+
+        #[test]
+        fn does_not_relay_stdout_when_collecting_into_string() {
+            let context = Context::test();
+            let StdoutTrimmed(_) =
+                run_result_with_context(context.clone(), Split("echo foo")).unwrap();
+            assert_eq!(context.stdout(), "");
+        }
+
+        #[test]
+        fn does_not_relay_stdout_when_collecting_into_result_of_string() {
+            let context = Context::test();
+            let _: Result<StdoutTrimmed, Error> =
+                run_result_with_context(context.clone(), Split("echo foo"));
+            assert_eq!(context.stdout(), "");
+        }
+    }
+
+    mod stderr {
+        use super::*;
+        use pretty_assertions::assert_eq;
+        use std::{thread, time::Duration};
+
+        #[test]
+        fn relays_stderr_by_default() {
+            let context = Context::test();
+            run_result_with_context_unit(context.clone(), (test_helper(), "write to stderr"))
+                .unwrap();
+            assert_eq!(context.stderr(), "foo\n");
+        }
+
+        #[test]
+        fn relays_stderr_for_non_zero_exit_codes() {
+            let context = Context::test();
+            let _: Result<(), Error> = run_result_with_context(
+                context.clone(),
+                (test_helper(), "write to stderr and exit with 42"),
+            );
+            assert_eq!(context.stderr(), "foo\n");
+        }
+
+        #[test]
+        fn streams_stderr() {
+            in_temporary_directory(|| {
+                let context = Context::test();
+                let context_clone = context.clone();
+                let thread = thread::spawn(|| {
+                    run_result_with_context_unit(
+                        context_clone,
+                        (test_helper(), "stream chunk to stderr then wait for file"),
+                    )
+                    .unwrap();
+                });
+                loop {
+                    let expected = "foo\n";
+                    let stderr = context.stderr();
+                    if stderr == expected {
+                        break;
+                    }
+                    assert!(
+                        stderr.len() <= expected.len(),
+                        "expected: {}, got: {}",
+                        expected,
+                        stderr
+                    );
+                    thread::sleep(Duration::from_secs_f32(0.05));
+                }
+                run!(%"touch file");
+                thread.join().unwrap();
+            });
+        }
+
+        mod stderr_prefix {
+            use super::*;
+            use pretty_assertions::assert_eq;
+
+            #[test]
+            fn prefixes_relayed_stderr_lines() {
+                let context = Context::test();
+                run_result_with_context_unit(
+                    context.clone(),
+                    (
+                        Split("sh -c"),
+                        "echo foo >&2; echo bar >&2",
+                        StderrPrefix("[worker] "),
+                    ),
+                )
+                .unwrap();
+                assert_eq!(context.stderr(), "[worker] foo\n[worker] bar\n");
+            }
+
+            #[test]
+            fn prefixes_lines_across_read_boundaries() {
+                let context = Context::test();
+                run_result_with_context_unit(
+                    context.clone(),
+                    (
+                        Split("sh -c"),
+                        "printf 'foo\\nbar\\n' >&2",
+                        StderrPrefix("> "),
+                        ReadBufferSize(1),
+                    ),
+                )
+                .unwrap();
+                assert_eq!(context.stderr(), "> foo\n> bar\n");
+            }
+
+            #[test]
+            fn prefixes_incomplete_last_lines() {
+                let context = Context::test();
+                run_result_with_context_unit(
+                    context.clone(),
+                    (Split("sh -c"), "printf foo >&2", StderrPrefix("> ")),
+                )
+                .unwrap();
+                assert_eq!(context.stderr(), "> foo");
+            }
+
+            #[test]
+            fn does_not_affect_stdout() {
+                let context = Context::test();
+                run_result_with_context_unit(
+                    context.clone(),
+                    (Split("echo foo"), StderrPrefix("> ")),
+                )
+                .unwrap();
+                assert_eq!(context.stdout(), "foo\n");
+            }
+
+            #[test]
+            fn does_not_affect_captured_stderr() {
+                let Stderr(stderr) = run_output!(%"sh -c", "echo foo >&2", StderrPrefix("> "));
+                assert_eq!(stderr, "foo\n");
+            }
+
+            #[test]
+            fn does_not_affect_stderr_files() {
+                in_temporary_directory(|| {
+                    run!(
+                        %"sh -c",
+                        "echo foo >&2",
+                        StderrFile("stderr"),
+                        StderrPrefix("> ")
+                    );
+                    assert_eq!(fs::read_to_string("stderr").unwrap(), "foo\n");
+                });
+            }
+        }
+
+        #[test]
+        fn capture_stderr() {
+            let Stderr(stderr) = run_output!(test_helper(), "write to stderr");
+            assert_eq!(stderr, "foo\n");
+        }
+
+        #[test]
+        fn assumes_stderr_is_utf_8() {
+            let result: Result<Stderr, Error> = run_result!(test_helper(), "invalid utf-8 stderr");
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                format!(
+                    "{} {}:\n  invalid utf-8 written to stderr",
+                    test_helper().display(),
+                    quoted("invalid utf-8 stderr"),
+                )
+            );
+        }
+
+        #[test]
+        #[cfg(not(windows))]
+        fn does_allow_invalid_utf_8_to_stderr_when_not_captured() {
+            run!(test_helper(), "invalid utf-8 stderr");
+        }
+
+        #[test]
+        fn does_not_relay_stderr_when_catpuring() {
+            let context = Context::test();
+            let Stderr(_) =
+                run_result_with_context(context.clone(), (test_helper(), "write to stderr"))
+                    .unwrap();
+            assert_eq!(context.stderr(), "");
+        }
+
+        mod bytes {
+            use super::*;
+            use pretty_assertions::assert_eq;
+
+            #[test]
+            fn captures_stderr_as_bytes() {
+                let StderrBytes(stderr) = run_output!(test_helper(), "write to stderr");
+                assert_eq!(stderr, b"foo\n");
+            }
+
+            #[test]
+            fn allows_invalid_utf_8() {
+                let StderrBytes(stderr) = run_output!(test_helper(), "invalid utf-8 stderr");
+                assert_eq!(stderr, vec![0x80]);
+            }
+
+            #[test]
+            fn does_not_relay_stderr() {
+                let context = Context::test();
+                let StderrBytes(_) =
+                    run_result_with_context(context.clone(), (test_helper(), "write to stderr"))
+                        .unwrap();
+                assert_eq!(context.stderr(), "");
+            }
+        }
+
+        mod lossy {
+            use super::*;
+            use pretty_assertions::assert_eq;
+
+            #[test]
+            fn captures_stderr() {
+                let StderrLossy(stderr) = run_output!(test_helper(), "write to stderr");
+                assert_eq!(stderr, "foo\n");
+            }
+
+            #[test]
+            fn replaces_invalid_utf_8() {
+                let StderrLossy(stderr) = run_output!(test_helper(), "invalid utf-8 stderr");
+                assert_eq!(stderr, "\u{FFFD}");
+            }
+
+            #[test]
+            fn does_not_relay_stderr() {
+                let context = Context::test();
+                let StderrLossy(_) =
+                    run_result_with_context(context.clone(), (test_helper(), "write to stderr"))
+                        .unwrap();
+                assert_eq!(context.stderr(), "");
+            }
+        }
+    }
+
+    mod combined_output {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn captures_stdout_and_stderr_in_order() {
+            let CombinedOutput(output) =
+                run_output!(test_helper(), "write to stdout and stderr interleaved");
+            assert_eq!(output, "to stdout\nto stderr\nto stdout\n");
+        }
+
+        #[test]
+        fn does_not_relay_stdout_or_stderr() {
+            let context = Context::test();
+            let CombinedOutput(_) = run_result_with_context(
+                context.clone(),
+                (test_helper(), "write to stdout and stderr interleaved"),
+            )
+            .unwrap();
+            assert_eq!(context.stdout(), "");
+            assert_eq!(context.stderr(), "");
+        }
+
+        #[test]
+        fn can_be_combined_with_other_outputs() {
+            let (CombinedOutput(combined), StdoutUntrimmed(stdout), Stderr(stderr)) =
+                run_output!(test_helper(), "write to stdout and stderr interleaved");
+            assert_eq!(combined, "to stdout\nto stderr\nto stdout\n");
+            assert_eq!(stdout, "to stdout\nto stdout\n");
+            assert_eq!(stderr, "to stderr\n");
+        }
+
+        #[test]
+        fn assumes_output_is_utf_8() {
+            let result: Result<CombinedOutput, Error> =
+                run_result!(test_helper(), "invalid utf-8 stderr");
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                format!(
+                    "{} {}:\n  invalid utf-8 written to stdout or stderr",
+                    test_helper().display(),
+                    quoted("invalid utf-8 stderr"),
+                )
+            );
+        }
+    }
+
+    mod log_commands {
+        use super::*;
+
+        #[test]
+        fn logs_simple_commands() {
+            let context = Context::test();
+            run_result_with_context_unit(context.clone(), (LogCommand, "true")).unwrap();
+            assert_eq!(context.stderr(), "+ true\n");
+        }
+
+        #[test]
+        fn logs_commands_with_arguments() {
+            let context = Context::test();
+            run_result_with_context_unit(context.clone(), (LogCommand, Split("echo foo"))).unwrap();
+            assert_eq!(context.stderr(), "+ echo foo\n");
+        }
+
+        #[test]
+        fn quotes_arguments_with_spaces() {
+            let context = Context::test();
+            run_result_with_context_unit(context.clone(), (LogCommand, "echo", "foo bar")).unwrap();
+            assert_eq!(context.stderr(), format!("+ echo {}\n", quoted("foo bar")));
+        }
+
+        #[test]
+        fn quotes_empty_arguments() {
+            let context = Context::test();
+            run_result_with_context_unit(context.clone(), (LogCommand, "echo", "")).unwrap();
+            assert_eq!(context.stderr(), format!("+ echo {}\n", quoted("")));
+        }
+
+        #[test]
+        #[cfg(not(windows))]
+        fn escapes_single_quotes() {
+            let context = Context::test();
+            run_result_with_context_unit(context.clone(), (LogCommand, "echo", "it's")).unwrap();
+            assert_eq!(context.stderr(), "+ echo 'it'\\''s'\n");
+        }
+
+        #[test]
+        #[cfg(not(windows))]
+        fn quotes_shell_metacharacters() {
+            let context = Context::test();
+            run_result_with_context_unit(
+                context.clone(),
+                (LogCommand, "echo", "$HOME", "`ls`", "foo\nbar", "a;b", "*"),
+            )
+            .unwrap();
+            assert_eq!(
+                context.stderr(),
+                "+ echo '$HOME' '`ls`' 'foo\nbar' 'a;b' '*'\n"
+            );
+        }
+
+        #[test]
+        #[cfg(not(windows))]
+        fn does_not_quote_safe_arguments() {
+            let context = Context::test();
+            run_result_with_context_unit(
+                context.clone(),
+                (
+                    LogCommand,
+                    "echo",
+                    "./foo/bar.txt",
+                    "--foo=bar",
+                    "a,b:c@d+e%f",
+                ),
+            )
+            .unwrap();
+            assert_eq!(
+                context.stderr(),
+                "+ echo ./foo/bar.txt --foo=bar a,b:c@d+e%f\n"
+            );
+        }
+
+        #[test]
+        #[cfg(unix)]
+        fn logged_commands_can_be_pasted_into_a_shell() {
+            let context = Context::test();
+            let arguments = vec!["it's", "$HOME", "`ls`", "foo\nbar", "\\", "", "\"quoted\""];
+            let StdoutUntrimmed(expected) = run_result_with_context(
+                context.clone(),
+                (LogCommand, "printf", "[%s]\\n", arguments),
+            )
+            .unwrap();
+            let logged = context.stderr();
+            let logged = logged.trim_start_matches("+ ").trim_end_matches('\n');
+            let StdoutUntrimmed(output) = run_output!("sh", "-c", logged);
+            assert_eq!(output, expected);
+        }
+
+        #[test]
+        #[cfg(windows)]
+        fn uses_double_quotes_on_windows() {
+            let context = Context::test();
+            run_result_with_context_unit(
+                context.clone(),
+                (LogCommand, "echo", "foo bar", "say \"hi\"", "foo\\ bar\\"),
+            )
+            .unwrap();
+            assert_eq!(
+                context.stderr(),
+                "+ echo \"foo bar\" \"say \\\"hi\\\"\" \"foo\\ bar\\\\\"\n"
+            );
+        }
+
+        #[test]
+        #[cfg(unix)]
+        fn arguments_with_invalid_utf8_will_be_logged_with_lossy_conversion() {
+            use std::{ffi::OsStr, os::unix::prelude::OsStrExt, path::Path};
+            let context = Context::test();
+            let argument_with_invalid_utf8: &OsStr =
+                OsStrExt::from_bytes(&[102, 111, 111, 0x80, 98, 97, 114]);
+            let argument_with_invalid_utf8: &Path = argument_with_invalid_utf8.as_ref();
+            run_result_with_context_unit(
+                context.clone(),
+                (LogCommand, "echo", argument_with_invalid_utf8),
+            )
+            .unwrap();
+            assert_eq!(context.stderr(), "+ echo foo�bar\n");
+        }
+
+        mod log_command_to {
+            use super::*;
+            use std::sync::mpsc::{channel, Receiver};
+
+            fn collect_logs() -> (Receiver<String>, LogCommandTo<impl FnMut(&str) + Send>) {
+                let (sender, receiver) = channel();
+                (
+                    receiver,
+                    LogCommandTo(move |command: &str| sender.send(command.to_string()).unwrap()),
+                )
+            }
+
+            #[test]
+            fn passes_commands_to_the_given_function() {
+                let (logs, log_command_to) = collect_logs();
+                run!("echo", "foo bar", log_command_to);
+                assert_eq!(
+                    logs.try_iter().collect::<Vec<_>>(),
+                    vec![format!("echo {}", quoted("foo bar"))]
+                );
+            }
+
+            #[test]
+            fn does_not_write_to_stderr() {
+                let context = Context::test();
+                let (logs, log_command_to) = collect_logs();
+                run_result_with_context_unit(context.clone(), ("true", log_command_to)).unwrap();
+                assert_eq!(context.stderr(), "");
+                assert_eq!(logs.try_iter().collect::<Vec<_>>(), vec!["true"]);
+            }
+
+            #[test]
+            fn logs_full_pipelines() {
+                let (logs, log_command_to) = collect_logs();
+                run!(Pipe(("echo", "foo"), "cat"), log_command_to);
+                assert_eq!(logs.try_iter().collect::<Vec<_>>(), vec!["echo foo | cat"]);
+            }
+        }
+
+        mod log_command_verbose {
+            use super::*;
+
+            #[test]
+            fn logs_like_log_command_without_environment_and_working_directory() {
+                let context = Context::test();
+                run_result_with_context_unit(
+                    context.clone(),
+                    (LogCommandVerbose, Split("echo foo")),
+                )
+                .unwrap();
+                assert_eq!(context.stderr(), "+ echo foo\n");
+            }
+
+            #[test]
+            fn prefixes_added_environment_variables() {
+                let context = Context::test();
+                run_result_with_context_unit(
+                    context.clone(),
+                    (
+                        LogCommandVerbose,
+                        Env("FOO", "bar"),
+                        Env("BAR", "baz"),
+                        "true",
+                    ),
+                )
+                .unwrap();
+                assert_eq!(context.stderr(), "+ FOO=bar BAR=baz true\n");
+            }
+
+            #[test]
+            #[cfg(not(windows))]
+            fn quotes_environment_variable_values() {
+                let context = Context::test();
+                run_result_with_context_unit(
+                    context.clone(),
+                    (LogCommandVerbose, Env("FOO", "bar baz"), "true"),
+                )
+                .unwrap();
+                assert_eq!(context.stderr(), "+ FOO='bar baz' true\n");
+            }
+
+            #[test]
+            fn wraps_the_command_with_cd() {
+                in_temporary_directory(|| {
+                    fs::create_dir("dir").unwrap();
+                    let context = Context::test();
+                    run_result_with_context_unit(
+                        context.clone(),
+                        (
+                            LogCommandVerbose,
+                            CurrentDir("dir"),
+                            Env("FOO", "bar"),
+                            "true",
+                        ),
+                    )
+                    .unwrap();
+                    assert_eq!(context.stderr(), "+ (cd dir; FOO=bar true)\n");
+                });
+            }
+
+            #[test]
+            fn logs_every_stage_of_pipelines() {
+                in_temporary_directory(|| {
+                    fs::create_dir("dir").unwrap();
+                    let context = Context::test();
+                    run_result_with_context_unit(
+                        context.clone(),
+                        (
+                            LogCommandVerbose,
+                            Pipe(
+                                (Env("FOO", "bar"), Split("echo foo")),
+                                (CurrentDir("dir"), "cat"),
+                            ),
+                        ),
+                    )
+                    .unwrap();
+                    assert_eq!(context.stderr(), "+ FOO=bar echo foo | (cd dir; cat)\n");
+                });
+            }
+
+            #[test]
+            fn logs_commands_only_once_together_with_log_command() {
+                let context = Context::test();
+                run_result_with_context_unit(
+                    context.clone(),
+                    (LogCommand, LogCommandVerbose, Env("FOO", "bar"), "true"),
+                )
+                .unwrap();
+                assert_eq!(context.stderr(), "+ FOO=bar true\n");
+            }
+
+            #[test]
+            #[cfg(unix)]
+            fn logged_commands_can_be_pasted_into_a_shell() {
+                in_temporary_directory(|| {
+                    fs::create_dir("some dir").unwrap();
+                    let context = Context::test();
+                    let StdoutUntrimmed(expected) = run_result_with_context(
+                        context.clone(),
+                        (
+                            LogCommandVerbose,
+                            CurrentDir("some dir"),
+                            Env("FOO", "it's $HOME"),
+                            "sh",
+                            "-c",
+                            "pwd; echo \"$FOO\"",
+                        ),
+                    )
+                    .unwrap();
+                    let logged = context.stderr();
+                    let logged = logged.trim_start_matches("+ ").trim_end_matches('\n');
+                    let StdoutUntrimmed(output) = run_output!("sh", "-c", logged);
+                    assert_eq!(output, expected);
+                });
+            }
+        }
+    }
+
+    mod exit_status {
+        use super::*;
+
+        #[test]
+        fn zero() {
+            let Status(exit_status) = run_output!("true");
+            assert!(exit_status.success());
+        }
+
+        #[test]
+        fn one() {
+            let Status(exit_status) = run_output!("false");
+            assert!(!exit_status.success());
+        }
+
+        #[test]
+        fn forty_two() {
+            let Status(exit_status) = run_output!(test_helper(), "exit code 42");
+            assert!(!exit_status.success());
+            assert_eq!(exit_status.code(), Some(42));
+        }
+
+        #[test]
+        fn failing_commands_return_oks_when_exit_status_is_captured() {
+            let Status(exit_status) = run_result!("false").unwrap();
+            assert!(!exit_status.success());
+        }
+    }
+
+    #[cfg(unix)]
+    mod signal {
+        use super::*;
+
+        #[test]
+        fn returns_the_terminating_signal() {
+            let Signal(signal) = run_output!(%"sh -c", "kill -TERM $$");
+            assert_eq!(signal, Some(libc::SIGTERM));
+        }
+
+        #[test]
+        fn returns_none_for_normal_exits() {
+            let Signal(signal) = run_output!("true");
+            assert_eq!(signal, None);
+            let Signal(signal) = run_output!(test_helper(), "exit code 42");
+            assert_eq!(signal, None);
+        }
+
+        #[test]
+        fn works_for_sigkill() {
+            let Signal(signal) = run_output!(%"sh -c", "kill -KILL $$");
+            assert_eq!(signal, Some(libc::SIGKILL));
+        }
+
+        #[test]
+        fn does_not_return_errors_for_signals() {
+            let result: Result<Signal, Error> = run_result!(%"sh -c", "kill -TERM $$");
+            assert!(result.is_ok());
+        }
+    }
+
+    mod captured_output {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn captures_stdout_stderr_and_the_exit_status() {
+            let CapturedOutput {
+                stdout,
+                stderr,
+                status,
+            } = run_output!(%"sh -c", "echo foo; echo bar >&2");
+            assert_eq!(stdout, b"foo\n");
+            assert_eq!(stderr, b"bar\n");
+            assert!(status.success());
+        }
+
+        #[test]
+        fn non_zero_exit_codes_do_not_produce_errors() {
+            let result: Result<CapturedOutput, Error> = run_result!(test_helper(), "exit code 42");
+            assert_eq!(result.unwrap().status.code(), Some(42));
+        }
+
+        #[test]
+        fn captures_invalid_utf8() {
+            let CapturedOutput { stdout, .. } = run_output!(test_helper(), "invalid utf-8 stdout");
+            assert_eq!(stdout, vec![0x80]);
+        }
+
+        #[test]
+        fn does_not_relay_output() {
+            let context = Context::test();
+            let _: CapturedOutput = run_result_with_context(
+                context.clone(),
+                (Split("sh -c"), "echo foo; echo bar >&2"),
+            )
+            .unwrap();
+            assert_eq!(context.stdout(), "");
+            assert_eq!(context.stderr(), "");
+        }
+    }
+
+    mod bool_output {
+        use super::*;
+
+        #[test]
+        fn success_exit_status_is_true() {
+            assert!(run_output!("true"));
+        }
+
+        #[test]
+        fn failure_exit_status_is_false() {
+            assert!(!run_output!("false"));
+        }
+
+        #[test]
+        #[should_panic]
+        fn io_error_panics() {
+            assert!(run_output!("/"));
+        }
+    }
+
+    mod i32_output {
+        use super::*;
+
+        #[test]
+        fn zero() {
+            let exit_code: i32 = run_output!("true");
+            assert_eq!(exit_code, 0);
+        }
+
+        #[test]
+        fn forty_two() {
+            let exit_code: i32 = run_output!(test_helper(), "exit code 42");
+            assert_eq!(exit_code, 42);
+        }
+
+        #[test]
+        fn non_zero_exit_codes_do_not_produce_errors() {
+            let result: Result<i32, Error> = run_result!("false");
+            assert_eq!(result.unwrap(), 1);
+        }
+
+        #[test]
+        #[cfg(unix)]
+        fn signals_are_returned_negated() {
+            let exit_code: i32 = run_output!(%"sh -c", "kill -9 $$");
+            assert_eq!(exit_code, -9);
+        }
+    }
+
+    mod result_output {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn returns_stdout_on_success() {
+            let output: Result<String, String> = run_output!(%"echo foo");
+            assert_eq!(output, Ok("foo\n".to_string()));
+        }
+
+        #[test]
+        fn returns_stderr_on_failure() {
+            let output: Result<String, String> =
+                run_output!(%"sh -c", "echo foo; echo bar >&2; exit 42");
+            assert_eq!(output, Err("bar\n".to_string()));
+        }
+
+        #[test]
+        fn non_zero_exit_codes_do_not_produce_errors() {
+            let result: Result<Result<String, String>, Error> = run_result!("false");
+            assert_eq!(result.unwrap(), Err("".to_string()));
+        }
+
+        #[test]
+        fn does_not_relay_output() {
+            let context = Context::test();
+            let _: Result<String, String> = run_result_with_context(
+                context.clone(),
+                (Split("sh -c"), "echo foo; echo bar >&2"),
+            )
+            .unwrap();
+            assert_eq!(context.stdout(), "");
+            assert_eq!(context.stderr(), "");
+        }
+    }
+
+    mod timed {
+        use super::*;
+        #[cfg(unix)]
+        use std::time::Duration;
+
+        #[test]
+        fn wraps_other_outputs() {
+            let Timed(StdoutTrimmed(output), _) = run_output!(%"echo foo");
+            assert_eq!(output, "foo");
+            let Timed(exit_code, _): Timed<i32> = run_output!("false");
+            assert_eq!(exit_code, 1);
+        }
+
+        #[test]
+        #[cfg(unix)]
+        fn measures_how_long_the_child_process_runs() {
+            let Timed((), duration) = run_output!(%"sleep 0.2");
+            assert!(duration >= Duration::from_millis(200), "{:?}", duration);
+        }
+
+        #[test]
+        #[cfg(unix)]
+        fn includes_the_time_to_drain_the_output_streams() {
+            let Timed((), duration) = run_output!(%"sh -c", "sleep 0.2 &");
+            assert!(duration >= Duration::from_millis(200), "{:?}", duration);
+        }
+    }
+
+    #[cfg(feature = "bytes")]
+    mod stdout_bytes_buf {
+        use super::*;
+
+        #[test]
+        fn captures_stdout_as_bytes() {
+            let StdoutBytesBuf(output) = run_output!(%"echo foo");
+            assert_eq!(output, bytes::Bytes::from_static(b"foo\n"));
+        }
+
+        #[test]
+        fn allows_invalid_utf8() {
+            let StdoutBytesBuf(output) = run_output!(test_helper(), "invalid utf-8 stdout");
+            assert_eq!(output, bytes::Bytes::from_static(&[0x80]));
+        }
+
+        #[test]
+        fn does_not_relay_stdout() {
+            let context = Context::test();
+            let StdoutBytesBuf(_) =
+                run_result_with_context(context.clone(), Split("echo foo")).unwrap();
+            assert_eq!(context.stdout(), "");
+        }
+    }
+
+    #[cfg(feature = "encoding")]
+    mod stdout_encoded {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn decodes_stdout_with_the_given_encoding() {
+            let StdoutEncoded { stdout, encoding } = run_output!(
+                test_helper(),
+                "invalid utf-8 stdout",
+                StdoutEncoding(encoding_rs::WINDOWS_1252)
+            );
+            assert_eq!(stdout, "€");
+            assert_eq!(encoding, encoding_rs::WINDOWS_1252);
+        }
+
+        #[test]
+        fn decodes_utf8_by_default() {
+            let StdoutEncoded { stdout, encoding } = run_output!("echo", "café");
+            assert_eq!(stdout, "café\n");
+            assert_eq!(encoding, encoding_rs::UTF_8);
+        }
+
+        #[test]
+        fn replaces_malformed_sequences() {
+            let StdoutEncoded { stdout, .. } = run_output!(test_helper(), "invalid utf-8 stdout");
+            assert_eq!(stdout, "\u{FFFD}");
+        }
+
+        #[test]
+        fn does_not_relay_stdout() {
+            let context = Context::test();
+            let StdoutEncoded { .. } =
+                run_result_with_context(context.clone(), Split("echo foo")).unwrap();
+            assert_eq!(context.stdout(), "");
+        }
+    }
+
+    #[cfg(feature = "serde_json")]
+    mod json {
+        use super::*;
+        use std::collections::BTreeMap;
+
+        #[test]
+        fn deserializes_stdout() {
+            let Json(value): Json<BTreeMap<String, i64>> =
+                run_output!("echo", r#"{"foo": 1, "bar": 2}"#);
+            let expected: BTreeMap<String, i64> =
+                vec![("bar".to_string(), 2), ("foo".to_string(), 1)]
+                    .into_iter()
+                    .collect();
+            assert_eq!(value, expected);
+        }
+
+        #[test]
+        fn captures_arbitrary_json_values() {
+            let JsonValue(value) = run_output!("echo", r#"{"foo": [1, "bar", null]}"#);
+            assert_eq!(value, serde_json_crate::json!({ "foo": [1, "bar", null] }));
+        }
+
+        #[test]
+        fn deserialization_errors_contain_the_full_command() {
+            let result: Result<JsonValue, Error> = run_result!("echo", "{foo");
+            let error = result.unwrap_err();
+            match &error {
+                Error::Deserialization { full_command, .. } => {
+                    assert_eq!(full_command, &format!("echo {}", quoted("{foo")));
+                }
+                _ => panic!("should match Error::Deserialization"),
+            }
+            assert!(
+                error.to_string().starts_with(&format!(
+                    "echo {}:\n  could not deserialize stdout: ",
+                    quoted("{foo")
+                )),
+                "{}",
+                error
+            );
+            assert!(std::error::Error::source(&error).is_some());
+        }
+
+        #[test]
+        fn typed_json_reports_type_mismatches() {
+            let result: Result<Json<Vec<i64>>, Error> = run_result!("echo", r#"["foo"]"#);
+            match result {
+                Err(Error::Deserialization { .. }) => {}
+                _ => panic!("should match Error::Deserialization"),
+            }
+        }
+    }
+
+    #[cfg(feature = "toml")]
+    mod toml {
+        use super::*;
+        use std::collections::BTreeMap;
+
+        #[test]
+        fn deserializes_stdout() {
+            let Toml(value): Toml<BTreeMap<String, i64>> = run_output!("echo", "foo = 1\nbar = 2");
+            let expected: BTreeMap<String, i64> =
+                vec![("bar".to_string(), 2), ("foo".to_string(), 1)]
+                    .into_iter()
+                    .collect();
+            assert_eq!(value, expected);
+        }
+
+        #[test]
+        fn deserialization_errors_contain_the_full_command() {
+            let result: Result<Toml<BTreeMap<String, i64>>, Error> =
+                run_result!("echo", "foo = bar");
+            let error = result.unwrap_err();
+            match &error {
+                Error::Deserialization { full_command, .. } => {
+                    assert_eq!(full_command, &format!("echo {}", quoted("foo = bar")));
+                }
+                _ => panic!("should match Error::Deserialization"),
+            }
+            assert!(
+                error.to_string().starts_with(&format!(
+                    "echo {}:\n  could not deserialize stdout: ",
+                    quoted("foo = bar")
+                )),
+                "{}",
+                error
+            );
+            assert!(std::error::Error::source(&error).is_some());
+        }
+    }
+
+    mod tuple_inputs {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn two_tuple() {
+            let StdoutTrimmed(output) = run_output!(("echo", "foo"));
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn three_tuples() {
+            let StdoutTrimmed(output) = run_output!(("echo", "foo", "bar"));
+            assert_eq!(output, "foo bar");
+        }
+
+        #[test]
+        fn nested_tuples() {
+            let StdoutTrimmed(output) = run_output!(("echo", ("foo", "bar")));
+            assert_eq!(output, "foo bar");
+        }
+
+        #[test]
+        fn unit_input() {
+            let StdoutTrimmed(output) = run_output!(("echo", ()));
+            assert_eq!(output, "");
+        }
+    }
+
+    mod tuple_outputs {
+        use super::*;
+
+        #[test]
+        fn two_tuple_1() {
+            let (StdoutTrimmed(output), Status(exit_status)) =
+                run_output!(test_helper(), "output foo and exit with 42");
+            assert_eq!(output, "foo");
+            assert_eq!(exit_status.code(), Some(42));
+        }
+
+        #[test]
+        fn two_tuple_2() {
+            let (Status(exit_status), StdoutTrimmed(output)) =
+                run_output!(test_helper(), "output foo and exit with 42");
+            assert_eq!(output, "foo");
+            assert_eq!(exit_status.code(), Some(42));
+        }
+
+        #[test]
+        fn result_of_tuple() {
+            let (StdoutTrimmed(output), Status(exit_status)) = run_result!(%"echo foo").unwrap();
+            assert_eq!(output, "foo");
+            assert!(exit_status.success());
+        }
+
+        #[test]
+        fn result_of_tuple_when_erroring() {
+            let (StdoutTrimmed(output), Status(exit_status)) = run_result!("false").unwrap();
+            assert_eq!(output, "");
+            assert_eq!(exit_status.code(), Some(1));
+        }
+
+        #[test]
+        fn three_tuples() {
+            let (Stderr(stderr), StdoutTrimmed(stdout), Status(exit_status)) =
+                run_output!(%"echo foo");
+            assert_eq!(stderr, "");
+            assert_eq!(stdout, "foo");
+            assert_eq!(exit_status.code(), Some(0));
+        }
+
+        #[test]
+        fn capturing_stdout_on_errors() {
+            let (StdoutTrimmed(output), Status(exit_status)) =
+                run_output!(test_helper(), "output foo and exit with 42");
+            assert!(!exit_status.success());
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn capturing_stderr_on_errors() {
+            let (Stderr(output), Status(exit_status)) =
+                run_output!(test_helper(), "write to stderr and exit with 42");
+            assert!(!exit_status.success());
+            assert_eq!(output, "foo\n");
+        }
+    }
+
+    mod current_dir {
+        use super::*;
+        use std::path::Path;
+
+        #[test]
+        fn sets_the_working_directory() {
+            in_temporary_directory(|| {
+                fs::create_dir("dir").unwrap();
+                fs::write("dir/file", "foo").unwrap();
+                fs::write("file", "wrong file").unwrap();
+                let StdoutUntrimmed(output) = run_output!(%"cat file", CurrentDir("dir"));
+                assert_eq!(output, "foo");
+            });
+        }
+
+        #[test]
+        fn works_for_other_types() {
+            in_temporary_directory(|| {
+                fs::create_dir("dir").unwrap();
+                let dir: String = "dir".to_string();
+                run!("true", CurrentDir(dir));
+                let dir: PathBuf = PathBuf::from("dir");
+                run!("true", CurrentDir(dir));
+                let dir: &Path = Path::new("dir");
+                run!("true", CurrentDir(dir));
+            });
+        }
+
+        #[test]
+        fn missing_directories_produce_errors_that_name_the_path() {
+            in_temporary_directory(|| {
+                let result: Result<(), Error> = run_result!("true", CurrentDir("does-not-exist"));
+                let error = result.unwrap_err();
+                match &error {
+                    Error::WorkingDirectoryNotFound { path, .. } => {
+                        assert_eq!(path, &PathBuf::from("does-not-exist"));
+                    }
+                    _ => panic!("should match Error::WorkingDirectoryNotFound"),
+                }
+                assert_eq!(
+                    error.to_string(),
+                    "true:\n  working directory not found: does-not-exist"
+                );
+            });
+        }
+
+        #[test]
+        fn files_are_not_accepted_as_working_directories() {
+            in_temporary_directory(|| {
+                fs::write("file", "").unwrap();
+                let result: Result<(), Error> = run_result!("true", CurrentDir("file"));
+                match result {
+                    Err(Error::WorkingDirectoryNotFound { .. }) => {}
+                    _ => panic!("should match Error::WorkingDirectoryNotFound"),
+                }
+            });
+        }
+
+        #[test]
+        fn missing_directories_do_not_truncate_output_files() {
+            in_temporary_directory(|| {
+                fs::write("file", "foo").unwrap();
+                let result: Result<(), Error> = run_result!(
+                    ("echo", "bar"),
+                    CurrentDir("does-not-exist"),
+                    StdoutFile("file"),
+                    StderrFile("file")
+                );
+                match result {
+                    Err(Error::WorkingDirectoryNotFound { .. }) => {}
+                    _ => panic!("should match Error::WorkingDirectoryNotFound"),
+                }
+                assert_eq!(fs::read_to_string("file").unwrap(), "foo");
+            });
+        }
+    }
+
+    mod run_status {
+        use super::*;
+
+        #[test]
+        fn returns_the_exit_status() {
+            assert!("true".run_status().success());
+            let status = (test_helper(), "exit code 42").run_status();
+            assert_eq!(status.code(), Some(42));
+        }
+
+        #[test]
+        #[should_panic(
+            expected = "cradle error: File not found error when executing 'does-not-exist'"
+        )]
+        fn panics_on_missing_executables() {
+            "does-not-exist".run_status();
+        }
+
+        #[test]
+        fn works_for_commands() {
+            let status = Command::new((test_helper(), "exit code 42")).run_status();
+            assert_eq!(status.code(), Some(42));
+        }
+    }
+
+    mod run_result_into {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[derive(Debug)]
+        enum CustomError {
+            Cradle(Error),
+        }
+
+        impl From<Error> for CustomError {
+            fn from(error: Error) -> Self {
+                CustomError::Cradle(error)
+            }
+        }
+
+        #[test]
+        fn returns_the_output_on_success() {
+            let result: Result<StdoutTrimmed, CustomError> = Split("echo foo").run_result_into();
+            let StdoutTrimmed(output) = result.unwrap();
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn converts_errors() {
+            let result: Result<(), CustomError> = "false".run_result_into();
+            match result {
+                Err(CustomError::Cradle(Error::NonZeroExitCode { .. })) => {}
+                result => panic!("expected NonZeroExitCode, got: {:?}", result),
+            }
+        }
+
+        #[test]
+        fn works_with_the_question_mark_operator() {
+            fn test() -> Result<String, CustomError> {
+                let StdoutTrimmed(output) =
+                    Split("echo foo").run_result_into::<_, CustomError>()?;
+                Ok(output)
+            }
+            assert_eq!(test().unwrap(), "foo");
+        }
+
+        #[test]
+        fn works_for_commands() {
+            let result: Result<(), CustomError> = Command::new("false").run_result_into();
+            assert!(result.is_err());
+        }
+    }
+
+    mod run_result_verbose {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn returns_the_output_on_success() {
+            let StdoutTrimmed(output) = Split("echo foo").run_result_verbose().unwrap();
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn returns_the_error_and_a_summary_on_failure() {
+            let result: Result<(), (Error, Box<CommandSummary>)> = (
+                Split("false foo"),
+                CurrentDir("/"),
+                Env("FOO", "foo"),
+                EnvRemove("BAR"),
+            )
+                .run_result_verbose();
+            let (error, summary) = result.unwrap_err();
+            match error {
+                Error::NonZeroExitCode { .. } => {}
+                error => panic!("expected NonZeroExitCode, got: {:?}", error),
+            }
+            assert_eq!(
+                *summary,
+                CommandSummary {
+                    full_command: "false foo".to_string(),
+                    arguments: vec![OsString::from("false"), OsString::from("foo")],
+                    current_dir: Some(PathBuf::from("/")),
+                    cleared_env: false,
+                    added_env: vec![(OsString::from("FOO"), OsString::from("foo"))],
+                    removed_env: vec![OsString::from("BAR")],
+                    path_prefix: Vec::new(),
+                }
+            );
+        }
+
+        #[test]
+        fn includes_cleared_environments_and_path_prefixes() {
+            let result: Result<(), (Error, Box<CommandSummary>)> =
+                ("does-not-exist", ClearEnv, WithPath(vec!["/foo"])).run_result_verbose();
+            let (error, summary) = result.unwrap_err();
+            match error {
+                Error::FileNotFound { .. } => {}
+                error => panic!("expected FileNotFound, got: {:?}", error),
+            }
+            assert!(summary.cleared_env);
+            assert_eq!(summary.path_prefix, vec![PathBuf::from("/foo")]);
+        }
+
+        #[test]
+        fn works_for_commands() {
+            let result: Result<(), (Error, Box<CommandSummary>)> =
+                Command::new("false").current_dir("/").run_result_verbose();
+            let (_, summary) = result.unwrap_err();
+            assert_eq!(summary.arguments, vec!["false"]);
+            assert_eq!(summary.current_dir, Some(PathBuf::from("/")));
+        }
+
+        #[test]
+        fn summarizes_the_last_command_of_pipes() {
+            let result: Result<(), (Error, Box<CommandSummary>)> =
+                Pipe(Split("echo foo"), Split("grep bar")).run_result_verbose();
+            let (_, summary) = result.unwrap_err();
+            assert_eq!(summary.full_command, "echo foo | grep bar");
+            assert_eq!(summary.arguments, vec!["grep", "bar"]);
+        }
+    }
+
+    mod current_dir_create {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn creates_missing_directories() {
+            in_temporary_directory(|| {
+                run!(%"touch file", CurrentDirCreate("foo/bar"));
+                assert!(PathBuf::from("foo/bar/file").exists());
+            });
+        }
+
+        #[test]
+        fn works_for_existing_directories() {
+            in_temporary_directory(|| {
+                fs::create_dir("dir").unwrap();
+                fs::write("dir/file", "foo").unwrap();
+                let StdoutUntrimmed(output) = run_output!(%"cat file", CurrentDirCreate("dir"));
+                assert_eq!(output, "foo");
+            });
+        }
+
+        #[test]
+        fn creation_failures_produce_errors_that_name_the_path() {
+            in_temporary_directory(|| {
+                fs::write("file", "").unwrap();
+                let result: Result<(), Error> = run_result!("true", CurrentDirCreate("file/dir"));
+                match result {
+                    Err(Error::CommandIoError { message, .. }) => {
+                        assert!(message.starts_with("true:\n  file"), "{}", message);
+                    }
+                    result => panic!("expected CommandIoError, got: {:?}", result),
+                }
+            });
+        }
+
+        #[test]
+        fn current_dir_afterwards_does_not_create_directories() {
+            in_temporary_directory(|| {
+                let result: Result<(), Error> = run_result!(
+                    "true",
+                    CurrentDirCreate("does-not-exist"),
+                    CurrentDir("does-not-exist")
+                );
+                match result {
+                    Err(Error::WorkingDirectoryNotFound { .. }) => {}
+                    _ => panic!("should match Error::WorkingDirectoryNotFound"),
+                }
+                assert!(!PathBuf::from("does-not-exist").exists());
+            });
+        }
+    }
+
+    mod relative_to_parent {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn resolves_paths_against_the_parents_current_directory() {
+            in_temporary_directory(|| {
+                fs::create_dir("dir").unwrap();
+                fs::write("file", "foo").unwrap();
+                let StdoutUntrimmed(output) =
+                    run_output!("cat", RelativeToParent("file"), CurrentDir("dir"));
+                assert_eq!(output, "foo");
+            });
+        }
+
+        #[test]
+        fn turns_relative_paths_into_absolute_paths() {
+            in_temporary_directory(|| {
+                let args = preview_args(("echo", RelativeToParent("foo")));
+                assert_eq!(args[1], current_dir().unwrap().join("foo").into_os_string());
+            });
+        }
+
+        #[test]
+        fn does_not_change_absolute_paths() {
+            let path = current_dir().unwrap().join("foo");
+            let args = preview_args(("echo", RelativeToParent(&path)));
+            assert_eq!(args[1], path.into_os_string());
+        }
+
+        #[test]
+        fn works_for_paths_that_do_not_exist_yet() {
+            in_temporary_directory(|| {
+                fs::create_dir("dir").unwrap();
+                run!("touch", RelativeToParent("file"), CurrentDir("dir"));
+                assert!(PathBuf::from("file").exists());
+                assert!(!PathBuf::from("dir/file").exists());
+            });
+        }
+    }
+
+    #[cfg(unix)]
+    #[rustversion::since(1.64)]
+    mod process_group {
+        use super::*;
+
+        fn pid_and_process_group(output: &str) -> (String, String) {
+            let words = output
+                .split(|c: char| !c.is_ascii_digit())
+                .filter(|word| !word.is_empty())
+                .collect::<Vec<&str>>();
+            (words[0].to_string(), words[1].to_string())
+        }
+
+        #[test]
+        fn spawns_the_child_in_a_new_process_group() {
+            let StdoutTrimmed(output) =
+                run_output!(test_helper(), "print process group", ProcessGroup);
+            let (pid, process_group) = pid_and_process_group(&output);
+            assert_eq!(pid, process_group);
+        }
+
+        #[test]
+        fn children_inherit_the_process_group_by_default() {
+            let StdoutTrimmed(output) = run_output!(test_helper(), "print process group");
+            let (pid, process_group) = pid_and_process_group(&output);
+            assert_ne!(pid, process_group);
+        }
+    }
+
+    #[cfg(unix)]
+    mod pty {
+        use super::*;
+        use pretty_assertions::assert_eq;
+        use std::{
+            thread,
+            time::{Duration, Instant},
+        };
+
+        #[test]
+        fn connects_stdout_to_a_terminal() {
+            let StdoutTrimmed(output) =
+                run_output!(%"sh -c", "test -t 1 && echo terminal || echo pipe", Pty);
+            assert_eq!(output, "terminal");
+        }
+
+        #[test]
+        fn connects_stderr_to_a_terminal() {
+            let StdoutTrimmed(output) =
+                run_output!(%"sh -c", "test -t 2 && echo terminal || echo pipe", Pty);
+            assert_eq!(output, "terminal");
+        }
+
+        #[test]
+        fn stdout_is_a_pipe_by_default() {
+            let StdoutTrimmed(output) =
+                run_output!(%"sh -c", "test -t 1 && echo terminal || echo pipe");
+            assert_eq!(output, "pipe");
+        }
+
+        #[test]
+        fn does_not_translate_line_endings() {
+            let StdoutUntrimmed(output) = run_output!("echo", "foo\nbar", Pty);
+            assert_eq!(output, "foo\nbar\n");
+        }
+
+        #[test]
+        fn captures_stdout_and_stderr_separately() {
+            let (StdoutUntrimmed(stdout), Stderr(stderr)) =
+                run_output!(%"sh -c", "echo foo; echo bar >&2", Pty);
+            assert_eq!(stdout, "foo\n");
+            assert_eq!(stderr, "bar\n");
+        }
+
+        #[test]
+        fn relays_output() {
+            let context = Context::test();
+            run_result_with_context_unit(
+                context.clone(),
+                (Split("sh -c"), "echo foo; echo bar >&2", Pty),
+            )
+            .unwrap();
+            assert_eq!(context.stdout(), "foo\n");
+            assert_eq!(context.stderr(), "bar\n");
+        }
+
+        #[test]
+        fn captures_large_outputs() {
+            let StdoutUntrimmed(output) = run_output!(%"seq 100000", Pty);
+            assert_eq!(output.lines().count(), 100000);
+            assert!(output.ends_with("100000\n"));
+        }
+
+        #[test]
+        fn works_with_pipes() {
+            let StdoutTrimmed(output) = run_output!(
+                Pipe(
+                    (Split("sh -c"), "test -t 1 && echo terminal || echo pipe"),
+                    "cat"
+                ),
+                Pty
+            );
+            assert_eq!(output, "pipe");
+            let StdoutTrimmed(output) = run_output!(Pipe(
+                Split("echo foo"),
+                (
+                    (Split("sh -c"), "test -t 1 && echo terminal || echo pipe"),
+                    Pty
+                )
+            ));
+            assert_eq!(output, "terminal");
+        }
+
+        #[test]
+        fn returns_the_exit_code() {
+            let Status(status) = run_output!(%"sh -c", "exit 42", Pty);
+            assert_eq!(status.code(), Some(42));
+        }
+
+        #[test]
+        fn does_not_leak_terminal_file_descriptors_into_child_processes() {
+            let start = Instant::now();
+            run!(%"sh -c", "sleep 3 > /dev/null 2>&1 &", Pty);
+            assert!(start.elapsed() < Duration::from_secs(2));
+        }
+
+        #[test]
+        fn concurrent_commands_do_not_wait_for_each_other() {
+            let long_running = thread::spawn(|| {
+                (0..20)
+                    .map(|_| (Split("sleep 5"), Pty, KillOnDrop).spawn().unwrap())
+                    .collect::<Vec<_>>()
+            });
+            let start = Instant::now();
+            for _ in 0..20 {
+                let StdoutTrimmed(output) = run_output!(%"echo foo", Pty);
+                assert_eq!(output, "foo");
+            }
+            let elapsed = start.elapsed();
+            drop(long_running.join().unwrap());
+            assert!(elapsed < Duration::from_secs(4));
+        }
+    }
+
+    #[cfg(unix)]
+    mod umask {
+        use super::*;
+        use std::os::unix::fs::PermissionsExt;
+
+        fn mode(path: &str) -> u32 {
+            fs::metadata(path).unwrap().permissions().mode() & 0o777
+        }
+
+        #[test]
+        fn sets_the_umask_of_the_child_process() {
+            in_temporary_directory(|| {
+                run!(%"touch file", Umask(0o077));
+                assert_eq!(mode("file"), 0o600);
+            });
+        }
+
+        #[test]
+        fn allows_permissive_umasks() {
+            in_temporary_directory(|| {
+                run!(%"touch file", Umask(0o000));
+                assert_eq!(mode("file"), 0o666);
+            });
+        }
+    }
+
+    #[cfg(unix)]
+    mod nice {
+        use super::*;
+
+        fn niceness<I: Input>(input: I) -> i32 {
+            let StdoutTrimmed(output) = run_output!("nice", input);
+            output.parse().unwrap()
+        }
+
+        #[test]
+        fn increases_the_niceness_of_the_child_process() {
+            let parent = niceness(());
+            assert_eq!(niceness(Nice(5)), (parent + 5).min(19));
+        }
+
+        #[test]
+        fn does_not_modify_the_niceness_of_the_parent_process() {
+            let before = niceness(());
+            run!("true", Nice(3));
+            assert_eq!(niceness(()), before);
+        }
+
+        #[test]
+        fn decreasing_the_niceness_without_privileges_produces_errors() {
+            if unsafe { libc::geteuid() } == 0 {
+                return;
+            }
+            let result: Result<(), Error> = run_result!("true", Nice(-5));
+            match result {
+                Err(Error::CommandIoError { .. }) => {}
+                result => panic!("expected CommandIoError, got: {:?}", result),
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    mod cpu_time_limit {
+        use super::*;
+        use std::time::Duration;
+
+        #[test]
+        fn terminates_child_processes_with_sigxcpu() {
+            let Signal(signal) = run_output!(
+                %"sh -c",
+                "while true; do :; done",
+                CpuTimeLimit(Duration::from_secs(1))
+            );
+            assert_eq!(signal, Some(libc::SIGXCPU));
+        }
+
+        #[test]
+        fn exceeding_the_limit_produces_errors() {
+            let result: Result<(), Error> = run_result!(
+                %"sh -c",
+                "while true; do :; done",
+                CpuTimeLimit(Duration::from_millis(100))
+            );
+            match result {
+                Err(Error::NonZeroExitCode { .. }) => {}
+                result => panic!("expected NonZeroExitCode, got: {:?}", result),
+            }
+        }
+
+        #[test]
+        fn does_not_limit_wall_clock_time() {
+            let Status(status) = run_output!(%"sleep 1.5", CpuTimeLimit(Duration::from_secs(1)));
+            assert!(status.success());
+        }
+
+        #[test]
+        fn sets_the_resource_limit() {
+            let StdoutTrimmed(output) =
+                run_output!(%"sh -c", "ulimit -t", CpuTimeLimit(Duration::from_millis(2500)));
+            assert_eq!(output, "3");
+        }
+    }
+
+    #[cfg(unix)]
+    mod memory_limit {
+        use super::*;
+
+        const MIB: usize = 1024 * 1024;
+
+        #[test]
+        fn sets_the_resource_limit() {
+            let StdoutTrimmed(output) = run_output!(%"sh -c", "ulimit -v", MemoryLimit(256 * MIB));
+            assert_eq!(output, "262144");
+        }
+
+        #[test]
+        fn allocations_exceeding_the_limit_fail() {
+            let (Status(status), Stderr(_)) =
+                run_output!(test_helper(), "allocate 512 mib", MemoryLimit(256 * MIB));
+            assert!(!status.success());
+        }
+
+        #[test]
+        fn allocations_within_the_limit_succeed() {
+            let StdoutTrimmed(output) =
+                run_output!(test_helper(), "allocate 512 mib", MemoryLimit(1024 * MIB));
+            assert_eq!(output, format!("allocated {} bytes", 512 * MIB));
+        }
+    }
+
+    mod elevated {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[cfg(unix)]
+        #[test]
+        fn runs_commands_through_sudo() {
+            in_temporary_directory(|| {
+                fs::write("sudo", "#!/bin/sh\necho elevated: \"$@\"\n").unwrap();
+                run!(%"chmod +x sudo");
+                let StdoutTrimmed(output) = run_output!(
+                    %"echo foo",
+                    Elevated,
+                    WithPath(vec![current_dir().unwrap()])
+                );
+                assert_eq!(output, "elevated: echo foo");
+            });
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn runs_commands_through_the_given_elevator() {
+            let StdoutTrimmed(output) = run_output!(%"echo foo", ElevatedWith("env"));
+            assert_eq!(output, "foo");
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn logs_the_elevator() {
+            let context = Context::test();
+            run_result_with_context_unit(
+                context.clone(),
+                (LogCommand, Split("echo foo"), ElevatedWith("env")),
+            )
+            .unwrap();
+            assert_eq!(context.stderr(), "+ env echo foo\n");
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn works_with_executable() {
+            let StdoutTrimmed(output) = run_output!("foo", ElevatedWith("env"), Executable("echo"));
+            assert_eq!(output, "foo");
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn applies_to_all_commands_when_given_for_a_whole_pipe() {
+            in_temporary_directory(|| {
+                fs::write("elevator", "#!/bin/sh\necho \"$@\" >> log\nexec \"$@\"\n").unwrap();
+                run!(%"chmod +x elevator");
+                let elevator = current_dir().unwrap().join("elevator");
+                let StdoutTrimmed(output) =
+                    run_output!(Pipe(Split("echo foo"), "cat"), ElevatedWith(&elevator));
+                assert_eq!(output, "foo");
+                let mut log: Vec<String> = fs::read_to_string("log")
+                    .unwrap()
+                    .lines()
+                    .map(String::from)
+                    .collect();
+                log.sort();
+                assert_eq!(log, vec!["cat", "echo foo"]);
+            });
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn logs_the_elevator_for_all_commands_of_a_pipe() {
+            let context = Context::test();
+            run_result_with_context_unit(
+                context.clone(),
+                (
+                    LogCommand,
+                    Pipe(Split("echo foo"), "cat"),
+                    ElevatedWith("env"),
+                ),
+            )
+            .unwrap();
+            assert_eq!(context.stderr(), "+ env echo foo | env cat\n");
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn does_not_override_the_elevation_of_single_commands_in_a_pipe() {
+            let context = Context::test();
+            run_result_with_context_unit(
+                context.clone(),
+                (
+                    LogCommand,
+                    Pipe((Split("echo foo"), ElevatedWith("nice")), "cat"),
+                    ElevatedWith("env"),
+                ),
+            )
+            .unwrap();
+            assert_eq!(context.stderr(), "+ nice echo foo | env cat\n");
+        }
+
+        #[test]
+        fn is_not_included_in_preview_args() {
+            let args = preview_args((Split("echo foo"), ElevatedWith("env")));
+            assert_eq!(args, vec!["echo", "foo"]);
+        }
+
+        #[test]
+        fn no_executable_given() {
+            let result: Result<(), Error> = run_result!(ElevatedWith("env"));
+            match result {
+                Err(Error::NoExecutableGiven) => {}
+                result => panic!("expected NoExecutableGiven, got: {:?}", result),
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    mod uid_and_gid {
+        use super::*;
+
+        #[test]
+        fn allows_to_set_the_current_user_and_group() {
+            let uid = unsafe { libc::getuid() };
+            let gid = unsafe { libc::getgid() };
+            let StdoutTrimmed(output) = run_output!(%"id -u", Uid(uid), Gid(gid));
+            assert_eq!(output, uid.to_string());
+            let StdoutTrimmed(output) = run_output!(%"id -g", Uid(uid), Gid(gid));
+            assert_eq!(output, gid.to_string());
+        }
+
+        #[test]
+        fn drops_privileges_when_running_as_root() {
+            if unsafe { libc::geteuid() } != 0 {
+                return;
+            }
+            let nobody = 65534;
+            let StdoutTrimmed(output) = run_output!(%"id -u", Uid(nobody), Gid(nobody));
+            assert_eq!(output, nobody.to_string());
+            let StdoutTrimmed(output) = run_output!(%"id -g", Uid(nobody), Gid(nobody));
+            assert_eq!(output, nobody.to_string());
+        }
+
+        #[test]
+        fn setting_the_user_without_permission_is_an_error() {
+            if unsafe { libc::geteuid() } == 0 {
+                return;
+            }
+            let result: Result<(), Error> = run_result!("true", Uid(0));
+            match result {
+                Err(Error::CommandIoError { .. }) => {}
+                result => panic!("expected CommandIoError, got: {:?}", result),
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    #[rustversion::since(1.45)]
+    mod arg0 {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn sets_argv_0() {
+            let StdoutTrimmed(output) = run_output!("sh", Arg0("foo"), "-c", "echo $0");
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn defaults_to_the_executable() {
+            let StdoutTrimmed(output) = run_output!("sh", "-c", "echo $0");
+            assert_eq!(output, "sh");
+        }
+
+        #[test]
+        fn does_not_change_the_other_arguments() {
+            let StdoutTrimmed(output) =
+                run_output!(test_helper(), Arg0("foo"), "echo", "FOO", Env("FOO", "bar"));
+            assert_eq!(output, "bar");
+        }
+
+        #[test]
+        fn is_not_included_in_logged_commands() {
+            let context = Context::test();
+            run_result_with_context_unit(context.clone(), ("true", Arg0("foo"), LogCommand))
+                .unwrap();
+            assert_eq!(context.stderr(), "+ true\n");
+        }
+    }
+
+    #[cfg(unix)]
+    mod close_fd {
+        use super::*;
+
+        fn is_open_in_child<I: Input>(fd: i32, input: I) -> bool {
+            run_output!(%"sh -c", format!("true >&{}", fd), input)
+        }
+
+        #[test]
+        fn closes_inherited_file_descriptors() {
+            let fd = unsafe { libc::dup(2) };
+            assert!(is_open_in_child(fd, ()));
+            assert!(!is_open_in_child(fd, CloseFd(fd)));
+            unsafe { libc::close(fd) };
+        }
+
+        #[test]
+        fn allows_to_close_multiple_file_descriptors() {
+            let first = unsafe { libc::dup(2) };
+            let second = unsafe { libc::dup(2) };
+            assert!(!is_open_in_child(first, (CloseFd(first), CloseFd(second))));
+            assert!(!is_open_in_child(second, (CloseFd(first), CloseFd(second))));
+            unsafe {
+                libc::close(first);
+                libc::close(second);
+            }
+        }
+
+        #[test]
+        fn does_not_close_file_descriptors_in_the_parent() {
+            let fd = unsafe { libc::dup(2) };
+            run!("true", CloseFd(fd));
+            assert_ne!(unsafe { libc::fcntl(fd, libc::F_GETFD) }, -1);
+            unsafe { libc::close(fd) };
+        }
+
+        #[test]
+        fn ignores_file_descriptors_that_are_not_open() {
+            let StdoutTrimmed(output) = run_output!(%"echo foo", CloseFd(12345));
+            assert_eq!(output, "foo");
+        }
+    }
+
+    #[cfg(windows)]
+    mod creation_flags {
+        use super::*;
+
+        #[test]
+        fn runs_child_processes_with_creation_flags() {
+            let StdoutTrimmed(output) = run_output!(
+                %"cmd /c echo foo",
+                CreationFlags(CreationFlags::CREATE_NO_WINDOW)
+            );
+            assert_eq!(output, "foo");
+        }
+    }
+
+    mod capturing_stdout {
+        use super::*;
+
+        mod trimmed {
+            use super::*;
+
+            #[test]
+            fn trims_trailing_whitespace() {
+                let StdoutTrimmed(output) = run_output!(%"echo foo");
+                assert_eq!(output, "foo");
+            }
+
+            #[test]
+            fn trims_leading_whitespace() {
+                let StdoutTrimmed(output) = run_output!(%"echo -n", " foo");
+                assert_eq!(output, "foo");
+            }
+
+            #[test]
+            fn does_not_remove_whitespace_within_output() {
+                let StdoutTrimmed(output) = run_output!(%"echo -n", "foo bar");
+                assert_eq!(output, "foo bar");
+            }
+
+            #[test]
+            fn does_not_modify_output_without_whitespace() {
+                let StdoutTrimmed(output) = run_output!(%"echo -n", "foo");
+                assert_eq!(output, "foo");
+            }
+
+            #[test]
+            fn does_not_relay_stdout() {
+                let context = Context::test();
+                let StdoutTrimmed(_) =
+                    run_result_with_context(context.clone(), Split("echo foo")).unwrap();
+                assert_eq!(context.stdout(), "");
+            }
+        }
+
+        mod untrimmed {
+            use super::*;
+
+            #[test]
+            fn does_not_trim_trailing_newline() {
+                let StdoutUntrimmed(output) = run_output!(%"echo foo");
+                assert_eq!(output, "foo\n");
+            }
+
+            #[test]
+            fn does_not_trim_leading_whitespace() {
+                let StdoutUntrimmed(output) = run_output!(%"echo -n", " foo");
+                assert_eq!(output, " foo");
+            }
+
+            #[test]
+            fn does_not_relay_stdout() {
+                let context = Context::test();
+                let StdoutUntrimmed(_) =
+                    run_result_with_context(context.clone(), Split("echo foo")).unwrap();
+                assert_eq!(context.stdout(), "");
+            }
+        }
+
+        mod path {
+            use super::*;
+
+            #[test]
+            fn returns_trimmed_stdout_as_a_path() {
+                let StdoutPath(path) = run_output!(%"echo -n", " foo/bar \n");
+                assert_eq!(path, PathBuf::from("foo/bar"));
+            }
+
+            #[test]
+            fn empty_output_results_in_an_empty_path() {
+                let StdoutPath(path) = run_output!("true");
+                assert_eq!(path, PathBuf::new());
+            }
+
+            #[test]
+            #[cfg(unix)]
+            fn allows_invalid_utf_8() {
+                use std::os::unix::ffi::OsStrExt;
+                let StdoutPath(path) = run_output!(test_helper(), "invalid utf-8 stdout");
+                assert_eq!(path.as_os_str().as_bytes(), &[0x80]);
+            }
+        }
+
+        mod tee {
+            use super::*;
+
+            #[test]
+            fn captures_stdout() {
+                let StdoutTee(output) = run_output!(%"echo foo");
+                assert_eq!(output, "foo\n");
+            }
+
+            #[test]
+            fn relays_stdout() {
+                let context = Context::test();
+                let StdoutTee(output) =
+                    run_result_with_context(context.clone(), Split("echo foo")).unwrap();
+                assert_eq!(output, "foo\n");
+                assert_eq!(context.stdout(), "foo\n");
+            }
+
+            #[test]
+            fn relays_stdout_when_combined_with_other_stdout_outputs() {
+                let context = Context::test();
+                let (StdoutTee(_), StdoutTrimmed(output)) =
+                    run_result_with_context(context.clone(), Split("echo foo")).unwrap();
+                assert_eq!(output, "foo");
+                assert_eq!(context.stdout(), "foo\n");
+            }
+        }
+
+        mod lines {
+            use super::*;
+
+            #[test]
+            fn splits_stdout_into_lines() {
+                let Lines(lines) = run_output!("echo", "foo\nbar");
+                assert_eq!(lines, vec!["foo", "bar"]);
+            }
+
+            #[test]
+            fn does_not_produce_a_trailing_empty_line() {
+                let Lines(lines) = run_output!(%"echo -n", "foo\n");
+                assert_eq!(lines, vec!["foo"]);
+            }
+
+            #[test]
+            fn strips_carriage_returns() {
+                let Lines(lines) = run_output!(%"echo -n", "foo\r\nbar\r\n");
+                assert_eq!(lines, vec!["foo", "bar"]);
+            }
+
+            #[test]
+            fn empty_output_results_in_no_lines() {
+                let Lines(lines) = run_output!("true");
+                assert_eq!(lines, Vec::<String>::new());
+            }
+
+            #[test]
+            fn does_not_relay_stdout() {
+                let context = Context::test();
+                let Lines(_) = run_result_with_context(context.clone(), Split("echo foo")).unwrap();
+                assert_eq!(context.stdout(), "");
+            }
+        }
+
+        mod nul_separated_lines {
+            use super::*;
+
+            #[test]
+            fn splits_stdout_on_nul_bytes() {
+                let NulSeparatedLines(lines) = run_output!("printf", "foo\\0bar\\0");
+                assert_eq!(lines, vec![OsString::from("foo"), OsString::from("bar")]);
+            }
+
+            #[test]
+            fn keeps_whitespace_and_newlines() {
+                let NulSeparatedLines(lines) = run_output!("printf", "foo bar\\nbaz\\0");
+                assert_eq!(lines, vec![OsString::from("foo bar\nbaz")]);
+            }
+
+            #[test]
+            fn includes_the_last_entry_without_trailing_nul() {
+                let NulSeparatedLines(lines) = run_output!("printf", "foo\\0bar");
+                assert_eq!(lines, vec![OsString::from("foo"), OsString::from("bar")]);
+            }
+
+            #[test]
+            fn empty_output_results_in_no_entries() {
+                let NulSeparatedLines(lines) = run_output!("true");
+                assert_eq!(lines, Vec::<OsString>::new());
+            }
+
+            #[test]
+            #[cfg(unix)]
+            fn allows_invalid_utf8() {
+                use std::os::unix::ffi::OsStringExt;
+
+                let NulSeparatedLines(lines) = run_output!("printf", "\\377\\0");
+                assert_eq!(lines, vec![OsString::from_vec(vec![0xff])]);
+            }
+
+            #[test]
+            fn works_with_find() {
+                in_temporary_directory(|| {
+                    fs::write("foo bar", "").unwrap();
+                    fs::write("baz\nqux", "").unwrap();
+                    let NulSeparatedLines(mut files) = run_output!(%"find . -type f -print0");
+                    files.sort();
+                    assert_eq!(
+                        files,
+                        vec![OsString::from("./baz\nqux"), OsString::from("./foo bar")]
+                    );
+                });
+            }
+        }
+
+        mod fields {
+            use super::*;
+
+            #[test]
+            fn splits_stdout_on_whitespace_by_default() {
+                let Fields(fields) = run_output!("echo", " foo \t bar\nbaz ");
+                assert_eq!(fields, vec!["foo", "bar", "baz"]);
+            }
+
+            #[test]
+            fn splits_stdout_on_the_given_separator() {
+                let Fields(fields) = run_output!("echo", "foo bar,baz", FieldSeparator(','));
+                assert_eq!(fields, vec!["foo bar", "baz"]);
+            }
+
+            #[test]
+            fn preserves_empty_fields() {
+                let Fields(fields) = run_output!("echo", ",foo,,bar", FieldSeparator(','));
+                assert_eq!(fields, vec!["", "foo", "", "bar"]);
+            }
+
+            #[test]
+            fn trailing_separators_result_in_a_trailing_empty_field() {
+                let Fields(fields) = run_output!("echo", "foo,", FieldSeparator(','));
+                assert_eq!(fields, vec!["foo", ""]);
+            }
+
+            #[test]
+            fn removes_a_single_trailing_line_ending() {
+                let Fields(fields) = run_output!(%"echo -n", "foo,bar\r\n", FieldSeparator(','));
+                assert_eq!(fields, vec!["foo", "bar"]);
+            }
+
+            #[test]
+            fn empty_output_results_in_no_fields() {
+                let Fields(fields) = run_output!("true");
+                assert_eq!(fields, Vec::<String>::new());
+                let Fields(fields) = run_output!("echo", FieldSeparator(','));
+                assert_eq!(fields, Vec::<String>::new());
+            }
+
+            #[test]
+            fn works_for_cut_style_pipelines() {
+                let Fields(fields) = run_output!(
+                    Pipe(("echo", "a:b:c"), Split("cut -d: -f2,3")),
+                    FieldSeparator(':')
+                );
+                assert_eq!(fields, vec!["b", "c"]);
+            }
+
+            #[test]
+            fn does_not_relay_stdout() {
+                let context = Context::test();
+                let Fields(_) =
+                    run_result_with_context(context.clone(), Split("echo foo")).unwrap();
+                assert_eq!(context.stdout(), "");
+            }
+        }
+
+        mod words {
+            use super::*;
+
+            #[test]
+            fn splits_stdout_on_whitespace() {
+                let Words(words) = run_output!("echo", " foo \t bar\nbaz ");
+                assert_eq!(words, vec!["foo", "bar", "baz"]);
+            }
+
+            #[test]
+            fn empty_output_results_in_no_words() {
+                let Words(words) = run_output!("echo", " ");
+                assert_eq!(words, Vec::<String>::new());
+            }
+
+            #[test]
+            fn ignores_field_separator() {
+                let Words(words) = run_output!("echo", "foo,bar baz", FieldSeparator(','));
+                assert_eq!(words, vec!["foo,bar", "baz"]);
+            }
+
+            #[test]
+            fn does_not_relay_stdout() {
+                let context = Context::test();
+                let Words(_) = run_result_with_context(context.clone(), Split("echo foo")).unwrap();
+                assert_eq!(context.stdout(), "");
+            }
+        }
+
+        mod env_map {
+            use super::*;
+            use std::collections::BTreeMap;
+
+            fn env_map(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+                pairs
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect()
+            }
+
+            #[test]
+            fn parses_key_value_lines() {
+                let EnvMap(map) = run_output!("echo", "FOO=foo\nBAR=bar");
+                assert_eq!(map, env_map(&[("BAR", "bar"), ("FOO", "foo")]));
+            }
+
+            #[test]
+            fn splits_on_the_first_equals_sign() {
+                let EnvMap(map) = run_output!("echo", "FOO=bar=baz");
+                assert_eq!(map, env_map(&[("FOO", "bar=baz")]));
+            }
+
+            #[test]
+            fn allows_empty_values() {
+                let EnvMap(map) = run_output!("echo", "FOO=");
+                assert_eq!(map, env_map(&[("FOO", "")]));
+            }
+
+            #[test]
+            fn skips_lines_without_equals_signs() {
+                let EnvMap(map) = run_output!("echo", "foo\nFOO=bar\n\nbar");
+                assert_eq!(map, env_map(&[("FOO", "bar")]));
+            }
+
+            #[test]
+            fn captures_the_environment_of_the_child_process() {
+                let EnvMap(map) = run_output!(
+                    test_helper(),
+                    "print environment",
+                    ClearEnv,
+                    Env("FOO", "foo")
+                );
+                assert_eq!(map.get("FOO"), Some(&"foo".to_string()));
+                assert_eq!(map.get("PATH"), None);
+            }
+        }
+
+        mod bytes {
+            use super::*;
+
+            #[test]
+            fn captures_stdout_as_bytes() {
+                let StdoutBytes(output) = run_output!(%"echo foo");
+                assert_eq!(output, b"foo\n");
+            }
+
+            #[test]
+            fn allows_invalid_utf_8() {
+                let StdoutBytes(output) = run_output!(test_helper(), "invalid utf-8 stdout");
+                assert_eq!(output, vec![0x80]);
+            }
+
+            #[test]
+            fn can_be_combined_with_status() {
+                let (StdoutBytes(output), Status(exit_status)) =
+                    run_output!(test_helper(), "output foo and exit with 42");
+                assert_eq!(output, b"foo\n");
+                assert_eq!(exit_status.code(), Some(42));
+            }
+
+            #[test]
+            fn does_not_relay_stdout() {
+                let context = Context::test();
+                let StdoutBytes(_) =
+                    run_result_with_context(context.clone(), Split("echo foo")).unwrap();
+                assert_eq!(context.stdout(), "");
+            }
+        }
+
+        mod lossy {
+            use super::*;
+            use pretty_assertions::assert_eq;
+
+            #[test]
+            fn captures_stdout() {
+                let StdoutLossy(output) = run_output!(%"echo foo");
+                assert_eq!(output, "foo\n");
+            }
+
+            #[test]
+            fn replaces_invalid_utf_8() {
+                let StdoutLossy(output) = run_output!(test_helper(), "invalid utf-8 stdout");
+                assert_eq!(output, "\u{FFFD}");
+            }
+
+            #[test]
+            fn does_not_relay_stdout() {
+                let context = Context::test();
+                let StdoutLossy(_) =
+                    run_result_with_context(context.clone(), Split("echo foo")).unwrap();
+                assert_eq!(context.stdout(), "");
+            }
+        }
+    }
+
+    mod stdout_file {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn writes_stdout_to_the_file() {
+            in_temporary_directory(|| {
+                run!(%"echo foo", StdoutFile("file"));
+                assert_eq!(fs::read_to_string("file").unwrap(), "foo\n");
+            });
+        }
+
+        #[test]
+        fn does_not_relay_stdout() {
+            in_temporary_directory(|| {
+                let context = Context::test();
+                run_result_with_context_unit(
+                    context.clone(),
+                    (Split("echo foo"), StdoutFile("file")),
+                )
+                .unwrap();
+                assert_eq!(context.stdout(), "");
+            });
+        }
+
+        #[test]
+        fn truncates_existing_files() {
+            in_temporary_directory(|| {
+                fs::write("file", "some long existing content").unwrap();
+                run!(%"echo foo", StdoutFile("file"));
+                assert_eq!(fs::read_to_string("file").unwrap(), "foo\n");
+            });
+        }
+
+        #[test]
+        fn can_be_combined_with_capturing_stdout() {
+            in_temporary_directory(|| {
+                let StdoutUntrimmed(output) = run_output!(%"echo foo", StdoutFile("file"));
+                assert_eq!(output, "foo\n");
+                assert_eq!(fs::read_to_string("file").unwrap(), "foo\n");
+            });
+        }
+
+        #[test]
+        fn errors_before_spawning_the_child_when_the_file_cannot_be_created() {
+            in_temporary_directory(|| {
+                let result: Result<(), Error> =
+                    run_result!(%"touch created", StdoutFile("does-not-exist/file"));
+                match result {
+                    Err(error @ Error::CommandIoError { .. }) => {
+                        let message = error.to_string();
+                        assert!(
+                            message.contains("does-not-exist/file: "),
+                            "{:?} does not contain the path",
+                            message
+                        );
+                    }
+                    result => panic!("expected CommandIoError, got: {:?}", result),
+                }
+                assert!(!PathBuf::from("created").exists());
+            });
+        }
+    }
+
+    mod stdout_append_file {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn appends_to_existing_files() {
+            in_temporary_directory(|| {
+                run!(%"echo foo", StdoutAppendFile("file"));
+                run!(%"echo bar", StdoutAppendFile("file"));
+                assert_eq!(fs::read_to_string("file").unwrap(), "foo\nbar\n");
+            });
+        }
+    }
+
+    mod stderr_file {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn writes_stderr_to_the_file() {
+            in_temporary_directory(|| {
+                run!(test_helper(), "write to stderr", StderrFile("file"));
+                assert_eq!(fs::read_to_string("file").unwrap(), "foo\n");
+            });
+        }
+
+        #[test]
+        fn does_not_relay_stderr() {
+            in_temporary_directory(|| {
+                let context = Context::test();
+                run_result_with_context_unit(
+                    context.clone(),
+                    (test_helper(), "write to stderr", StderrFile("file")),
+                )
+                .unwrap();
+                assert_eq!(context.stderr(), "");
+            });
+        }
+
+        #[test]
+        fn appends_to_existing_files() {
+            in_temporary_directory(|| {
+                run!(test_helper(), "write to stderr", StderrAppendFile("file"));
+                run!(test_helper(), "write to stderr", StderrAppendFile("file"));
+                assert_eq!(fs::read_to_string("file").unwrap(), "foo\nfoo\n");
+            });
+        }
+    }
+
+    mod tee_file {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn writes_stdout_to_the_file_and_relays_it() {
+            in_temporary_directory(|| {
+                let context = Context::test();
+                run_result_with_context_unit(
+                    context.clone(),
+                    (Split("echo foo"), StdoutTeeFile("file")),
+                )
+                .unwrap();
+                assert_eq!(fs::read_to_string("file").unwrap(), "foo\n");
+                assert_eq!(context.stdout(), "foo\n");
+            });
+        }
+
+        #[test]
+        fn writes_stderr_to_the_file_and_relays_it() {
+            in_temporary_directory(|| {
+                let context = Context::test();
+                run_result_with_context_unit(
+                    context.clone(),
+                    (test_helper(), "write to stderr", StderrTeeFile("file")),
+                )
+                .unwrap();
+                assert_eq!(fs::read_to_string("file").unwrap(), "foo\n");
+                assert_eq!(context.stderr(), "foo\n");
+            });
+        }
+
+        #[test]
+        fn truncates_existing_files() {
+            in_temporary_directory(|| {
+                fs::write("file", "some long existing content").unwrap();
+                run!(%"echo foo", StdoutTeeFile("file"), Quiet);
+                assert_eq!(fs::read_to_string("file").unwrap(), "foo\n");
+            });
+        }
+
+        #[test]
+        fn captured_output_is_written_to_the_file_but_not_relayed() {
+            in_temporary_directory(|| {
+                let context = Context::test();
+                let StdoutTrimmed(output) = run_result_with_context(
+                    context.clone(),
+                    (Split("echo foo"), StdoutTeeFile("file")),
+                )
+                .unwrap();
+                assert_eq!(output, "foo");
+                assert_eq!(fs::read_to_string("file").unwrap(), "foo\n");
+                assert_eq!(context.stdout(), "");
+            });
+        }
+
+        #[test]
+        fn stderr_prefix_only_applies_to_relayed_output() {
+            in_temporary_directory(|| {
+                let context = Context::test();
+                run_result_with_context_unit(
+                    context.clone(),
+                    (
+                        test_helper(),
+                        "write to stderr",
+                        StderrTeeFile("file"),
+                        StderrPrefix("[prefix] "),
+                    ),
+                )
+                .unwrap();
+                assert_eq!(fs::read_to_string("file").unwrap(), "foo\n");
+                assert_eq!(context.stderr(), "[prefix] foo\n");
+            });
+        }
+
+        #[cfg(target_os = "linux")]
+        #[test]
+        fn errors_when_writing_to_the_file_fails() {
+            let result: Result<(), Error> = run_result!(%"echo foo", StdoutTeeFile("/dev/full"));
+            match result {
+                Err(Error::CommandIoError { .. }) => {}
+                result => panic!("expected CommandIoError, got: {:?}", result),
+            }
+        }
+    }
+
+    mod quiet {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn does_not_relay_stdout_or_stderr() {
+            let context = Context::test();
+            run_result_with_context_unit(
+                context.clone(),
+                (
+                    test_helper(),
+                    "write to stdout and stderr interleaved",
+                    Quiet,
+                ),
+            )
+            .unwrap();
+            assert_eq!(context.stdout(), "");
+            assert_eq!(context.stderr(), "");
+        }
+
+        #[test]
+        fn does_not_relay_output_of_failing_commands() {
+            let context = Context::test();
+            let result = run_result_with_context_unit(
+                context.clone(),
+                (test_helper(), "write to stderr and exit with 42", Quiet),
+            );
+            assert!(result.is_err());
+            assert_eq!(context.stderr(), "");
+        }
+
+        #[test]
+        fn still_allows_to_capture_output() {
+            let context = Context::test();
+            let StdoutTrimmed(output) = run_result_with_context(
+                context.clone(),
+                (
+                    test_helper(),
+                    "write to stdout and stderr interleaved",
+                    Quiet,
+                ),
+            )
+            .unwrap();
+            assert_eq!(output, "to stdout\nto stdout");
+            assert_eq!(context.stderr(), "");
+        }
+
+        #[test]
+        fn does_not_affect_log_command() {
+            let context = Context::test();
+            run_result_with_context_unit(context.clone(), (LogCommand, Split("echo foo"), Quiet))
+                .unwrap();
+            assert_eq!(context.stdout(), "");
+            assert_eq!(context.stderr(), "+ echo foo\n");
+        }
+
+        #[test]
+        fn does_not_affect_output_files() {
+            in_temporary_directory(|| {
+                run!(%"echo foo", Quiet, StdoutFile("file"));
+                assert_eq!(fs::read_to_string("file").unwrap(), "foo\n");
+            });
+        }
+
+        #[test]
+        fn silences_all_commands_of_a_pipe() {
+            let context = Context::test();
+            run_result_with_context_unit(
+                context.clone(),
+                (
+                    Pipe(
+                        (test_helper(), "write to stdout and stderr interleaved"),
+                        (test_helper(), "reverse"),
+                    ),
+                    Quiet,
+                ),
+            )
+            .unwrap();
+            assert_eq!(context.stdout(), "");
+            assert_eq!(context.stderr(), "");
+        }
+    }
+
+    mod interactive {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn does_not_relay_output_through_cradle() {
+            let context = Context::test();
+            run_result_with_context_unit(
+                context.clone(),
+                (
+                    test_helper(),
+                    "write to stdout and stderr interleaved",
+                    Interactive,
+                ),
+            )
+            .unwrap();
+            assert_eq!(context.stdout(), "");
+            assert_eq!(context.stderr(), "");
+        }
+
+        #[cfg(target_os = "linux")]
+        #[test]
+        fn child_processes_inherit_the_parents_standard_streams() {
+            in_temporary_directory(|| {
+                run!(
+                    %"sh -c",
+                    "links=$(for fd in 0 1 2; do readlink /proc/$$/fd/$fd; done); echo \"$links\" > file",
+                    Interactive
+                );
+                let expected: Vec<String> = (0..3)
+                    .map(|fd| {
+                        fs::read_link(format!("/proc/self/fd/{}", fd))
+                            .unwrap()
+                            .to_string_lossy()
+                            .into_owned()
+                    })
+                    .collect();
+                let actual = fs::read_to_string("file").unwrap();
+                assert_eq!(actual.lines().collect::<Vec<_>>(), expected);
+            });
+        }
+
+        #[test]
+        fn still_writes_stdin_inputs() {
+            in_temporary_directory(|| {
+                run!(%"sh -c", "cat > file", Stdin("foo"), Interactive);
+                assert_eq!(fs::read_to_string("file").unwrap(), "foo");
+            });
+        }
+
+        #[test]
+        fn works_with_status() {
+            let Status(status) = run_output!("false", Interactive);
+            assert!(!status.success());
+        }
+
+        #[test]
+        fn errors_when_combined_with_capturing_outputs() {
+            let result: Result<StdoutTrimmed, Error> = run_result!(%"echo foo", Interactive);
+            match result {
+                Err(Error::InteractiveOutputCaptured { .. }) => {}
+                result => panic!("expected InteractiveOutputCaptured, got: {:?}", result),
+            }
+            let result: Result<Stderr, Error> = run_result!(%"echo foo", Interactive);
+            match result {
+                Err(Error::InteractiveOutputCaptured { .. }) => {}
+                result => panic!("expected InteractiveOutputCaptured, got: {:?}", result),
+            }
+        }
+
+        #[test]
+        fn error_message() {
+            let result: Result<StdoutTrimmed, Error> = run_result!(%"echo foo", Interactive);
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "echo foo:\n  Interactive cannot be combined with outputs that capture stdout or stderr"
+            );
+        }
+
+        #[test]
+        fn allows_capturing_the_output_of_earlier_pipe_stages() {
+            in_temporary_directory(|| {
+                run!(
+                    Pipe(Split("echo foo"), (Split("sh -c"), "cat > file")),
+                    Interactive
+                );
+                assert_eq!(fs::read_to_string("file").unwrap(), "foo\n");
+            });
+        }
+    }
+
+    mod stderr_to_stdout {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn captures_stderr_in_stdout() {
+            let StdoutUntrimmed(output) =
+                run_output!(test_helper(), "write to stderr", StderrToStdout);
+            assert_eq!(output, "foo\n");
+        }
+
+        #[test]
+        fn merges_both_streams_in_order() {
+            let StdoutUntrimmed(output) = run_output!(
+                test_helper(),
+                "write to stdout and stderr interleaved",
+                StderrToStdout
+            );
+            assert_eq!(output, "to stdout\nto stderr\nto stdout\n");
+        }
+
+        #[test]
+        fn captured_stderr_is_empty() {
+            let (StdoutUntrimmed(stdout), Stderr(stderr)) =
+                run_output!(test_helper(), "write to stderr", StderrToStdout);
+            assert_eq!(stdout, "foo\n");
+            assert_eq!(stderr, "");
+        }
+
+        #[test]
+        fn relays_stderr_to_the_parents_stdout() {
+            let context = Context::test();
+            run_result_with_context_unit(
+                context.clone(),
+                (test_helper(), "write to stderr", StderrToStdout),
+            )
+            .unwrap();
+            assert_eq!(context.stdout(), "foo\n");
+            assert_eq!(context.stderr(), "");
+        }
+
+        #[test]
+        fn works_with_stdout_files() {
+            in_temporary_directory(|| {
+                run!(
+                    test_helper(),
+                    "write to stdout and stderr interleaved",
+                    StderrToStdout,
+                    StdoutFile("file")
+                );
+                assert_eq!(
+                    fs::read_to_string("file").unwrap(),
+                    "to stdout\nto stderr\nto stdout\n"
+                );
+            });
+        }
+    }
+
+    mod stdout_tail {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn keeps_only_the_last_lines() {
+            let StdoutUntrimmed(output) = run_output!("echo", "foo\nbar\nbaz", StdoutTail(2));
+            assert_eq!(output, "bar\nbaz\n");
+        }
+
+        #[test]
+        fn keeps_everything_when_there_are_fewer_lines() {
+            let StdoutUntrimmed(output) = run_output!("echo", "foo\nbar", StdoutTail(5));
+            assert_eq!(output, "foo\nbar\n");
+        }
+
+        #[test]
+        fn keeps_a_last_line_without_newline() {
+            let StdoutUntrimmed(output) = run_output!(%"echo -n", "foo\nbar\nbaz", StdoutTail(2));
+            assert_eq!(output, "bar\nbaz");
+        }
+
+        #[test]
+        fn zero_lines_result_in_empty_output() {
+            let StdoutUntrimmed(output) = run_output!(%"echo foo", StdoutTail(0));
+            assert_eq!(output, "");
+        }
+
+        #[test]
+        fn works_for_large_outputs() {
+            let StdoutTrimmed(output) = run_output!(%"seq 1 100000", StdoutTail(1));
+            assert_eq!(output, "100000");
+        }
+
+        #[test]
+        fn relays_all_output_with_stdout_tee() {
+            let context = Context::test();
+            let StdoutTee(output) =
+                run_result_with_context(context.clone(), ("echo", "foo\nbar", StdoutTail(1)))
+                    .unwrap();
+            assert_eq!(output, "bar\n");
+            assert_eq!(context.stdout(), "foo\nbar\n");
+        }
+
+        #[test]
+        fn works_with_stderr_to_stdout() {
+            let StdoutUntrimmed(output) = run_output!(
+                test_helper(),
+                "write to stdout and stderr interleaved",
+                StderrToStdout,
+                StdoutTail(2)
+            );
+            assert_eq!(output, "to stderr\nto stdout\n");
+        }
+
+        mod stdout_tailed {
+            use super::*;
+            use pretty_assertions::assert_eq;
+
+            #[test]
+            fn reports_dropped_lines() {
+                let StdoutTailed { stdout, truncated } =
+                    run_output!("echo", "foo\nbar\nbaz", StdoutTail(2));
+                assert_eq!(stdout, "bar\nbaz\n");
+                assert!(truncated);
+            }
+
+            #[test]
+            fn exactly_the_limit_is_not_truncated() {
+                let StdoutTailed { stdout, truncated } =
+                    run_output!("echo", "foo\nbar", StdoutTail(2));
+                assert_eq!(stdout, "foo\nbar\n");
+                assert!(!truncated);
+            }
+
+            #[test]
+            fn last_line_without_newline_counts_towards_the_limit() {
+                let StdoutTailed { stdout, truncated } =
+                    run_output!(%"echo -n", "foo\nbar\nbaz", StdoutTail(2));
+                assert_eq!(stdout, "bar\nbaz");
+                assert!(truncated);
+                let StdoutTailed { truncated, .. } =
+                    run_output!(%"echo -n", "foo\nbar", StdoutTail(2));
+                assert!(!truncated);
+            }
+
+            #[test]
+            fn is_not_truncated_without_stdout_tail() {
+                let StdoutTailed { stdout, truncated } = run_output!(%"seq 1 1000");
+                assert_eq!(stdout.lines().count(), 1000);
+                assert!(!truncated);
+            }
+
+            #[test]
+            fn works_with_stderr_to_stdout() {
+                let StdoutTailed { truncated, .. } = run_output!(
+                    test_helper(),
+                    "write to stdout and stderr interleaved",
+                    StderrToStdout,
+                    StdoutTail(2)
+                );
+                assert!(truncated);
+                let StdoutTailed { truncated, .. } = run_output!(
+                    test_helper(),
+                    "write to stdout and stderr interleaved",
+                    StderrToStdout,
+                    StdoutTail(100)
+                );
+                assert!(!truncated);
+            }
+        }
+    }
+
+    mod on_stdout_line {
+        use super::*;
+        use pretty_assertions::assert_eq;
+        use std::{
+            sync::mpsc::{channel, Receiver},
+            thread,
+        };
+
+        fn line_collector() -> (
+            OnStdoutLine<impl FnMut(&str) + Send + 'static>,
+            Receiver<String>,
+        ) {
+            let (sender, receiver) = channel();
+            (
+                OnStdoutLine(move |line: &str| sender.send(line.to_string()).unwrap()),
+                receiver,
+            )
+        }
+
+        #[test]
+        fn calls_the_function_for_every_line() {
+            let (on_stdout_line, receiver) = line_collector();
+            let StdoutUntrimmed(_) = run_output!("echo", "foo\nbar", on_stdout_line);
+            assert_eq!(receiver.iter().collect::<Vec<_>>(), vec!["foo", "bar"]);
+        }
+
+        #[test]
+        fn passes_final_lines_without_newline() {
+            let (on_stdout_line, receiver) = line_collector();
+            let StdoutUntrimmed(_) = run_output!(%"echo -n", "foo\nbar", on_stdout_line);
+            assert_eq!(receiver.iter().collect::<Vec<_>>(), vec!["foo", "bar"]);
+        }
+
+        #[test]
+        fn strips_carriage_returns() {
+            let (on_stdout_line, receiver) = line_collector();
+            let StdoutUntrimmed(_) = run_output!(%"printf", "foo\r\nbar\r\n", on_stdout_line);
+            assert_eq!(receiver.iter().collect::<Vec<_>>(), vec!["foo", "bar"]);
+        }
+
+        #[test]
+        fn buffers_lines_that_span_multiple_reads() {
+            let (on_stdout_line, receiver) = line_collector();
+            let StdoutUntrimmed(_) =
+                run_output!("echo", "foo bar\nbaz", ReadBufferSize(2), on_stdout_line);
+            assert_eq!(receiver.iter().collect::<Vec<_>>(), vec!["foo bar", "baz"]);
+        }
+
+        #[test]
+        fn calls_the_function_while_the_child_is_running() {
+            in_temporary_directory(|| {
+                let (on_stdout_line, receiver) = line_collector();
+                let thread = thread::spawn(move || {
+                    run_result_with_context_unit(
+                        Context::test(),
+                        (
+                            test_helper(),
+                            "stream chunk then wait for file",
+                            on_stdout_line,
+                        ),
+                    )
+                    .unwrap();
+                });
+                assert_eq!(receiver.recv().unwrap(), "foo");
+                fs::write("file", "").unwrap();
+                thread.join().unwrap();
+            });
+        }
+
+        #[test]
+        fn does_not_affect_relaying_or_capturing() {
+            let context = Context::test();
+            let (on_stdout_line, receiver) = line_collector();
+            let StdoutTee(output) =
+                run_result_with_context(context.clone(), ("echo", "foo\nbar", on_stdout_line))
+                    .unwrap();
+            assert_eq!(output, "foo\nbar\n");
+            assert_eq!(context.stdout(), "foo\nbar\n");
+            assert_eq!(receiver.iter().collect::<Vec<_>>(), vec!["foo", "bar"]);
+        }
+    }
+
+    mod read_buffer_size {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn captures_output_with_small_buffers() {
+            let StdoutUntrimmed(output) = run_output!("echo", "foo\nbar", ReadBufferSize(1));
+            assert_eq!(output, "foo\nbar\n");
+        }
+
+        #[test]
+        fn captures_output_with_large_buffers() {
+            let StdoutTrimmed(output) = run_output!(%"seq 1 100000", ReadBufferSize(1024 * 1024));
+            assert_eq!(output.lines().count(), 100000);
+        }
+
+        #[test]
+        fn treats_zero_as_one() {
+            let (StdoutUntrimmed(stdout), Stderr(stderr)) = run_output!(
+                %"sh -c",
+                "echo foo; echo bar >&2",
+                ReadBufferSize(0)
+            );
+            assert_eq!((stdout.as_str(), stderr.as_str()), ("foo\n", "bar\n"));
+        }
+
+        #[test]
+        fn relays_output() {
+            let context = Context::test();
+            run_result_with_context_unit(context.clone(), (Split("echo foo"), ReadBufferSize(2)))
+                .unwrap();
+            assert_eq!(context.stdout(), "foo\n");
+        }
+    }
+
+    mod max_output_size {
+        use super::*;
+        use pretty_assertions::assert_eq;
+        use std::time::{Duration, Instant};
+
+        fn assert_output_too_large<T: std::fmt::Debug>(result: Result<T, Error>, expected: usize) {
+            match result {
+                Err(Error::OutputTooLarge { limit, .. }) => assert_eq!(limit, expected),
+                result => panic!("expected OutputTooLarge, got: {:?}", result),
+            }
+        }
+
+        #[test]
+        fn allows_output_within_the_limit() {
+            let StdoutUntrimmed(output) = run_output!(%"echo foo", MaxOutputSize(4));
+            assert_eq!(output, "foo\n");
+        }
+
+        #[test]
+        fn errors_when_the_output_exceeds_the_limit() {
+            let result: Result<StdoutUntrimmed, Error> = run_result!(%"echo foo", MaxOutputSize(3));
+            assert_output_too_large(result, 3);
+        }
+
+        #[test]
+        fn stops_child_processes_with_unbounded_output() {
+            let result: Result<StdoutUntrimmed, Error> = run_result!("yes", MaxOutputSize(1000));
+            assert_output_too_large(result, 1000);
+        }
+
+        #[test]
+        #[cfg(unix)]
+        fn kills_child_processes_that_ignore_closed_pipes() {
+            let result: Result<StdoutUntrimmed, Error> = run_result!(
+                %"sh -c",
+                "trap '' PIPE; while true; do echo foo; done",
+                MaxOutputSize(1000)
+            );
+            assert_output_too_large(result, 1000);
+        }
+
+        #[test]
+        #[cfg(unix)]
+        fn kills_child_processes_when_stderr_exceeds_the_limit() {
+            let start = Instant::now();
+            let result: Result<Stderr, Error> = run_result!(
+                %"sh -c",
+                "trap '' PIPE; head -c 100000 /dev/zero >&2; sleep 5",
+                MaxOutputSize(10)
+            );
+            assert_output_too_large(result, 10);
+            assert!(start.elapsed() < Duration::from_secs(4));
+        }
+
+        #[test]
+        #[cfg(unix)]
+        fn kills_child_processes_when_combined_output_exceeds_the_limit() {
+            let start = Instant::now();
+            let result: Result<CombinedOutput, Error> = run_result!(
+                %"sh -c",
+                "trap '' PIPE; head -c 100000 /dev/zero >&2; sleep 5",
+                MaxOutputSize(10)
+            );
+            assert_output_too_large(result, 10);
+            assert!(start.elapsed() < Duration::from_secs(4));
+        }
+
+        #[test]
+        fn counts_captured_stderr() {
+            let result: Result<Stderr, Error> =
+                run_result!(test_helper(), "write to stderr", MaxOutputSize(1));
+            assert_output_too_large(result, 1);
+        }
+
+        #[test]
+        fn counts_all_captured_streams_together() {
+            let result: Result<CapturedOutput, Error> = run_result!(
+                test_helper(),
+                "write to stdout and stderr interleaved",
+                MaxOutputSize(25)
+            );
+            assert_output_too_large(result, 25);
+        }
+
+        #[test]
+        fn ignores_relayed_output() {
+            let context = Context::test();
+            run_result_with_context_unit(context.clone(), (Split("echo foo"), MaxOutputSize(1)))
+                .unwrap();
+            assert_eq!(context.stdout(), "foo\n");
+        }
+
+        #[test]
+        fn works_with_stderr_to_stdout() {
+            let result: Result<StdoutUntrimmed, Error> = run_result!(
+                test_helper(),
+                "write to stdout and stderr interleaved",
+                StderrToStdout,
+                MaxOutputSize(25)
+            );
+            assert_output_too_large(result, 25);
+        }
+
+        #[test]
+        fn is_displayed_with_the_command() {
+            let result: Result<StdoutUntrimmed, Error> = run_result!(%"echo foo", MaxOutputSize(3));
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "echo foo:\n  captured output exceeded the maximum size of 3 bytes"
+            );
+        }
+    }
+
+    mod executable {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn is_used_as_the_executable() {
+            let StdoutTrimmed(output) = run_output!(Executable("echo"), "foo");
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn is_always_the_first_argument() {
+            let StdoutTrimmed(output) = run_output!("foo", Executable("echo"), "bar");
+            assert_eq!(output, "foo bar");
+        }
+
+        #[test]
+        fn is_included_in_preview_args() {
+            assert_eq!(
+                preview_args(("foo", Executable("echo"))),
+                vec!["echo", "foo"]
+            );
+        }
+
+        #[test]
+        fn cannot_be_given_more_than_once() {
+            let result: Result<(), Error> =
+                run_result!("foo", Executable("echo"), Executable("ls"));
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "ls echo foo:\n  Executable cannot be given more than once"
+            );
+        }
+
+        #[test]
+        fn works_in_pipes() {
+            let StdoutTrimmed(output) = run_output!(Pipe(
+                ("foo", Executable("echo")),
+                (Executable("tr"), "a-z", "A-Z")
+            ));
+            assert_eq!(output, "FOO");
+        }
+    }
+
+    mod split {
+        use super::*;
+
+        #[test]
+        fn splits_words_by_whitespace() {
+            let StdoutTrimmed(output) = run_output!(Split("echo foo"));
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn skips_multiple_whitespace_characters() {
+            let StdoutUntrimmed(output) = run_output!("echo", Split("foo  bar"));
+            assert_eq!(output, "foo bar\n");
+        }
+
+        #[test]
+        fn trims_leading_whitespace() {
+            let StdoutTrimmed(output) = run_output!(Split(" echo foo"));
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn trims_trailing_whitespace() {
+            let StdoutUntrimmed(output) = run_output!("echo", Split("foo "));
+            assert_eq!(output, "foo\n");
+        }
+
+        #[test]
+        fn works_for_owned_strings() {
+            let command: String = format!("echo {}", "foo");
+            let StdoutUntrimmed(output) = run_output!(Split(command));
+            assert_eq!(output, "foo\n");
+        }
+
+        #[test]
+        fn works_for_references_to_strings() {
+            let command: String = "echo foo".to_string();
+            let StdoutUntrimmed(output) = run_output!(Split(&command));
+            assert_eq!(output, "foo\n");
+        }
+
+        mod percent_sign {
+            use super::*;
+
+            #[test]
+            fn splits_words() {
+                let StdoutUntrimmed(output) = run_output!(%"echo foo");
+                assert_eq!(output, "foo\n");
+            }
+
+            #[test]
+            fn works_for_later_arguments() {
+                let StdoutUntrimmed(output) = run_output!("echo", %"foo\tbar");
+                assert_eq!(output, "foo bar\n");
+            }
+
+            #[test]
+            fn for_first_of_multiple_arguments() {
+                let StdoutUntrimmed(output) = run_output!(%"echo foo", "bar");
+                assert_eq!(output, "foo bar\n");
+            }
+
+            #[test]
+            fn non_literals() {
+                let command = "echo foo";
+                let StdoutUntrimmed(output) = run_output!(%command);
+                assert_eq!(output, "foo\n");
+            }
+
+            #[test]
+            fn owned_strings() {
+                let command: String = "echo foo".to_string();
+                let StdoutUntrimmed(output) = run_output!(%command);
+                assert_eq!(output, "foo\n");
+            }
+
+            #[test]
+            fn in_run() {
+                run!(%"echo foo");
+            }
+
+            #[test]
+            fn in_run_result() {
+                let StdoutTrimmed(_) = run_result!(%"echo foo").unwrap();
+            }
+        }
+    }
+
+    mod split_os {
+        use super::*;
+
+        #[test]
+        fn splits_words_by_whitespace() {
+            let StdoutTrimmed(output) = run_output!(SplitOs(OsString::from("echo foo")));
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn skips_leading_trailing_and_multiple_whitespace_characters() {
+            let StdoutUntrimmed(output) = run_output!(SplitOs(OsStr::new(" echo\tfoo \n bar ")));
+            assert_eq!(output, "foo bar\n");
+        }
+
+        #[test]
+        #[cfg(unix)]
+        fn passes_invalid_utf8_through_unchanged() {
+            use std::os::unix::ffi::OsStringExt;
+
+            let command = OsString::from_vec(b"echo \xff foo\xfe".to_vec());
+            let StdoutBytes(output) = run_output!(SplitOs(command));
+            assert_eq!(output, b"\xff foo\xfe\n".to_vec());
+        }
+    }
+
+    mod nul_separated {
+        use super::*;
+
+        #[test]
+        fn splits_arguments_on_nul_bytes() {
+            let Lines(lines) = run_output!("printf", "%s\\n", NulSeparated("foo bar\0baz\0"));
+            assert_eq!(lines, vec!["foo bar", "baz"]);
+        }
+
+        #[test]
+        fn keeps_empty_arguments_between_nul_bytes() {
+            let Lines(lines) = run_output!("printf", "[%s]\\n", NulSeparated("foo\0\0bar"));
+            assert_eq!(lines, vec!["[foo]", "[]", "[bar]"]);
+        }
+
+        #[test]
+        fn empty_input_results_in_no_arguments() {
+            let StdoutUntrimmed(output) = run_output!("echo", NulSeparated(""));
+            assert_eq!(output, "\n");
+        }
+
+        #[test]
+        fn can_be_fed_with_nul_separated_lines() {
+            in_temporary_directory(|| {
+                fs::write("foo bar", "foo").unwrap();
+                let StdoutBytes(files) = run_output!(%"find . -type f -print0");
+                let StdoutUntrimmed(output) = run_output!("cat", NulSeparated(files));
+                assert_eq!(output, "foo");
+            });
+        }
+    }
+
+    mod shell {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn runs_commands_through_the_shell() {
+            let StdoutTrimmed(output) = run_output!(Shell("echo foo"));
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        #[cfg(unix)]
+        fn supports_shell_features() {
+            in_temporary_directory(|| {
+                run!(%"touch a.rs b.rs c.txt");
+                let StdoutTrimmed(output) =
+                    run_output!(Shell("ls *.rs | wc -l && false || echo done"));
+                assert_eq!(
+                    output.split_whitespace().collect::<Vec<_>>(),
+                    vec!["2", "done"]
+                );
+            });
+        }
+
+        #[test]
+        #[cfg(unix)]
+        fn uses_sh_on_unix() {
+            assert_eq!(preview_args(Shell("foo bar")), vec!["sh", "-c", "foo bar"]);
+        }
+
+        #[test]
+        #[cfg(windows)]
+        fn uses_cmd_on_windows() {
+            assert_eq!(preview_args(Shell("foo bar")), vec!["cmd", "/C", "foo bar"]);
+        }
+
+        #[test]
+        fn returns_the_exit_code_of_the_shell() {
+            let result: Result<(), Error> = run_result!(Shell("exit 42"));
+            assert_eq!(result.unwrap_err().exit_code(), Some(42));
+        }
+    }
+
+    mod splitting_with_library_functions {
+        use super::*;
+
+        #[test]
+        fn allow_to_use_split() {
+            let StdoutTrimmed(output) = run_output!("echo foo".split(' '));
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn split_whitespace() {
+            let StdoutTrimmed(output) = run_output!("echo foo".split_whitespace());
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn split_ascii_whitespace() {
+            let StdoutTrimmed(output) = run_output!("echo foo".split_ascii_whitespace());
+            assert_eq!(output, "foo");
+        }
+    }
+
+    mod paths {
+        use super::*;
+        use pretty_assertions::assert_eq;
+        use std::path::Path;
+
+        fn write_test_script() -> PathBuf {
+            if cfg!(unix) {
+                let file = PathBuf::from("./test-script");
+                let script = "#!/usr/bin/env bash\necho test-output\n";
+                fs::write(&file, script).unwrap();
+                run!(%"chmod +x test-script");
+                file
+            } else {
+                let file = PathBuf::from("./test-script.bat");
+                let script = "@echo test-output\n";
+                fs::write(&file, script).unwrap();
+                file
+            }
+        }
+
+        #[test]
+        fn ref_path_as_argument() {
+            in_temporary_directory(|| {
+                let file: &Path = Path::new("file");
+                fs::write(file, "test-contents").unwrap();
+                let StdoutUntrimmed(output) = run_output!("cat", file);
+                assert_eq!(output, "test-contents");
+            })
+        }
+
+        #[test]
+        fn ref_path_as_executable() {
+            in_temporary_directory(|| {
+                let file: &Path = &write_test_script();
+                let StdoutTrimmed(output) = run_output!(file);
+                assert_eq!(output, "test-output");
+            })
+        }
+
+        #[test]
+        fn path_buf_as_argument() {
+            in_temporary_directory(|| {
+                let file: PathBuf = PathBuf::from("file");
+                fs::write(&file, "test-contents").unwrap();
+                let StdoutUntrimmed(output) = run_output!("cat", file);
+                assert_eq!(output, "test-contents");
+            })
+        }
+
+        #[test]
+        fn path_buf_as_executable() {
+            in_temporary_directory(|| {
+                let file: PathBuf = write_test_script();
+                let StdoutTrimmed(output) = run_output!(file);
+                assert_eq!(output, "test-output");
+            })
+        }
+    }
+
+    mod stdin {
+        use super::*;
+
+        #[test]
+        fn allows_to_pass_in_strings_as_stdin() {
+            let StdoutUntrimmed(output) = run_output!(test_helper(), "reverse", Stdin("foo"));
+            assert_eq!(output, "oof");
+        }
+
+        #[test]
+        fn allows_passing_in_u8_slices_as_stdin() {
+            let StdoutUntrimmed(output) = run_output!(test_helper(), "reverse", Stdin(&[0, 1, 2]));
+            assert_eq!(output, "\x02\x01\x00");
+        }
+
+        mod stdin_line {
+            use super::*;
+
+            #[test]
+            fn appends_a_newline() {
+                let StdoutUntrimmed(output) =
+                    run_output!(test_helper(), "reverse", StdinLine("foo"));
+                assert_eq!(output, "\noof");
+            }
+
+            #[test]
+            fn does_not_append_a_newline_if_there_already_is_one() {
+                let StdoutUntrimmed(output) =
+                    run_output!(test_helper(), "reverse", StdinLine("foo\n"));
+                assert_eq!(output, "\noof");
+            }
+
+            #[test]
+            fn multiple_lines_get_their_own_newlines() {
+                let StdoutUntrimmed(output) =
+                    run_output!(test_helper(), "reverse", StdinLine("foo"), StdinLine("bar"));
+                assert_eq!(output, "\nrab\noof");
+            }
+
+            #[test]
+            fn can_be_combined_with_stdin() {
+                let StdoutUntrimmed(output) =
+                    run_output!(test_helper(), "reverse", StdinLine("foo"), Stdin("bar"));
+                assert_eq!(output, "rab\noof");
+            }
+        }
+
+        #[test]
+        #[cfg(unix)]
+        fn stdin_is_closed_by_default() {
+            let StdoutTrimmed(output) = run_output!(test_helper(), "wait until stdin is closed");
+            assert_eq!(output, "stdin is closed");
+        }
+
+        mod stdin_null {
+            use super::*;
+
+            #[test]
+            fn child_processes_read_empty_input() {
+                let StdoutUntrimmed(output) = run_output!("cat", StdinNull);
+                assert_eq!(output, "");
+            }
+
+            #[test]
+            #[cfg(unix)]
+            fn connects_stdin_to_the_null_device() {
+                run!(%"sh -c", "test -c /dev/stdin", StdinNull);
+            }
+
+            #[test]
+            #[cfg(unix)]
+            fn stdin_is_a_pipe_by_default() {
+                let Status(status) = run_output!(%"sh -c", "test -c /dev/stdin");
+                assert!(!status.success());
+            }
+
+            #[test]
+            #[cfg(unix)]
+            fn works_with_pipes() {
+                run!(
+                    Pipe((Split("sh -c"), "test -c /dev/stdin"), "cat"),
+                    StdinNull
+                );
+            }
+
+            #[test]
+            fn cannot_be_combined_with_stdin() {
+                let result: Result<(), Error> = run_result!("cat", StdinNull, Stdin("foo"));
+                let error = result.unwrap_err();
+                match error {
+                    Error::MultipleStdinSources { .. } => {}
+                    _ => panic!("should match Error::MultipleStdinSources"),
+                }
+                assert_eq!(
+                    error.to_string(),
+                    "cat:\n  multiple sources for stdin were given"
+                );
+            }
+        }
+
+        #[test]
+        fn writing_too_many_bytes_into_a_non_reading_child_may_error() {
+            let big_string = String::from_utf8(vec![b'a'; 2_usize.pow(16) + 1]).unwrap();
+            let result: Result<(), crate::Error> = run_result!("true", Stdin(big_string));
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "true:\n  the child process stopped reading stdin before all input was written"
+            );
+        }
+
+        #[test]
+        fn not_consumed_stdin_errors_can_be_matched_against() {
+            let result: Result<(), crate::Error> =
+                run_result!("true", Stdin(vec![b'a'; 2_usize.pow(16) + 1]));
+            match result {
+                Err(Error::StdinNotConsumed { source, .. }) => {
+                    assert_eq!(source.kind(), std::io::ErrorKind::BrokenPipe);
+                }
+                _ => panic!("should match Error::StdinNotConsumed"),
+            }
+        }
+
+        #[test]
+        fn multiple_stdin_arguments_are_all_passed_into_the_child_process() {
+            let StdoutUntrimmed(output) =
+                run_output!(test_helper(), "reverse", Stdin("foo"), Stdin("bar"));
+            assert_eq!(output, "raboof");
+        }
+
+        #[test]
+        fn works_for_owned_strings() {
+            let argument: String = "foo".to_string();
+            let StdoutUntrimmed(output) = run_output!(test_helper(), "reverse", Stdin(argument));
+            assert_eq!(output, "oof");
+        }
+
+        mod stdin_file {
+            use super::*;
+
+            #[test]
+            fn writes_file_contents_to_stdin() {
+                in_temporary_directory(|| {
+                    fs::write("file", "foo").unwrap();
+                    let StdoutUntrimmed(output) =
+                        run_output!(test_helper(), "reverse", StdinFile("file"));
+                    assert_eq!(output, "oof");
+                });
+            }
+
+            #[test]
+            fn can_be_combined_with_stdin() {
+                in_temporary_directory(|| {
+                    fs::write("file", "bar").unwrap();
+                    let StdoutUntrimmed(output) = run_output!(
+                        test_helper(),
+                        "reverse",
+                        Stdin("foo"),
+                        StdinFile("file"),
+                        Stdin("baz")
+                    );
+                    assert_eq!(output, "zabraboof");
+                });
+            }
+
+            #[test]
+            fn missing_files_produce_command_io_errors_that_include_the_path() {
+                in_temporary_directory(|| {
+                    let result: Result<(), Error> =
+                        run_result!(test_helper(), "reverse", StdinFile("does-not-exist"));
+                    match result {
+                        Err(error @ Error::CommandIoError { .. }) => {
+                            let message = error.to_string();
+                            assert!(
+                                message.contains("does-not-exist: "),
+                                "{:?} does not contain the path",
+                                message
+                            );
+                        }
+                        result => panic!("expected CommandIoError, got: {:?}", result),
+                    }
+                });
+            }
+        }
+
+        mod stdin_from_file {
+            use super::*;
+            use std::{fs::File, time::Duration};
+
+            #[test]
+            fn connects_the_file_to_stdin() {
+                in_temporary_directory(|| {
+                    fs::write("file", "foo").unwrap();
+                    let StdoutUntrimmed(output) = run_output!(
+                        test_helper(),
+                        "reverse",
+                        StdinFromFile(File::open("file").unwrap())
+                    );
+                    assert_eq!(output, "oof");
+                });
+            }
+
+            #[test]
+            fn works_for_the_first_command_in_pipes() {
+                in_temporary_directory(|| {
+                    fs::write("file", "foo").unwrap();
+                    let StdoutUntrimmed(output) = run_output!(
+                        Pipe((test_helper(), "reverse"), "cat"),
+                        StdinFromFile(File::open("file").unwrap())
+                    );
+                    assert_eq!(output, "oof");
+                });
+            }
+
+            #[cfg(unix)]
+            #[test]
+            fn every_retry_attempt_reads_the_whole_file() {
+                in_temporary_directory(|| {
+                    fs::write("file", "foo").unwrap();
+                    let result: Result<(), Error> = run_result!(
+                        ("sh", "-c", "cat >> attempts; false"),
+                        StdinFromFile(File::open("file").unwrap()),
+                        Retry {
+                            attempts: 3,
+                            delay: Duration::from_millis(0),
+                        }
+                    );
+                    assert!(result.is_err());
+                    assert_eq!(fs::read_to_string("attempts").unwrap(), "foofoofoo");
+                });
+            }
+
+            #[test]
+            fn cannot_be_combined_with_other_stdin_inputs() {
+                in_temporary_directory(|| {
+                    fs::write("file", "foo").unwrap();
+                    let result: Result<(), Error> = run_result!(
+                        "cat",
+                        Stdin("bar"),
+                        StdinFromFile(File::open("file").unwrap())
+                    );
+                    let error = result.unwrap_err();
+                    match error {
+                        Error::MultipleStdinSources { .. } => {}
+                        _ => panic!("should match Error::MultipleStdinSources"),
+                    }
+                    assert_eq!(
+                        error.to_string(),
+                        "cat:\n  multiple sources for stdin were given"
+                    );
+                });
+            }
+
+            #[test]
+            fn cannot_be_given_multiple_times() {
+                in_temporary_directory(|| {
+                    fs::write("a", "aaa").unwrap();
+                    fs::write("b", "bbb").unwrap();
+                    let result: Result<StdoutUntrimmed, Error> = run_result!(
+                        "cat",
+                        StdinFromFile(File::open("a").unwrap()),
+                        StdinFromFile(File::open("b").unwrap())
+                    );
+                    match result {
+                        Err(Error::MultipleStdinSources { .. }) => {}
+                        result => panic!("expected MultipleStdinSources, got: {:?}", result),
+                    }
+                });
+            }
+        }
+
+        mod stdin_reader {
+            use super::*;
+            use std::io::{self, Cursor, Read};
+
+            #[test]
+            fn writes_everything_from_the_reader_to_stdin() {
+                let StdoutUntrimmed(output) = run_output!(
+                    test_helper(),
+                    "reverse",
+                    StdinReader(Cursor::new(b"foo".to_vec()))
+                );
+                assert_eq!(output, "oof");
+            }
+
+            #[test]
+            fn can_be_combined_with_other_stdin_inputs() {
+                let StdoutUntrimmed(output) = run_output!(
+                    test_helper(),
+                    "reverse",
+                    Stdin("foo"),
+                    StdinReader(Cursor::new("bar")),
+                    Stdin("baz")
+                );
+                assert_eq!(output, "zabraboof");
+            }
+
+            #[test]
+            fn streams_big_amounts_of_data() {
+                let size = 2_usize.pow(20);
+                let StdoutUntrimmed(output) =
+                    run_output!("cat", StdinReader(io::repeat(b'a').take(size as u64)));
+                assert_eq!(output, "a".repeat(size));
+            }
+
+            #[test]
+            fn reading_errors_produce_command_io_errors() {
+                struct FailingReader;
+
+                impl Read for FailingReader {
+                    fn read(&mut self, _buffer: &mut [u8]) -> io::Result<usize> {
+                        Err(io::Error::new(io::ErrorKind::InvalidData, "reader failed"))
+                    }
+                }
+
+                let result: Result<(), Error> = run_result!("cat", StdinReader(FailingReader));
+                assert_eq!(result.unwrap_err().to_string(), "cat:\n  reader failed");
+            }
+        }
+
+        mod stdin_with {
+            use super::*;
+            use std::{
+                io::{self, Write},
+                time::Duration,
+            };
+
+            #[test]
+            fn writes_the_output_of_the_function_to_stdin() {
+                let StdoutUntrimmed(output) = run_output!(
+                    test_helper(),
+                    "reverse",
+                    StdinWith(|stdin: &mut dyn Write| stdin.write_all(b"foo"))
+                );
+                assert_eq!(output, "oof");
+            }
+
+            #[test]
+            fn can_be_combined_with_other_stdin_inputs() {
+                let StdoutUntrimmed(output) = run_output!(
+                    test_helper(),
+                    "reverse",
+                    Stdin("foo"),
+                    StdinWith(|stdin: &mut dyn Write| stdin.write_all(b"bar")),
+                    Stdin("baz")
+                );
+                assert_eq!(output, "zabraboof");
+            }
+
+            #[test]
+            fn streams_big_amounts_of_data() {
+                let StdoutUntrimmed(output) = run_output!(
+                    "cat",
+                    StdinWith(|stdin: &mut dyn Write| {
+                        for _ in 0..1024 {
+                            stdin.write_all(&[b'a'; 1024])?;
+                        }
+                        Ok(())
+                    })
+                );
+                assert_eq!(output, "a".repeat(2_usize.pow(20)));
+            }
+
+            #[test]
+            fn errors_from_the_function_produce_command_io_errors() {
+                let result: Result<(), Error> = run_result!(
+                    "cat",
+                    StdinWith(|_: &mut dyn Write| Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "function failed"
+                    )))
+                );
+                match result {
+                    Err(Error::CommandIoError { source, .. }) => {
+                        assert_eq!(source.kind(), io::ErrorKind::InvalidData);
+                        assert_eq!(source.to_string(), "function failed");
+                    }
+                    _ => panic!("should match Error::CommandIoError"),
+                }
+            }
+
+            #[test]
+            fn does_not_deadlock_when_the_child_stops_reading() {
+                let result: Result<(), Error> = run_result!(
+                    "true",
+                    StdinWith(|stdin: &mut dyn Write| loop {
+                        stdin.write_all(&[b'a'; 1024])?;
+                    })
+                );
+                match result {
+                    Err(Error::StdinNotConsumed { source, .. }) => {
+                        assert_eq!(source.kind(), io::ErrorKind::BrokenPipe);
+                    }
+                    _ => panic!("should match Error::StdinNotConsumed"),
+                }
+            }
+
+            #[test]
+            fn is_called_again_for_every_retry() {
+                let calls = Arc::new(Mutex::new(0));
+                let result: Result<(), Error> = run_result!(
+                    "false",
+                    Retry {
+                        attempts: 3,
+                        delay: Duration::from_millis(0),
+                    },
+                    StdinWith({
+                        let calls = calls.clone();
+                        move |_: &mut dyn Write| {
+                            *calls.lock().unwrap() += 1;
+                            Ok(())
+                        }
+                    })
+                );
+                assert!(result.is_err());
+                assert_eq!(*calls.lock().unwrap(), 3);
+            }
+        }
+    }
+
+    mod pipe {
+        use super::*;
+
+        #[test]
+        fn connects_stdout_of_the_first_command_to_stdin_of_the_second() {
+            let StdoutUntrimmed(output) =
+                run_output!(Pipe(("echo", "foo"), (test_helper(), "reverse")));
+            assert_eq!(output, "\noof");
+        }
+
+        #[test]
+        fn stdin_is_written_to_the_first_command() {
+            let StdoutUntrimmed(output) =
+                run_output!(Pipe((test_helper(), "reverse"), "cat"), Stdin("foo"));
+            assert_eq!(output, "oof");
+        }
+
+        #[test]
+        fn passes_big_amounts_of_data_through_the_pipe() {
+            let big_string = "a".repeat(2_usize.pow(20));
+            let StdoutUntrimmed(output) =
+                run_output!(Pipe("cat", "cat"), Stdin(big_string.clone()));
+            assert_eq!(output, big_string);
+        }
+
+        #[test]
+        fn pipes_can_be_nested() {
+            let StdoutUntrimmed(output) = run_output!(Pipe(
+                Pipe(("echo", "foo"), (test_helper(), "reverse")),
+                (test_helper(), "reverse")
+            ));
+            assert_eq!(output, "foo\n");
+            let StdoutUntrimmed(output) = run_output!(Pipe(
+                ("echo", "foo"),
+                Pipe((test_helper(), "reverse"), (test_helper(), "reverse"))
+            ));
+            assert_eq!(output, "foo\n");
+        }
+
+        #[test]
+        fn outputs_reflect_the_last_command() {
+            let Status(exit_status) = run_output!(Pipe("true", "false"));
+            assert!(!exit_status.success());
+            let Stderr(stderr) = run_output!(Pipe(
+                (test_helper(), "write to stderr"),
+                (test_helper(), "write to stderr")
+            ));
+            assert_eq!(stderr, "foo\n");
+        }
+
+        #[test]
+        fn failing_last_commands_produce_errors_with_the_full_pipeline() {
+            let result: Result<(), Error> = run_result!(Pipe("true", "false"));
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "true | false:\n  exited with exit code: 1"
+            );
+        }
+
+        #[test]
+        fn failing_first_commands_produce_errors_with_the_full_pipeline() {
+            let result: Result<(), Error> = run_result!(Pipe("false", "cat"));
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "false | cat:\n  exited with exit code: 1"
+            );
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn the_first_failing_command_determines_the_error() {
+            let result: Result<(), Error> =
+                run_result!(Pipe(("sh", "-c", "exit 3"), ("sh", "-c", "cat; exit 4")));
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "sh -c 'exit 3' | sh -c 'cat; exit 4':\n  exited with exit code: 3"
+            );
+        }
+
+        #[test]
+        fn missing_executables_in_any_stage_produce_file_not_found_errors() {
+            let result: Result<(), Error> = run_result!(Pipe("does-not-exist", "cat"));
+            match result {
+                Err(Error::FileNotFound { executable, .. }) => {
+                    assert_eq!(executable, "does-not-exist");
+                }
+                _ => panic!("should match Error::FileNotFound"),
+            }
+        }
+
+        #[test]
+        fn logs_the_full_pipeline_once() {
+            let context = Context::test();
+            let StdoutTrimmed(output) = run_result_with_context(
+                context.clone(),
+                (Pipe(("echo", "foo"), "cat"), LogCommand),
+            )
             .unwrap();
-            assert_eq!(context.stderr(), "+ echo foo�bar\n");
+            assert_eq!(output, "foo");
+            assert_eq!(context.stderr(), "+ echo foo | cat\n");
         }
     }
 
-    mod exit_status {
+    mod ok_exit_codes {
         use super::*;
 
         #[test]
-        fn zero() {
-            let Status(exit_status) = run_output!("true");
-            assert!(exit_status.success());
+        fn treats_the_given_exit_codes_as_success() {
+            let result: Result<(), Error> =
+                run_result!(test_helper(), "exit code 42", OkExitCodes(vec![42]));
+            assert!(result.is_ok());
         }
 
         #[test]
-        fn one() {
-            let Status(exit_status) = run_output!("false");
-            assert!(!exit_status.success());
+        fn other_exit_codes_still_produce_errors() {
+            let result: Result<(), Error> =
+                run_result!(test_helper(), "exit code 42", OkExitCodes(vec![1]));
+            match result {
+                Err(Error::NonZeroExitCode { .. }) => {}
+                _ => panic!("should match Error::NonZeroExitCode"),
+            }
         }
 
         #[test]
-        fn forty_two() {
-            let Status(exit_status) = run_output!(test_helper(), "exit code 42");
-            assert!(!exit_status.success());
-            assert_eq!(exit_status.code(), Some(42));
+        fn zero_is_always_success() {
+            let result: Result<(), Error> = run_result!("true", OkExitCodes(vec![1]));
+            assert!(result.is_ok());
+        }
+
+        #[test]
+        fn can_be_given_multiple_times() {
+            let result: Result<(), Error> = run_result!(
+                test_helper(),
+                "exit code 42",
+                OkExitCodes(vec![1]),
+                OkExitCodes(vec![42])
+            );
+            assert!(result.is_ok());
+        }
+
+        #[test]
+        fn does_not_change_the_returned_exit_code() {
+            let (Status(status), exit_code): (Status, i32) =
+                run_output!(test_helper(), "exit code 42", OkExitCodes(vec![42]));
+            assert!(!status.success());
+            assert_eq!(exit_code, 42);
+        }
+
+        #[test]
+        fn applies_to_individual_pipeline_stages() {
+            let StdoutTrimmed(output) = run_output!(Pipe(
+                (Split("grep foo"), Stdin("bar\n"), OkExitCodes(vec![1])),
+                Split("cat")
+            ));
+            assert_eq!(output, "");
+        }
+
+        #[test]
+        fn applies_to_all_commands_when_given_for_a_whole_pipe() {
+            let result: Result<(), Error> = run_result!(
+                Pipe(Split("grep foo"), Split("cat")),
+                Stdin("bar\n"),
+                OkExitCodes(vec![1])
+            );
+            assert!(result.is_ok());
+        }
+    }
+
+    mod retry {
+        use super::*;
+        use std::time::{Duration, Instant};
+
+        fn retry(attempts: u32) -> Retry {
+            Retry {
+                attempts,
+                delay: Duration::from_millis(10),
+            }
+        }
+
+        #[test]
+        fn retries_failing_commands() {
+            in_temporary_directory(|| {
+                let StdoutUntrimmed(output) =
+                    run_output!(test_helper(), "succeed on attempt", "3", retry(3));
+                assert_eq!(output, "attempt 3\n");
+            });
+        }
+
+        #[test]
+        fn does_not_retry_succeeding_commands() {
+            in_temporary_directory(|| {
+                let StdoutUntrimmed(output) =
+                    run_output!(test_helper(), "succeed on attempt", "1", retry(3));
+                assert_eq!(output, "attempt 1\n");
+                assert_eq!(fs::read_to_string("attempts").unwrap(), "1");
+            });
+        }
+
+        #[test]
+        fn returns_the_last_error_when_all_attempts_fail() {
+            in_temporary_directory(|| {
+                let result: Result<StdoutUntrimmed, Error> =
+                    run_result!(test_helper(), "succeed on attempt", "4", retry(3));
+                match result {
+                    Err(Error::NonZeroExitCode { .. }) => {}
+                    _ => panic!("should match Error::NonZeroExitCode"),
+                }
+                assert_eq!(fs::read_to_string("attempts").unwrap(), "3");
+            });
+        }
+
+        #[test]
+        fn sleeps_between_attempts() {
+            in_temporary_directory(|| {
+                let start = Instant::now();
+                run!(
+                    test_helper(),
+                    "succeed on attempt",
+                    "2",
+                    Retry {
+                        attempts: 2,
+                        delay: Duration::from_millis(200),
+                    }
+                );
+                let duration = start.elapsed();
+                assert!(duration >= Duration::from_millis(200), "{:?}", duration);
+            });
+        }
+
+        #[test]
+        fn does_not_retry_missing_executables() {
+            let context = Context::test();
+            let result: Result<(), Error> =
+                run_result_with_context(context.clone(), ("does-not-exist", LogCommand, retry(3)));
+            match result {
+                Err(Error::FileNotFound { .. }) => {}
+                _ => panic!("should match Error::FileNotFound"),
+            }
+            assert_eq!(context.stderr(), "+ does-not-exist\n");
+        }
+    }
+
+    mod timeout {
+        use super::*;
+        use std::time::{Duration, Instant};
+
+        fn timeout(after: u64, grace: u64) -> Timeout {
+            Timeout {
+                after: Duration::from_millis(after),
+                grace: Duration::from_millis(grace),
+            }
+        }
+
+        #[test]
+        fn terminates_commands_that_run_for_too_long() {
+            let start = Instant::now();
+            let result: Result<(), Error> = run_result!(%"sleep 10", timeout(100, 1000));
+            match result {
+                Err(Error::Timeout { .. }) => {}
+                _ => panic!("should match Error::Timeout"),
+            }
+            assert!(start.elapsed() < Duration::from_secs(5));
+        }
+
+        #[test]
+        fn does_not_affect_commands_that_finish_in_time() {
+            let StdoutTrimmed(output) = run_output!(%"echo foo", timeout(10_000, 1000));
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn does_not_mask_other_errors() {
+            let result: Result<(), Error> = run_result!("false", timeout(10_000, 1000));
+            match result {
+                Err(Error::NonZeroExitCode { .. }) => {}
+                _ => panic!("should match Error::NonZeroExitCode"),
+            }
+        }
+
+        #[test]
+        fn error_messages_contain_the_command_and_the_timeout() {
+            let result: Result<(), Error> = run_result!(%"sleep 10", timeout(100, 1000));
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "sleep 10:\n  timed out after 100ms"
+            );
+        }
+
+        #[test]
+        fn terminates_all_commands_of_pipelines() {
+            let result: Result<(), Error> =
+                run_result!(Pipe(("sleep", "10"), "cat"), timeout(100, 1000));
+            match result {
+                Err(Error::Timeout { .. }) => {}
+                _ => panic!("should match Error::Timeout"),
+            }
+        }
+
+        #[test]
+        #[cfg(unix)]
+        fn does_not_wait_for_subprocesses_that_keep_the_output_open() {
+            let start = Instant::now();
+            let result: Result<(), Error> =
+                run_result!("sh", "-c", "sleep 5; true", timeout(100, 100));
+            match result {
+                Err(Error::Timeout { .. }) => {}
+                _ => panic!("should match Error::Timeout"),
+            }
+            assert!(start.elapsed() < Duration::from_secs(3));
+        }
+
+        #[test]
+        #[cfg(unix)]
+        fn times_out_when_background_processes_keep_the_output_open() {
+            let start = Instant::now();
+            let result: Result<StdoutUntrimmed, Error> =
+                run_result!("sh", "-c", "echo foo; sleep 5 &", timeout(100, 100));
+            match result {
+                Err(Error::Timeout { .. }) => {}
+                _ => panic!("should match Error::Timeout"),
+            }
+            assert!(start.elapsed() < Duration::from_secs(3));
+        }
+
+        #[test]
+        #[cfg(unix)]
+        fn sends_sigterm_first() {
+            in_temporary_directory(|| {
+                let result: Result<(), Error> = run_result!(
+                    "sh",
+                    "-c",
+                    "trap 'touch terminated; exit' TERM; while true; do sleep 0.01; done",
+                    timeout(100, 10_000)
+                );
+                match result {
+                    Err(Error::Timeout { .. }) => {}
+                    _ => panic!("should match Error::Timeout"),
+                }
+                assert!(PathBuf::from("terminated").exists());
+            });
+        }
+
+        #[test]
+        #[cfg(unix)]
+        fn kills_commands_that_ignore_sigterm_after_the_grace_period() {
+            let start = Instant::now();
+            let result: Result<(), Error> = run_result!(
+                "sh",
+                "-c",
+                "trap '' TERM; while true; do sleep 0.01; done",
+                timeout(100, 100)
+            );
+            match result {
+                Err(Error::Timeout { .. }) => {}
+                _ => panic!("should match Error::Timeout"),
+            }
+            assert!(start.elapsed() < Duration::from_secs(5));
+        }
+    }
+
+    mod on_exit {
+        use super::*;
+        use std::{
+            sync::mpsc::{channel, Receiver},
+            time::Duration,
+        };
+
+        fn on_exit() -> (
+            OnExit<impl FnMut(&ChildOutput) + Send>,
+            Receiver<ChildOutput>,
+        ) {
+            let (sender, receiver) = channel();
+            let on_exit = OnExit(move |output: &ChildOutput| {
+                sender.send(output.clone()).unwrap();
+            });
+            (on_exit, receiver)
+        }
+
+        #[test]
+        fn is_called_with_the_exit_status() {
+            let (on_exit, receiver) = on_exit();
+            run!(%"true", on_exit);
+            assert!(receiver.recv().unwrap().exit_status().success());
+        }
+
+        #[test]
+        fn is_called_for_failing_commands() {
+            let (on_exit, receiver) = on_exit();
+            let result: Result<(), Error> = run_result!(test_helper(), "exit code 42", on_exit);
+            assert!(result.is_err());
+            assert_eq!(receiver.recv().unwrap().exit_status().code(), Some(42));
+        }
+
+        #[test]
+        fn is_called_for_timed_out_commands() {
+            let (on_exit, receiver) = on_exit();
+            let result: Result<(), Error> = run_result!(
+                %"sleep 5",
+                Timeout {
+                    after: Duration::from_millis(100),
+                    grace: Duration::from_millis(100),
+                },
+                on_exit
+            );
+            assert!(result.is_err());
+            assert!(!receiver.recv().unwrap().exit_status().success());
+        }
+
+        #[test]
+        fn is_called_when_the_output_cannot_be_collected() {
+            let (on_exit, receiver) = on_exit();
+            let result: Result<StdoutUntrimmed, Error> =
+                run_result!(%"echo foo", MaxOutputSize(3), on_exit);
+            match result {
+                Err(Error::OutputTooLarge { .. }) => {}
+                result => panic!("expected OutputTooLarge, got: {:?}", result),
+            }
+            assert_eq!(receiver.recv().unwrap().stdout(), None);
+        }
+
+        #[test]
+        fn includes_captured_output() {
+            let (on_exit, receiver) = on_exit();
+            let StdoutUntrimmed(_) = run_output!(%"echo foo", on_exit);
+            let output = receiver.recv().unwrap();
+            assert_eq!(output.stdout(), Some(b"foo\n".as_ref()));
+            assert_eq!(output.stderr(), None);
+        }
+
+        #[test]
+        fn includes_the_duration() {
+            let (on_exit, receiver) = on_exit();
+            run!(%"sleep 0.1", on_exit);
+            assert!(receiver.recv().unwrap().duration() >= Duration::from_millis(100));
+        }
+
+        #[test]
+        fn is_called_once_for_every_attempt() {
+            let (on_exit, receiver) = on_exit();
+            let result: Result<(), Error> = run_result!(
+                "false",
+                Retry {
+                    attempts: 3,
+                    delay: Duration::from_millis(0),
+                },
+                on_exit
+            );
+            assert!(result.is_err());
+            assert_eq!(receiver.try_iter().count(), 3);
+        }
+
+        #[test]
+        fn is_called_once_for_pipes() {
+            let (on_exit, receiver) = on_exit();
+            let StdoutTrimmed(_) = run_output!(Pipe(Split("echo foo"), "cat"), on_exit);
+            let outputs: Vec<ChildOutput> = receiver.try_iter().collect();
+            assert_eq!(outputs.len(), 1);
+            assert_eq!(outputs[0].stdout(), Some(b"foo\n".as_ref()));
+        }
+    }
+
+    mod on_spawn {
+        use super::*;
+        use std::{
+            sync::{
+                mpsc::{channel, Sender},
+                Arc, Mutex,
+            },
+            thread,
+        };
+
+        fn send_pids(sender: Sender<u32>) -> OnSpawn<impl FnMut(u32) + Send + 'static> {
+            OnSpawn(move |pid| sender.send(pid).unwrap())
+        }
+
+        #[test]
+        #[cfg(unix)]
+        fn calls_the_callback_with_the_pid_of_the_child_process() {
+            let (sender, receiver) = channel();
+            let StdoutTrimmed(output) =
+                run_output!(test_helper(), "print process group", send_pids(sender));
+            let pid = receiver.recv().unwrap();
+            assert!(
+                output.starts_with(&format!("pid: {},", pid)),
+                "{:?} doesn't contain pid {}",
+                output,
+                pid
+            );
+        }
+
+        #[test]
+        fn calls_the_callback_before_the_child_process_exits() {
+            in_temporary_directory(|| {
+                let (sender, receiver) = channel();
+                let thread = thread::spawn(move || {
+                    run!(
+                        test_helper(),
+                        "stream chunk then wait for file",
+                        send_pids(sender)
+                    );
+                });
+                receiver.recv().unwrap();
+                fs::write("file", "").unwrap();
+                thread.join().unwrap();
+            });
+        }
+
+        #[test]
+        fn is_called_once_per_run() {
+            let count = Arc::new(Mutex::new(0));
+            let count_clone = count.clone();
+            run!("true", OnSpawn(move |_| *count_clone.lock().unwrap() += 1));
+            assert_eq!(*count.lock().unwrap(), 1);
+        }
+
+        #[test]
+        fn is_called_for_the_command_it_belongs_to_in_pipes() {
+            let (first_sender, first_receiver) = channel();
+            let (second_sender, second_receiver) = channel();
+            run!(Pipe(
+                ("echo", "foo", send_pids(first_sender)),
+                ("cat", send_pids(second_sender))
+            ));
+            assert_ne!(
+                first_receiver.recv().unwrap(),
+                second_receiver.recv().unwrap()
+            );
+        }
+    }
+
+    mod configure_command {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn allows_to_modify_the_command() {
+            let StdoutTrimmed(output) = run_output!(
+                test_helper(),
+                "echo",
+                ConfigureCommand(|command: &mut std::process::Command| {
+                    command.arg("FOO").env("FOO", "foo");
+                })
+            );
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn runs_after_cradles_own_configuration() {
+            let StdoutTrimmed(output) = run_output!(
+                test_helper(),
+                %"echo FOO",
+                Env("FOO", "cradle"),
+                ConfigureCommand(|command: &mut std::process::Command| {
+                    command.env("FOO", "override");
+                })
+            );
+            assert_eq!(output, "override");
+        }
+
+        #[test]
+        fn is_called_for_every_attempt() {
+            in_temporary_directory(|| {
+                let (sender, receiver) = std::sync::mpsc::channel();
+                run!(
+                    test_helper(),
+                    "succeed on attempt",
+                    "2",
+                    Retry {
+                        attempts: 2,
+                        delay: std::time::Duration::from_millis(0),
+                    },
+                    ConfigureCommand(move |_: &mut std::process::Command| {
+                        sender.send(()).unwrap();
+                    })
+                );
+                assert_eq!(receiver.try_iter().count(), 2);
+            });
+        }
+    }
+
+    mod command {
+        use super::*;
+
+        #[test]
+        fn runs_the_accumulated_inputs() {
+            let StdoutTrimmed(output) = Command::new("echo").arg("foo").arg("bar").run_output();
+            assert_eq!(output, "foo bar");
+        }
+
+        #[test]
+        fn accepts_all_inputs_as_arguments() {
+            let StdoutUntrimmed(output) = Command::new(test_helper())
+                .arg(Split("reverse"))
+                .arg(Stdin("foo"))
+                .run_output();
+            assert_eq!(output, "oof");
+        }
+
+        #[test]
+        fn sets_environment_variables() {
+            let StdoutTrimmed(output) = Command::new(test_helper())
+                .arg("echo")
+                .arg("FOO")
+                .env("FOO", "bar")
+                .run_output();
+            assert_eq!(output, "bar");
+        }
+
+        #[test]
+        fn adds_arguments_from_iterators() {
+            let arguments: std::collections::BTreeSet<String> =
+                vec!["foo".to_string(), "bar baz".to_string()]
+                    .into_iter()
+                    .collect();
+            let Lines(output) = Command::new("printf")
+                .arg("%s\\n")
+                .args(arguments.iter())
+                .run_output();
+            assert_eq!(output, vec!["bar baz", "foo"]);
+        }
+
+        #[test]
+        fn sets_multiple_environment_variables() {
+            let mut environment = std::collections::BTreeMap::new();
+            environment.insert("FOO", "foo");
+            environment.insert("BAR", "bar");
+            let StdoutUntrimmed(output) = Command::new(test_helper())
+                .args(vec!["echo", "FOO", "BAR"])
+                .envs(environment)
+                .run_output();
+            assert_eq!(output, "foo\nbar\n");
+        }
+
+        #[test]
+        fn writes_stdin() {
+            let StdoutUntrimmed(output) = Command::new(test_helper())
+                .arg("reverse")
+                .stdin("foo")
+                .run_output();
+            assert_eq!(output, "oof");
         }
 
         #[test]
-        fn failing_commands_return_oks_when_exit_status_is_captured() {
-            let Status(exit_status) = run_result!("false").unwrap();
-            assert!(!exit_status.success());
+        fn can_be_built_from_runtime_data() {
+            let arguments: Vec<String> = vec!["echo".to_string(), "foo".to_string()];
+            let result: Result<StdoutTrimmed, Error> = Command::new(arguments).run_result();
+            assert_eq!(result.unwrap().0, "foo");
         }
-    }
 
-    mod bool_output {
-        use super::*;
+        #[test]
+        fn sets_the_working_directory() {
+            let temp_dir = TempDir::new().unwrap();
+            Command::new(Split("touch foo"))
+                .current_dir(temp_dir.path())
+                .run();
+            assert!(temp_dir.path().join("foo").exists());
+        }
 
         #[test]
-        fn success_exit_status_is_true() {
-            assert!(run_output!("true"));
+        fn logs_commands() {
+            let context = Context::test();
+            let StdoutTrimmed(output) = Command::new("echo")
+                .arg("foo")
+                .log()
+                .run_result_with_context(context.clone())
+                .unwrap();
+            assert_eq!(output, "foo");
+            assert_eq!(context.stderr(), "+ echo foo\n");
         }
 
         #[test]
-        fn failure_exit_status_is_false() {
-            assert!(!run_output!("false"));
+        fn can_be_cloned_to_share_inputs() {
+            let base = Command::new(test_helper()).arg("echo").env("FOO", "foo");
+            let StdoutTrimmed(output) = base.clone().arg("FOO").run_output();
+            assert_eq!(output, "foo");
+            let StdoutUntrimmed(output) = base.env("BAR", "bar").arg("FOO").arg("BAR").run_output();
+            assert_eq!(output, "foo\nbar\n");
         }
 
         #[test]
-        #[should_panic]
-        fn io_error_panics() {
-            assert!(run_output!("/"));
+        fn run_result_returns_errors() {
+            let result: Result<(), Error> = Command::new("false").run_result();
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "false:\n  exited with exit code: 1"
+            );
         }
     }
 
-    mod tuple_inputs {
+    mod spawn {
         use super::*;
+        use crate::input::spawn_with_context;
         use pretty_assertions::assert_eq;
 
         #[test]
-        fn two_tuple() {
-            let StdoutTrimmed(output) = run_output!(("echo", "foo"));
-            assert_eq!(output, "foo");
+        fn does_not_wait_for_the_child_process() {
+            in_temporary_directory(|| {
+                let child = (test_helper(), "stream chunk then wait for file")
+                    .spawn()
+                    .unwrap();
+                fs::write("file", "").unwrap();
+                assert!(child.wait().unwrap().success());
+            });
         }
 
         #[test]
-        fn three_tuples() {
-            let StdoutTrimmed(output) = run_output!(("echo", "foo", "bar"));
-            assert_eq!(output, "foo bar");
+        fn wait_returns_the_exit_status() {
+            let child = (test_helper(), "exit code 42").spawn().unwrap();
+            assert_eq!(child.wait().unwrap().code(), Some(42));
         }
 
         #[test]
-        fn nested_tuples() {
-            let StdoutTrimmed(output) = run_output!(("echo", ("foo", "bar")));
-            assert_eq!(output, "foo bar");
+        fn relays_output() {
+            let context = Context::test();
+            let child =
+                spawn_with_context(context.clone(), (Split("sh -c"), "echo foo; echo bar >&2"))
+                    .unwrap();
+            child.wait().unwrap();
+            assert_eq!(context.stdout(), "foo\n");
+            assert_eq!(context.stderr(), "bar\n");
         }
 
         #[test]
-        fn unit_input() {
-            let StdoutTrimmed(output) = run_output!(("echo", ()));
-            assert_eq!(output, "");
+        fn writes_stdin() {
+            let context = Context::test();
+            let child =
+                spawn_with_context(context.clone(), (test_helper(), "reverse", Stdin("foo")))
+                    .unwrap();
+            child.wait().unwrap();
+            assert_eq!(context.stdout(), "oof");
         }
-    }
 
-    mod tuple_outputs {
-        use super::*;
+        #[test]
+        fn returns_the_process_id() {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            let child = ("true", OnSpawn(move |pid| sender.send(pid).unwrap()))
+                .spawn()
+                .unwrap();
+            assert_eq!(child.id(), receiver.recv().unwrap());
+            child.wait().unwrap();
+        }
 
         #[test]
-        fn two_tuple_1() {
-            let (StdoutTrimmed(output), Status(exit_status)) =
-                run_output!(test_helper(), "output foo and exit with 42");
-            assert_eq!(output, "foo");
-            assert_eq!(exit_status.code(), Some(42));
+        #[cfg(unix)]
+        fn allows_to_kill_the_child_process() {
+            let mut child = ("sleep", "10").spawn().unwrap();
+            child.kill().unwrap();
+            let status = child.wait().unwrap();
+            {
+                use std::os::unix::process::ExitStatusExt;
+                assert_eq!(status.signal(), Some(9));
+            }
         }
 
         #[test]
-        fn two_tuple_2() {
-            let (Status(exit_status), StdoutTrimmed(output)) =
-                run_output!(test_helper(), "output foo and exit with 42");
-            assert_eq!(output, "foo");
-            assert_eq!(exit_status.code(), Some(42));
+        #[cfg(unix)]
+        fn kills_the_child_process_on_drop_if_configured() {
+            let child = ("sleep", "10").spawn().unwrap().kill_on_drop(true);
+            let pid = child.id();
+            drop(child);
+            let still_running: bool = run_output!("kill", "-0", pid.to_string());
+            assert!(!still_running);
         }
 
         #[test]
-        fn result_of_tuple() {
-            let (StdoutTrimmed(output), Status(exit_status)) = run_result!(%"echo foo").unwrap();
-            assert_eq!(output, "foo");
-            assert!(exit_status.success());
+        #[cfg(unix)]
+        fn kills_the_child_process_on_drop_with_kill_on_drop() {
+            let child = ("sleep", "10", KillOnDrop).spawn().unwrap();
+            let pid = child.id();
+            drop(child);
+            let still_running: bool = run_output!("kill", "-0", pid.to_string());
+            assert!(!still_running);
         }
 
         #[test]
-        fn result_of_tuple_when_erroring() {
-            let (StdoutTrimmed(output), Status(exit_status)) = run_result!("false").unwrap();
-            assert_eq!(output, "");
-            assert_eq!(exit_status.code(), Some(1));
+        #[cfg(unix)]
+        fn does_not_kill_the_child_process_on_drop_by_default() {
+            let child = ("sleep", "10").spawn().unwrap();
+            let pid = child.id();
+            drop(child);
+            let still_running: bool = run_output!("kill", "-0", pid.to_string());
+            assert!(still_running);
+            run!("kill", pid.to_string());
         }
 
         #[test]
-        fn three_tuples() {
-            let (Stderr(stderr), StdoutTrimmed(stdout), Status(exit_status)) =
-                run_output!(%"echo foo");
-            assert_eq!(stderr, "");
-            assert_eq!(stdout, "foo");
-            assert_eq!(exit_status.code(), Some(0));
+        #[cfg(unix)]
+        fn kill_on_drop_can_be_disabled_on_the_handle() {
+            let child = ("sleep", "10", KillOnDrop)
+                .spawn()
+                .unwrap()
+                .kill_on_drop(false);
+            let pid = child.id();
+            drop(child);
+            let still_running: bool = run_output!("kill", "-0", pid.to_string());
+            assert!(still_running);
+            run!("kill", pid.to_string());
         }
 
         #[test]
-        fn capturing_stdout_on_errors() {
-            let (StdoutTrimmed(output), Status(exit_status)) =
-                run_output!(test_helper(), "output foo and exit with 42");
-            assert!(!exit_status.success());
-            assert_eq!(output, "foo");
+        #[cfg(unix)]
+        fn does_not_block_on_drop_when_grandchildren_keep_the_output_open() {
+            let child = ("sh", "-c", "sleep 10 & echo foo", KillOnDrop)
+                .spawn()
+                .unwrap();
+            let start = std::time::Instant::now();
+            drop(child);
+            assert!(start.elapsed() < std::time::Duration::from_secs(5));
         }
 
         #[test]
-        fn capturing_stderr_on_errors() {
-            let (Stderr(output), Status(exit_status)) =
-                run_output!(test_helper(), "write to stderr and exit with 42");
-            assert!(!exit_status.success());
-            assert_eq!(output, "foo\n");
+        #[cfg(unix)]
+        fn wait_kills_the_remaining_commands_of_a_pipe_on_errors() {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            let child = (
+                Pipe(
+                    "true",
+                    ("sleep", "10", OnSpawn(move |pid| sender.send(pid).unwrap())),
+                ),
+                Stdin(vec![b'a'; 2_usize.pow(16) + 1]),
+            )
+                .spawn()
+                .unwrap();
+            let pid = receiver.recv().unwrap();
+            let start = std::time::Instant::now();
+            match child.wait() {
+                Err(Error::StdinNotConsumed { .. }) => {}
+                _ => panic!("should match Error::StdinNotConsumed"),
+            }
+            assert!(start.elapsed() < std::time::Duration::from_secs(5));
+            let still_running: bool = run_output!("kill", "-0", pid.to_string());
+            assert!(!still_running);
         }
-    }
 
-    mod current_dir {
-        use super::*;
-        use std::path::Path;
+        #[test]
+        fn reports_spawn_errors() {
+            let result = "does-not-exist".spawn();
+            match result {
+                Err(Error::FileNotFound { .. }) => {}
+                _ => panic!("should match Error::FileNotFound"),
+            }
+        }
 
         #[test]
-        fn sets_the_working_directory() {
-            in_temporary_directory(|| {
-                fs::create_dir("dir").unwrap();
-                fs::write("dir/file", "foo").unwrap();
-                fs::write("file", "wrong file").unwrap();
-                let StdoutUntrimmed(output) = run_output!(%"cat file", CurrentDir("dir"));
-                assert_eq!(output, "foo");
-            });
+        fn works_for_pipes() {
+            let context = Context::test();
+            let child =
+                spawn_with_context(context.clone(), Pipe(("echo", "foo"), ("tr", "a-z", "A-Z")))
+                    .unwrap();
+            assert!(child.wait().unwrap().success());
+            assert_eq!(context.stdout(), "FOO\n");
         }
 
         #[test]
-        fn works_for_other_types() {
-            in_temporary_directory(|| {
-                fs::create_dir("dir").unwrap();
-                let dir: String = "dir".to_string();
-                run!("true", CurrentDir(dir));
-                let dir: PathBuf = PathBuf::from("dir");
-                run!("true", CurrentDir(dir));
-                let dir: &Path = Path::new("dir");
-                run!("true", CurrentDir(dir));
-            });
+        fn works_for_commands() {
+            let child = Command::new("false").spawn().unwrap();
+            assert_eq!(child.wait().unwrap().code(), Some(1));
         }
     }
 
-    mod capturing_stdout {
+    mod stream_stdout_lines {
         use super::*;
+        use crate::input::stream_stdout_lines_with_context;
+        use pretty_assertions::assert_eq;
 
-        mod trimmed {
-            use super::*;
+        #[test]
+        fn yields_the_lines_of_stdout() {
+            let lines = ("echo", "foo\nbar").stream_stdout_lines().unwrap();
+            let lines: Vec<String> = lines.map(Result::unwrap).collect();
+            assert_eq!(lines, vec!["foo", "bar"]);
+        }
 
-            #[test]
-            fn trims_trailing_whitespace() {
-                let StdoutTrimmed(output) = run_output!(%"echo foo");
-                assert_eq!(output, "foo");
-            }
+        #[test]
+        fn strips_carriage_returns() {
+            let lines = (Split("echo -n"), "foo\r\nbar")
+                .stream_stdout_lines()
+                .unwrap();
+            let lines: Vec<String> = lines.map(Result::unwrap).collect();
+            assert_eq!(lines, vec!["foo", "bar"]);
+        }
 
-            #[test]
-            fn trims_leading_whitespace() {
-                let StdoutTrimmed(output) = run_output!(%"echo -n", " foo");
-                assert_eq!(output, "foo");
-            }
+        #[test]
+        fn yields_lines_while_the_child_is_running() {
+            in_temporary_directory(|| {
+                let mut lines = (test_helper(), "stream chunk then wait for file")
+                    .stream_stdout_lines()
+                    .unwrap();
+                assert_eq!(lines.next().unwrap().unwrap(), "foo");
+                fs::write("file", "").unwrap();
+                assert!(lines.next().is_none());
+            });
+        }
 
-            #[test]
-            fn does_not_remove_whitespace_within_output() {
-                let StdoutTrimmed(output) = run_output!(%"echo -n", "foo bar");
-                assert_eq!(output, "foo bar");
-            }
+        #[test]
+        #[cfg(unix)]
+        fn kills_the_child_process_when_dropped() {
+            let mut lines = ("yes", "foo").stream_stdout_lines().unwrap();
+            assert_eq!(lines.next().unwrap().unwrap(), "foo");
+            let pid = lines.id();
+            drop(lines);
+            let still_running: bool = run_output!("kill", "-0", pid.to_string());
+            assert!(!still_running);
+        }
 
-            #[test]
-            fn does_not_modify_output_without_whitespace() {
-                let StdoutTrimmed(output) = run_output!(%"echo -n", "foo");
-                assert_eq!(output, "foo");
-            }
+        #[test]
+        fn wait_returns_the_exit_status() {
+            let lines = (test_helper(), "output foo and exit with 42")
+                .stream_stdout_lines()
+                .unwrap();
+            assert_eq!(lines.wait().unwrap().code(), Some(42));
+        }
 
-            #[test]
-            fn does_not_relay_stdout() {
-                let context = Context::test();
-                let StdoutTrimmed(_) =
-                    run_result_with_context(context.clone(), Split("echo foo")).unwrap();
-                assert_eq!(context.stdout(), "");
-            }
+        #[test]
+        fn invalid_utf8_results_in_invalid_data_errors() {
+            let mut lines = (test_helper(), "invalid utf-8 stdout")
+                .stream_stdout_lines()
+                .unwrap();
+            assert_eq!(
+                lines.next().unwrap().unwrap_err().kind(),
+                std::io::ErrorKind::InvalidData
+            );
+            assert!(lines.next().is_none());
         }
 
-        mod untrimmed {
-            use super::*;
+        #[test]
+        fn relays_stderr() {
+            let context = Context::test();
+            let lines = stream_stdout_lines_with_context(
+                context.clone(),
+                (test_helper(), "write to stdout and stderr interleaved"),
+            )
+            .unwrap();
+            assert!(lines.wait().unwrap().success());
+            assert_eq!(context.stdout(), "");
+            assert_eq!(context.stderr(), "to stderr\n");
+        }
 
-            #[test]
-            fn does_not_trim_trailing_newline() {
-                let StdoutUntrimmed(output) = run_output!(%"echo foo");
-                assert_eq!(output, "foo\n");
-            }
+        #[test]
+        fn works_for_pipes() {
+            let lines = Pipe(("echo", "foo"), ("tr", "a-z", "A-Z"))
+                .stream_stdout_lines()
+                .unwrap();
+            let lines: Vec<String> = lines.map(Result::unwrap).collect();
+            assert_eq!(lines, vec!["FOO"]);
+        }
 
-            #[test]
-            fn does_not_trim_leading_whitespace() {
-                let StdoutUntrimmed(output) = run_output!(%"echo -n", " foo");
-                assert_eq!(output, " foo");
-            }
+        #[test]
+        fn works_for_commands() {
+            let lines = Command::new("echo")
+                .arg("foo")
+                .stream_stdout_lines()
+                .unwrap();
+            let lines: Vec<String> = lines.map(Result::unwrap).collect();
+            assert_eq!(lines, vec!["foo"]);
+        }
 
-            #[test]
-            fn does_not_relay_stdout() {
-                let context = Context::test();
-                let StdoutUntrimmed(_) =
-                    run_result_with_context(context.clone(), Split("echo foo")).unwrap();
-                assert_eq!(context.stdout(), "");
+        #[test]
+        fn reports_spawn_errors() {
+            let result = "does-not-exist".stream_stdout_lines();
+            match result {
+                Err(Error::FileNotFound { .. }) => {}
+                _ => panic!("should match Error::FileNotFound"),
             }
         }
     }
 
-    mod split {
+    mod spawn_stdout {
         use super::*;
+        use pretty_assertions::assert_eq;
 
         #[test]
-        fn splits_words_by_whitespace() {
-            let StdoutTrimmed(output) = run_output!(Split("echo foo"));
-            assert_eq!(output, "foo");
+        fn connects_stdout_to_the_stdin_of_another_command() {
+            let stdout = ("echo", "foo\nbar").spawn_stdout().unwrap();
+            let StdoutTrimmed(output) = run_output!(%"grep bar", stdout);
+            assert_eq!(output, "bar");
         }
 
         #[test]
-        fn skips_multiple_whitespace_characters() {
-            let StdoutUntrimmed(output) = run_output!("echo", Split("foo  bar"));
-            assert_eq!(output, "foo bar\n");
+        fn allows_to_configure_every_command_separately() {
+            in_temporary_directory(|| {
+                fs::create_dir("dir").unwrap();
+                fs::write("dir/file", "foo").unwrap();
+                let stdout = (Split("cat file"), CurrentDir("dir"))
+                    .spawn_stdout()
+                    .unwrap();
+                let StdoutTrimmed(output) =
+                    run_output!(test_helper(), "reverse", stdout, Env("FOO", "foo"));
+                assert_eq!(output, "oof");
+            });
         }
 
         #[test]
-        fn trims_leading_whitespace() {
-            let StdoutTrimmed(output) = run_output!(Split(" echo foo"));
-            assert_eq!(output, "foo");
+        fn works_for_large_outputs() {
+            let stdout = Split("seq 1 100000").spawn_stdout().unwrap();
+            let StdoutTrimmed(output) = run_output!(%"wc -l", stdout);
+            assert_eq!(output, "100000");
         }
 
         #[test]
-        fn trims_trailing_whitespace() {
-            let StdoutUntrimmed(output) = run_output!("echo", Split("foo "));
-            assert_eq!(output, "foo\n");
+        fn allows_chaining_multiple_commands() {
+            let stdout = Split("echo foo").spawn_stdout().unwrap();
+            let stdout = ("tr", "a-z", "A-Z", stdout).spawn_stdout().unwrap();
+            let StdoutTrimmed(output) = run_output!(test_helper(), "reverse", stdout);
+            assert_eq!(output, "OOF");
         }
 
-        mod percent_sign {
-            use super::*;
-
-            #[test]
-            fn splits_words() {
-                let StdoutUntrimmed(output) = run_output!(%"echo foo");
-                assert_eq!(output, "foo\n");
-            }
-
-            #[test]
-            fn works_for_later_arguments() {
-                let StdoutUntrimmed(output) = run_output!("echo", %"foo\tbar");
-                assert_eq!(output, "foo bar\n");
-            }
+        #[test]
+        fn works_for_commands() {
+            let stdout = Command::new("echo").arg("foo").spawn_stdout().unwrap();
+            let StdoutTrimmed(output) = run_output!("cat", stdout);
+            assert_eq!(output, "foo");
+        }
 
-            #[test]
-            fn for_first_of_multiple_arguments() {
-                let StdoutUntrimmed(output) = run_output!(%"echo foo", "bar");
-                assert_eq!(output, "foo bar\n");
-            }
+        #[test]
+        fn accepts_stdout_of_std_commands() {
+            let mut child = std::process::Command::new("echo")
+                .arg("foo")
+                .stdout(std::process::Stdio::piped())
+                .spawn()
+                .unwrap();
+            let stdout = child.stdout.take().unwrap();
+            let StdoutTrimmed(output) = run_output!("cat", stdout);
+            assert_eq!(output, "foo");
+            child.wait().unwrap();
+        }
 
-            #[test]
-            fn non_literals() {
-                let command = "echo foo";
-                let StdoutUntrimmed(output) = run_output!(%command);
-                assert_eq!(output, "foo\n");
+        #[test]
+        fn cannot_be_combined_with_other_stdin_sources() {
+            let stdout = Split("echo foo").spawn_stdout().unwrap();
+            let result: Result<(), Error> = run_result!("cat", stdout, Stdin("bar"));
+            match result {
+                Err(Error::MultipleStdinSources { .. }) => {}
+                result => panic!("expected MultipleStdinSources, got: {:?}", result),
             }
+        }
 
-            #[test]
-            fn in_run() {
-                run!(%"echo foo");
-            }
+        #[test]
+        fn cannot_be_combined_with_stdin_from_file() {
+            in_temporary_directory(|| {
+                fs::write("file", "foo").unwrap();
+                let file = fs::File::open("file").unwrap();
+                let stdout = ("echo", "piped").spawn_stdout().unwrap();
+                let result: Result<StdoutUntrimmed, Error> =
+                    run_result!("cat", StdinFromFile(file), stdout);
+                match result {
+                    Err(Error::MultipleStdinSources { .. }) => {}
+                    result => panic!("expected MultipleStdinSources, got: {:?}", result),
+                }
+            });
+        }
 
-            #[test]
-            fn in_run_result() {
-                let StdoutTrimmed(_) = run_result!(%"echo foo").unwrap();
+        #[test]
+        fn reports_spawn_errors() {
+            let result = "does-not-exist".spawn_stdout();
+            match result {
+                Err(Error::FileNotFound { .. }) => {}
+                _ => panic!("should match Error::FileNotFound"),
             }
         }
     }
 
-    mod splitting_with_library_functions {
+    mod preview_args {
         use super::*;
 
         #[test]
-        fn allow_to_use_split() {
-            let StdoutTrimmed(output) = run_output!("echo foo".split(' '));
-            assert_eq!(output, "foo");
+        fn returns_the_arguments_without_running_anything() {
+            in_temporary_directory(|| {
+                let args = preview_args(Split("touch foo"));
+                assert_eq!(args, vec!["touch", "foo"]);
+                assert!(!PathBuf::from("foo").exists());
+            });
         }
 
         #[test]
-        fn split_whitespace() {
-            let StdoutTrimmed(output) = run_output!("echo foo".split_whitespace());
-            assert_eq!(output, "foo");
+        fn includes_arguments_from_all_inputs() {
+            let args = preview_args((
+                "echo",
+                vec!["foo", "bar"],
+                OsString::from("baz"),
+                Env("FOO", "foo"),
+                LogCommand,
+            ));
+            assert_eq!(args, vec!["echo", "foo", "bar", "baz"]);
         }
 
         #[test]
-        fn split_ascii_whitespace() {
-            let StdoutTrimmed(output) = run_output!("echo foo".split_ascii_whitespace());
-            assert_eq!(output, "foo");
+        fn returns_the_arguments_of_the_last_command_of_pipes() {
+            let args = preview_args(Pipe(("echo", "foo"), ("grep", "foo")));
+            assert_eq!(args, vec!["grep", "foo"]);
         }
     }
 
-    mod paths {
+    mod which {
         use super::*;
         use pretty_assertions::assert_eq;
-        use std::path::Path;
 
-        fn write_test_script() -> PathBuf {
-            if cfg!(unix) {
-                let file = PathBuf::from("./test-script");
-                let script = "#!/usr/bin/env bash\necho test-output\n";
-                fs::write(&file, script).unwrap();
-                run!(%"chmod +x test-script");
-                file
-            } else {
-                let file = PathBuf::from("./test-script.bat");
-                let script = "@echo test-output\n";
-                fs::write(&file, script).unwrap();
-                file
-            }
+        #[test]
+        fn returns_none_for_unknown_executables() {
+            assert_eq!(which("does-not-exist"), None);
         }
 
+        #[cfg(unix)]
         #[test]
-        fn ref_path_as_argument() {
-            in_temporary_directory(|| {
-                let file: &Path = Path::new("file");
-                fs::write(file, "test-contents").unwrap();
-                let StdoutUntrimmed(output) = run_output!("cat", file);
-                assert_eq!(output, "test-contents");
-            })
+        fn finds_executables_in_the_path() {
+            let StdoutTrimmed(expected) = run_output!("which", "sh");
+            assert_eq!(which("sh"), Some(PathBuf::from(expected)));
         }
 
         #[test]
-        fn ref_path_as_executable() {
-            in_temporary_directory(|| {
-                let file: &Path = &write_test_script();
-                let StdoutTrimmed(output) = run_output!(file);
-                assert_eq!(output, "test-output");
-            })
+        fn accepts_paths_to_executables() {
+            let test_helper = test_helper();
+            assert_eq!(which(&test_helper), Some(test_helper));
         }
 
         #[test]
-        fn path_buf_as_argument() {
-            in_temporary_directory(|| {
-                let file: PathBuf = PathBuf::from("file");
-                fs::write(&file, "test-contents").unwrap();
-                let StdoutUntrimmed(output) = run_output!("cat", file);
-                assert_eq!(output, "test-contents");
-            })
+        fn returns_none_for_paths_that_do_not_exist() {
+            assert_eq!(which("./does-not-exist"), None);
         }
 
         #[test]
-        fn path_buf_as_executable() {
+        fn returns_none_for_directories() {
+            let directory = std::env::temp_dir();
+            assert_eq!(which(&directory), None);
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn returns_none_for_files_that_are_not_executable() {
             in_temporary_directory(|| {
-                let file: PathBuf = write_test_script();
-                let StdoutTrimmed(output) = run_output!(file);
-                assert_eq!(output, "test-output");
-            })
+                fs::write("file", "").unwrap();
+                assert_eq!(which("./file"), None);
+                run!(%"chmod +x file");
+                assert_eq!(which("./file"), Some(PathBuf::from("./file")));
+            });
         }
     }
 
-    mod stdin {
+    mod effective_env {
         use super::*;
+        use pretty_assertions::assert_eq;
+        use std::collections::BTreeMap;
+
+        fn get(env: &BTreeMap<OsString, OsString>, key: &str) -> Option<String> {
+            env.get(&OsString::from(key))
+                .map(|value| value.to_string_lossy().into_owned())
+        }
 
         #[test]
-        fn allows_to_pass_in_strings_as_stdin() {
-            let StdoutUntrimmed(output) = run_output!(test_helper(), "reverse", Stdin("foo"));
-            assert_eq!(output, "oof");
+        fn includes_the_parent_environment() {
+            let env = effective_env(());
+            let parent: BTreeMap<OsString, OsString> = std::env::vars_os().collect();
+            assert_eq!(env, parent);
         }
 
         #[test]
-        fn allows_passing_in_u8_slices_as_stdin() {
-            let StdoutUntrimmed(output) = run_output!(test_helper(), "reverse", Stdin(&[0, 1, 2]));
-            assert_eq!(output, "\x02\x01\x00");
+        fn includes_added_variables() {
+            let env = effective_env(Env("CRADLE_EFFECTIVE_ENV_TEST", "foo"));
+            assert_eq!(
+                get(&env, "CRADLE_EFFECTIVE_ENV_TEST"),
+                Some("foo".to_string())
+            );
         }
 
         #[test]
-        #[cfg(unix)]
-        fn stdin_is_closed_by_default() {
-            let StdoutTrimmed(output) = run_output!(test_helper(), "wait until stdin is closed");
-            assert_eq!(output, "stdin is closed");
+        fn excludes_removed_variables() {
+            let env = effective_env(EnvRemove("PATH"));
+            assert_eq!(get(&env, "PATH"), None);
         }
 
         #[test]
-        fn writing_too_many_bytes_into_a_non_reading_child_may_error() {
-            let big_string = String::from_utf8(vec![b'a'; 2_usize.pow(16) + 1]).unwrap();
-            let result: Result<(), crate::Error> = run_result!("true", Stdin(big_string));
-            let message = result.unwrap_err().to_string();
-            assert!(if cfg!(unix) {
-                message == "true:\n  Broken pipe (os error 32)"
-            } else {
-                [
-                    "true:\n  The pipe is being closed. (os error 232)",
-                    "true:\n  The pipe has been ended. (os error 109)",
-                ]
-                .contains(&message.as_str())
-            });
+        fn clear_env_removes_all_inherited_variables() {
+            let env = effective_env((ClearEnv, Env("FOO", "foo")));
+            let expected: BTreeMap<OsString, OsString> =
+                vec![(OsString::from("FOO"), OsString::from("foo"))]
+                    .into_iter()
+                    .collect();
+            assert_eq!(env, expected);
         }
 
         #[test]
-        fn multiple_stdin_arguments_are_all_passed_into_the_child_process() {
-            let StdoutUntrimmed(output) =
-                run_output!(test_helper(), "reverse", Stdin("foo"), Stdin("bar"));
-            assert_eq!(output, "raboof");
+        fn later_inputs_take_precedence() {
+            let env = effective_env((Env("FOO", "foo"), EnvRemove("FOO")));
+            assert_eq!(get(&env, "FOO"), None);
+            let env = effective_env((EnvRemove("FOO"), Env("FOO", "foo")));
+            assert_eq!(get(&env, "FOO"), Some("foo".to_string()));
         }
 
         #[test]
-        fn works_for_owned_strings() {
-            let argument: String = "foo".to_string();
-            let StdoutUntrimmed(output) = run_output!(test_helper(), "reverse", Stdin(argument));
-            assert_eq!(output, "oof");
+        fn prepends_with_path_directories() {
+            let env = effective_env((ClearEnv, Env("PATH", "/bar"), WithPath(vec!["/foo"])));
+            let expected = std::env::join_paths(vec!["/foo", "/bar"]).unwrap();
+            assert_eq!(env.get(&OsString::from("PATH")), Some(&expected));
+        }
+
+        #[test]
+        fn matches_the_environment_of_the_child_process() {
+            let input = || {
+                (
+                    ClearEnv,
+                    Env("FOO", "foo"),
+                    Env("BAR", "bar"),
+                    EnvRemove("BAR"),
+                    WithPath(vec!["/foo"]),
+                )
+            };
+            let EnvMap(child_env) = run_output!(test_helper(), "print environment", input());
+            let env: BTreeMap<String, String> = effective_env(input())
+                .into_iter()
+                .map(|(key, value)| {
+                    (
+                        key.to_string_lossy().into_owned(),
+                        value.to_string_lossy().into_owned(),
+                    )
+                })
+                .collect();
+            assert_eq!(env, child_env);
         }
     }
 
@@ -1451,6 +6539,228 @@ mod tests {
                 run_output!(test_helper(), "echo", "FOO", Env("FOO", ""),);
             assert_eq!(output, "empty variable: FOO\n");
         }
+
+        mod clear_env {
+            use super::*;
+            use pretty_assertions::assert_eq;
+
+            #[test]
+            fn clears_the_environment() {
+                let StdoutUntrimmed(output) =
+                    run_output!(test_helper(), "print environment", ClearEnv);
+                assert_eq!(output, "");
+            }
+
+            #[test]
+            fn allows_to_add_variables_afterwards() {
+                let StdoutUntrimmed(output) = run_output!(
+                    test_helper(),
+                    "print environment",
+                    ClearEnv,
+                    Env("FOO", "bar")
+                );
+                assert_eq!(output, "FOO=bar\n");
+            }
+
+            #[test]
+            fn clears_variables_that_were_added_before() {
+                let StdoutUntrimmed(output) = run_output!(
+                    test_helper(),
+                    "print environment",
+                    Env("FOO", "bar"),
+                    ClearEnv
+                );
+                assert_eq!(output, "");
+            }
+        }
+
+        mod env_remove {
+            use super::*;
+            use pretty_assertions::assert_eq;
+
+            #[test]
+            fn removes_variables_inherited_from_the_parent() {
+                let unused_key = find_unused_environment_variable();
+                env::set_var(&unused_key, "foo");
+                let StdoutUntrimmed(output) = run_output!("env", EnvRemove(&unused_key));
+                assert!(!output.contains(&unused_key));
+            }
+
+            #[test]
+            fn removes_variables_that_were_added_before() {
+                let StdoutUntrimmed(output) =
+                    run_output!("env", Env("CRADLE_FOO", "foo"), EnvRemove("CRADLE_FOO"));
+                assert!(!output.contains("CRADLE_FOO"));
+            }
+
+            #[test]
+            fn allows_to_add_variables_again_after_removing_them() {
+                let unused_key = find_unused_environment_variable();
+                env::set_var(&unused_key, "foo");
+                let StdoutTrimmed(output) = run_output!(
+                    test_helper(),
+                    "echo",
+                    &unused_key,
+                    EnvRemove(&unused_key),
+                    Env(&unused_key, "bar")
+                );
+                assert_eq!(output, "bar");
+            }
+        }
+    }
+
+    mod env_opt {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn adds_variables_for_some() {
+            let StdoutTrimmed(output) =
+                run_output!(test_helper(), %"echo FOO", EnvOpt("FOO", Some("foo")));
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn does_nothing_for_none() {
+            let StdoutTrimmed(output) = run_output!(
+                test_helper(),
+                %"echo FOO",
+                Env("FOO", "foo"),
+                EnvOpt("FOO", None::<&str>)
+            );
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn works_with_owned_values() {
+            let value: Option<String> = Some("foo".to_string());
+            let StdoutTrimmed(output) =
+                run_output!(test_helper(), %"echo FOO", EnvOpt("FOO", value));
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn composes_in_tuples() {
+            let env_vars = (EnvOpt("FOO", Some("a")), EnvOpt("BAR", None::<&str>));
+            let StdoutTrimmed(output) = run_output!(test_helper(), %"echo FOO", env_vars);
+            assert_eq!(output, "a");
+        }
+    }
+
+    mod environment_maps {
+        use super::*;
+        use pretty_assertions::assert_eq;
+        use std::collections::{BTreeMap, HashMap};
+
+        #[test]
+        fn btree_maps_add_environment_variables() {
+            let mut env_vars = BTreeMap::new();
+            env_vars.insert("FOO", "a");
+            env_vars.insert("BAR", "b");
+            let StdoutUntrimmed(output) = run_output!(test_helper(), %"echo FOO BAR", env_vars);
+            assert_eq!(output, "a\nb\n");
+        }
+
+        #[test]
+        fn hash_maps_add_environment_variables() {
+            let mut env_vars = HashMap::new();
+            env_vars.insert("FOO".to_string(), "a".to_string());
+            env_vars.insert("BAR".to_string(), "b".to_string());
+            let StdoutUntrimmed(output) = run_output!(test_helper(), %"echo FOO BAR", env_vars);
+            assert_eq!(output, "a\nb\n");
+        }
+
+        #[test]
+        fn maps_are_not_used_as_arguments() {
+            let mut env_vars = BTreeMap::new();
+            env_vars.insert("FOO", "a");
+            assert_eq!(preview_args(("echo", env_vars)), vec!["echo"]);
+        }
+
+        #[test]
+        fn maps_can_be_passed_by_reference() {
+            let mut env_vars = BTreeMap::new();
+            env_vars.insert("FOO", "a");
+            let StdoutTrimmed(output) = run_output!(test_helper(), %"echo FOO", &env_vars);
+            assert_eq!(output, "a");
+        }
+    }
+
+    mod with_path {
+        use super::*;
+        use pretty_assertions::assert_eq;
+        use std::env;
+
+        fn child_path<I: Input>(input: I) -> Vec<PathBuf> {
+            let StdoutTrimmed(output) = run_output!(test_helper(), "echo", "PATH", input);
+            env::split_paths(&output).collect()
+        }
+
+        #[test]
+        fn prepends_directories_to_the_parents_path() {
+            let mut expected = vec![PathBuf::from("foo"), PathBuf::from("bar")];
+            expected.extend(env::split_paths(&env::var_os("PATH").unwrap()));
+            assert_eq!(child_path(WithPath(vec!["foo", "bar"])), expected);
+        }
+
+        #[test]
+        fn does_not_modify_the_parents_path() {
+            let before = env::var_os("PATH");
+            run!(test_helper(), "echo", "PATH", WithPath(vec!["foo"]));
+            assert_eq!(env::var_os("PATH"), before);
+        }
+
+        #[test]
+        fn allows_to_find_executables_in_the_given_directories() {
+            let directory = test_helper().parent().unwrap().to_owned();
+            let StdoutTrimmed(output) = run_output!(
+                "test_executables_helper",
+                "echo",
+                "FOO",
+                Env("FOO", "foo"),
+                WithPath(vec![directory])
+            );
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn respects_paths_set_with_env() {
+            let path = env::join_paths(vec!["bar", "baz"]).unwrap();
+            assert_eq!(
+                child_path((Env("PATH", path), WithPath(vec!["foo"]))),
+                vec![
+                    PathBuf::from("foo"),
+                    PathBuf::from("bar"),
+                    PathBuf::from("baz")
+                ]
+            );
+        }
+
+        #[test]
+        fn only_uses_the_given_directories_with_clear_env() {
+            assert_eq!(
+                child_path((ClearEnv, WithPath(vec!["foo"]))),
+                vec![PathBuf::from("foo")]
+            );
+        }
+
+        #[test]
+        fn only_uses_the_given_directories_when_path_is_removed() {
+            assert_eq!(
+                child_path((EnvRemove("PATH"), WithPath(vec!["foo"]))),
+                vec![PathBuf::from("foo")]
+            );
+        }
+
+        #[test]
+        #[cfg(unix)]
+        fn directories_containing_the_separator_produce_errors() {
+            let result: Result<(), Error> = run_result!("true", WithPath(vec!["foo:bar"]));
+            match result {
+                Err(Error::CommandIoError { .. }) => {}
+                _ => panic!("should match Error::CommandIoError"),
+            }
+        }
     }
 
     mod run_interface {
@@ -1493,5 +6803,32 @@ mod tests {
                 _ => panic!("should match Error::FileNotFound"),
             }
         }
+
+        #[test]
+        fn run_with() {
+            in_temporary_directory(|| {
+                fs::create_dir("dir").unwrap();
+                ("touch", "foo").run_with(CurrentDir("dir"));
+                assert!(Path::new("dir/foo").exists());
+            });
+        }
+
+        #[test]
+        fn run_output_with() {
+            let StdoutTrimmed(output) =
+                (test_helper(), "echo").run_output_with(("FOO", Env("FOO", "foo")));
+            assert_eq!(output, "foo");
+        }
+
+        #[test]
+        fn run_result_with() {
+            let StdoutTrimmed(output) = "echo".run_result_with("foo").unwrap();
+            assert_eq!(output, "foo");
+            let result: Result<(), Error> = "false".run_result_with(LogCommand);
+            match result {
+                Err(Error::NonZeroExitCode { .. }) => {}
+                _ => panic!("should match Error::NonZeroExitCode"),
+            }
+        }
     }
 }
@@ -1,7 +1,15 @@
 //! The [`Output`] trait that defines all possible outputs of a child process.
 
-use crate::{child_output::ChildOutput, config::Config, error::Error};
-use std::process::ExitStatus;
+use crate::{
+    child_output::ChildOutput,
+    config::{Config, WriterSlot},
+    error::Error,
+    input::{bytes_to_os_string, split_nul_separated, StdoutTo},
+};
+use std::{
+    collections::BTreeMap, ffi::OsString, io::Write, path::PathBuf, process::ExitStatus,
+    sync::Mutex, time::Duration,
+};
 
 /// All possible return types of [`run!`], [`run_output!`] or
 /// [`run_result!`] must implement this trait.
@@ -34,8 +42,32 @@ use std::process::ExitStatus;
 /// - To capture output streams:
 ///   - [`StdoutTrimmed`]: To capture `stdout`, trimmed of whitespace.
 ///   - [`StdoutUntrimmed`]: To capture `stdout` untrimmed.
+///   - [`StdoutBytes`]: To capture `stdout` as raw bytes.
+///   - [`StdoutLossy`]: To capture `stdout`, replacing invalid utf-8.
+///   - [`StdoutPath`]: To capture `stdout` as a trimmed [`PathBuf`].
+///   - [`StdoutTee`]: To capture `stdout` while also relaying it.
+///   - [`StdoutTo`](crate::StdoutTo): To get back the writer that `stdout` was written into.
+///   - [`StdoutTailed`]: To capture the last lines of `stdout`
+///     and whether earlier lines were dropped.
+///   - [`Lines`]: To capture `stdout` split into lines.
+///   - [`Fields`]: To capture `stdout` split into fields.
+///   - [`Words`]: To capture `stdout` split by whitespace.
+///   - [`NulSeparatedLines`]: To capture `stdout` split on `NUL` bytes.
+///   - [`EnvMap`]: To capture `stdout` parsed as `KEY=VALUE` lines.
 ///   - [`Stderr`]: To capture `stderr`.
+///   - [`StderrBytes`]: To capture `stderr` as raw bytes.
+///   - [`StderrLossy`]: To capture `stderr`, replacing invalid utf-8.
+///   - [`CombinedOutput`]: To capture `stdout` and `stderr` interleaved.
 /// - [`Status`]: To capture the command's [`ExitStatus`].
+/// - `Signal`: To capture the signal that terminated the command (only on unix).
+/// - [`CapturedOutput`]: To capture `stdout`, `stderr` and the [`ExitStatus`] at once.
+/// - [`i32`]: To capture the command's exit code.
+/// - [`Result<String, String>`](std::result::Result): To capture `stdout` on success
+///   and `stderr` on failure.
+/// - [`Timed`]: To additionally capture how long the command took.
+/// - `Toml`: To deserialize `stdout` as TOML (requires the `toml` feature).
+/// - `Json` and `JsonValue`: To deserialize `stdout` as JSON (requires the `serde_json` feature).
+/// - `StdoutEncoded`: To decode `stdout` with legacy encodings (requires the `encoding` feature).
 ///
 /// Also, [`Output`] is implemented for tuples.
 /// You can use this to combine multiple return types that implement [`Output`].
@@ -51,6 +83,7 @@ use std::process::ExitStatus;
 /// ```
 ///
 /// [`()`]: trait.Output.html#impl-Output-for-()
+/// [`i32`]: trait.Output.html#impl-Output-for-i32
 ///
 /// ## Custom [`Output`] impls
 ///
@@ -59,6 +92,9 @@ use std::process::ExitStatus;
 /// The API is inconvenient, under-documented, and easy to misuse,
 /// i.e. it is easily possible to provoke [`Internal`](Error::Internal) errors.
 ///
+/// If you need full access to the output of a child process,
+/// consider using [`CapturedOutput`] instead.
+///
 /// See
 /// [Issue 184: Provide a better API for writing custom Output impls](https://github.com/soenkehahn/cradle/issues/184)
 /// for more details and discussion.
@@ -209,6 +245,414 @@ impl Output for StdoutUntrimmed {
     }
 }
 
+/// Like [`StdoutUntrimmed`], [`StdoutTee`] captures what the child process
+/// writes to `stdout`, interpreted as utf-8.
+/// But unlike [`StdoutUntrimmed`], it also relays the child's `stdout`
+/// to the parent's `stdout` while the child is running:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// // writes 'foo' to stdout
+/// let StdoutTee(output) = run_output!(%"echo foo");
+/// assert_eq!(output, "foo\n");
+/// ```
+///
+/// This is useful e.g. in CI logs,
+/// when you want to see the output live, but also inspect it afterwards.
+#[derive(Debug, PartialEq, Clone)]
+pub struct StdoutTee(pub String);
+
+impl Output for StdoutTee {
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        StdoutUntrimmed::configure(config);
+        config.options.relay_captured_stdout = true;
+    }
+
+    #[doc(hidden)]
+    fn from_child_output(config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        let StdoutUntrimmed(stdout) = StdoutUntrimmed::from_child_output(config, child_output)?;
+        Ok(StdoutTee(stdout))
+    }
+}
+
+/// Like [`StdoutUntrimmed`], but also reports whether lines were dropped
+/// because of a [`StdoutTail`](crate::StdoutTail) limit:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let StdoutTailed { stdout, truncated } =
+///     run_output!("echo", "foo\nbar\nbaz", StdoutTail(2));
+/// assert_eq!(stdout, "bar\nbaz\n");
+/// assert!(truncated);
+/// ```
+///
+/// `truncated` is only `true` if lines were actually dropped,
+/// so it's `false` when the child process writes exactly as many lines as the limit.
+/// Without a [`StdoutTail`](crate::StdoutTail), `truncated` is always `false`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct StdoutTailed {
+    /// The kept lines the child process wrote to `stdout`, interpreted as utf-8.
+    pub stdout: String,
+    /// Whether earlier lines were dropped.
+    pub truncated: bool,
+}
+
+impl Output for StdoutTailed {
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        StdoutUntrimmed::configure(config);
+    }
+
+    #[doc(hidden)]
+    fn from_child_output(config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        let StdoutUntrimmed(stdout) = StdoutUntrimmed::from_child_output(config, child_output)?;
+        Ok(StdoutTailed {
+            stdout,
+            truncated: child_output.stdout_truncated,
+        })
+    }
+}
+
+/// Returns what the child process writes to `stdout`, interpreted as utf-8,
+/// split into lines.
+/// Like [`StdoutUntrimmed`], this suppresses relaying the child's `stdout`
+/// to the parent's `stdout`.
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let Lines(lines) = run_output!("echo", "foo\nbar");
+/// assert_eq!(lines, vec!["foo", "bar"]);
+/// ```
+///
+/// Lines are split by `\n` or `\r\n`. The line endings are not included
+/// in the returned lines.
+/// A trailing line ending doesn't result in an additional empty line.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Lines(pub Vec<String>);
+
+impl Output for Lines {
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        StdoutUntrimmed::configure(config);
+    }
+
+    #[doc(hidden)]
+    fn from_child_output(config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        let StdoutUntrimmed(stdout) = StdoutUntrimmed::from_child_output(config, child_output)?;
+        Ok(Lines(stdout.lines().map(|line| line.to_owned()).collect()))
+    }
+}
+
+/// Returns what the child process writes to `stdout`, interpreted as utf-8,
+/// split into fields.
+/// By default, fields are separated by whitespace:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let Fields(fields) = run_output!("echo", "foo  bar\tbaz");
+/// assert_eq!(fields, vec!["foo", "bar", "baz"]);
+/// ```
+///
+/// A different separator can be configured with
+/// [`FieldSeparator`](crate::FieldSeparator):
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let Fields(fields) = run_output!("echo", "foo,,bar,", FieldSeparator(','));
+/// assert_eq!(fields, vec!["foo", "", "bar", ""]);
+/// ```
+///
+/// When splitting on whitespace, there are no empty fields.
+/// When splitting on a [`FieldSeparator`](crate::FieldSeparator),
+/// a single trailing line ending is removed first, and then all fields are kept,
+/// including empty ones and a trailing empty field after a trailing separator,
+/// like `cut` does.
+/// Empty output results in no fields in both cases.
+///
+/// Like [`StdoutUntrimmed`], this suppresses relaying the child's `stdout`
+/// to the parent's `stdout`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Fields(pub Vec<String>);
+
+impl Output for Fields {
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        StdoutUntrimmed::configure(config);
+    }
+
+    #[doc(hidden)]
+    fn from_child_output(config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        let StdoutUntrimmed(stdout) = StdoutUntrimmed::from_child_output(config, child_output)?;
+        let fields = match config.options.field_separator {
+            None => stdout.split_whitespace().map(String::from).collect(),
+            Some(separator) => {
+                let mut line = stdout.as_str();
+                if line.ends_with('\n') {
+                    line = &line[..line.len() - 1];
+                    if line.ends_with('\r') {
+                        line = &line[..line.len() - 1];
+                    }
+                }
+                if line.is_empty() {
+                    Vec::new()
+                } else {
+                    line.split(separator).map(String::from).collect()
+                }
+            }
+        };
+        Ok(Fields(fields))
+    }
+}
+
+/// Returns what the child process writes to `stdout`, interpreted as utf-8,
+/// split into words:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let Words(words) = run_output!(%"echo a b c");
+/// assert_eq!(words, vec!["a", "b", "c"]);
+/// ```
+///
+/// Words are separated by any amount of whitespace, including line endings.
+/// There are no empty words.
+/// Unlike [`Fields`], [`Words`] is not affected by
+/// [`FieldSeparator`](crate::FieldSeparator).
+///
+/// Like [`StdoutUntrimmed`], this suppresses relaying the child's `stdout`
+/// to the parent's `stdout`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Words(pub Vec<String>);
+
+impl Output for Words {
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        StdoutUntrimmed::configure(config);
+    }
+
+    #[doc(hidden)]
+    fn from_child_output(config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        let StdoutUntrimmed(stdout) = StdoutUntrimmed::from_child_output(config, child_output)?;
+        Ok(Words(stdout.split_whitespace().map(String::from).collect()))
+    }
+}
+
+/// Returns what the child process writes to `stdout`, split on `NUL` bytes (`\0`).
+/// This is useful for commands like `find -print0`,
+/// that output file names that may contain whitespace or newlines:
+///
+/// ```
+/// use cradle::prelude::*;
+/// use std::ffi::OsString;
+///
+/// # #[cfg(unix)]
+/// # {
+/// let NulSeparatedLines(files) = run_output!("printf", "foo bar\\0baz\\nqux\\0");
+/// assert_eq!(
+///     files,
+///     vec![OsString::from("foo bar"), OsString::from("baz\nqux")]
+/// );
+/// # }
+/// ```
+///
+/// A trailing `NUL` byte doesn't result in an additional empty entry.
+/// On unix, the output is not required to be valid utf-8.
+/// On other platforms, invalid utf-8 is replaced with `U+FFFD`.
+///
+/// Like [`StdoutUntrimmed`], this suppresses relaying the child's `stdout`
+/// to the parent's `stdout`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct NulSeparatedLines(pub Vec<OsString>);
+
+impl Output for NulSeparatedLines {
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        StdoutBytes::configure(config);
+    }
+
+    #[doc(hidden)]
+    fn from_child_output(config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        let StdoutBytes(stdout) = StdoutBytes::from_child_output(config, child_output)?;
+        Ok(NulSeparatedLines(
+            split_nul_separated(&stdout)
+                .into_iter()
+                .map(bytes_to_os_string)
+                .collect(),
+        ))
+    }
+}
+
+/// Parses what the child process writes to `stdout` as lines of the form `KEY=VALUE`,
+/// like the output of `env`.
+/// Like [`StdoutUntrimmed`], this suppresses relaying the child's `stdout`
+/// to the parent's `stdout`.
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// # #[cfg(unix)]
+/// # {
+/// let EnvMap(environment) = run_output!("/usr/bin/env", ClearEnv, Env("FOO", "bar=baz"));
+/// assert_eq!(environment.len(), 1);
+/// assert_eq!(environment["FOO"], "bar=baz");
+/// # }
+/// ```
+///
+/// Lines are split on the first `=`, so values may contain `=`.
+/// Lines that don't contain a `=` are skipped.
+/// If a key occurs multiple times, the last value wins.
+#[derive(Debug, PartialEq, Clone)]
+pub struct EnvMap(pub BTreeMap<String, String>);
+
+impl Output for EnvMap {
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        StdoutUntrimmed::configure(config);
+    }
+
+    #[doc(hidden)]
+    fn from_child_output(config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        let Lines(lines) = Lines::from_child_output(config, child_output)?;
+        Ok(EnvMap(
+            lines
+                .into_iter()
+                .filter_map(|line| {
+                    let index = line.find('=')?;
+                    Some((line[..index].to_owned(), line[index + 1..].to_owned()))
+                })
+                .collect(),
+        ))
+    }
+}
+
+/// Returns what the child process writes to `stdout`,
+/// trimmed of leading and trailing whitespace, as a [`PathBuf`].
+/// This is useful e.g. for finding executables:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// # #[cfg(unix)]
+/// # {
+/// let StdoutPath(ls) = run_output!(%"which ls");
+/// assert!(ls.exists());
+/// # }
+/// ```
+///
+/// On unix, the output is not required to be valid utf-8,
+/// since paths aren't guaranteed to be valid utf-8 either.
+/// On other platforms, invalid utf-8 will result in an
+/// [`InvalidUtf8ToStdout`](Error::InvalidUtf8ToStdout) error.
+///
+/// Like [`StdoutTrimmed`], this suppresses relaying the child's `stdout`
+/// to the parent's `stdout`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct StdoutPath(pub PathBuf);
+
+impl Output for StdoutPath {
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        StdoutBytes::configure(config);
+    }
+
+    #[cfg(unix)]
+    #[doc(hidden)]
+    fn from_child_output(config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        use std::{ffi::OsString, os::unix::ffi::OsStringExt};
+        let StdoutBytes(stdout) = StdoutBytes::from_child_output(config, child_output)?;
+        let start = stdout
+            .iter()
+            .position(|byte| !byte.is_ascii_whitespace())
+            .unwrap_or(stdout.len());
+        let end = stdout
+            .iter()
+            .rposition(|byte| !byte.is_ascii_whitespace())
+            .map_or(start, |last| last + 1);
+        Ok(StdoutPath(PathBuf::from(OsString::from_vec(
+            stdout[start..end].to_vec(),
+        ))))
+    }
+
+    #[cfg(not(unix))]
+    #[doc(hidden)]
+    fn from_child_output(config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        let StdoutTrimmed(stdout) = StdoutTrimmed::from_child_output(config, child_output)?;
+        Ok(StdoutPath(PathBuf::from(stdout)))
+    }
+}
+
+/// Returns what the child process writes to `stdout` as raw bytes,
+/// without interpreting them as utf-8.
+/// This is useful for capturing binary output.
+/// Like [`StdoutUntrimmed`], this suppresses relaying the child's `stdout`
+/// to the parent's `stdout`.
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let StdoutBytes(output) = run_output!(%"echo foo");
+/// assert_eq!(output, b"foo\n");
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub struct StdoutBytes(pub Vec<u8>);
+
+impl Output for StdoutBytes {
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        config.capture_stdout = true;
+    }
+
+    #[doc(hidden)]
+    fn from_child_output(config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        let stdout = child_output
+            .stdout
+            .clone()
+            .ok_or_else(|| Error::internal("stdout not captured", config))?;
+        Ok(StdoutBytes(stdout))
+    }
+}
+
+/// Like [`StdoutUntrimmed`], but invalid utf-8 doesn't result in an
+/// [`Error::InvalidUtf8ToStdout`].
+/// Instead, invalid sequences are replaced with `U+FFFD REPLACEMENT CHARACTER`,
+/// like [`String::from_utf8_lossy`] does:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// # #[cfg(unix)]
+/// # {
+/// let StdoutLossy(output) = run_output!("printf", "foo\\377");
+/// assert_eq!(output, "foo\u{FFFD}");
+/// # }
+/// ```
+///
+/// This is useful e.g. for logging output of programs that
+/// mostly, but not always, write utf-8.
+/// Like [`StdoutUntrimmed`], this suppresses relaying the child's `stdout`
+/// to the parent's `stdout`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct StdoutLossy(pub String);
+
+impl Output for StdoutLossy {
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        StdoutBytes::configure(config);
+    }
+
+    #[doc(hidden)]
+    fn from_child_output(config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        let StdoutBytes(stdout) = StdoutBytes::from_child_output(config, child_output)?;
+        Ok(StdoutLossy(String::from_utf8_lossy(&stdout).into_owned()))
+    }
+}
+
 /// [`Stderr`] allows to capture the `stderr` of a child process:
 ///
 /// ```
@@ -250,6 +694,114 @@ impl Output for Stderr {
     }
 }
 
+/// Same as [`Stderr`], but returns the raw bytes that the child process
+/// writes to `stderr`, without interpreting them as utf-8:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let (StderrBytes(stderr), Status(_)) = run_output!(%"ls does-not-exist");
+/// assert!(!stderr.is_empty());
+/// ```
+///
+/// Like [`Stderr`], this suppresses relaying the child's `stderr`
+/// to the parent's `stderr`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct StderrBytes(pub Vec<u8>);
+
+impl Output for StderrBytes {
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        config.capture_stderr = true;
+    }
+
+    #[doc(hidden)]
+    fn from_child_output(config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        let stderr = child_output
+            .stderr
+            .clone()
+            .ok_or_else(|| Error::internal("stderr not captured", config))?;
+        Ok(StderrBytes(stderr))
+    }
+}
+
+/// Like [`Stderr`], but invalid utf-8 doesn't result in an
+/// [`Error::InvalidUtf8ToStderr`].
+/// Instead, invalid sequences are replaced with `U+FFFD REPLACEMENT CHARACTER`,
+/// like [`String::from_utf8_lossy`] does:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// # #[cfg(unix)]
+/// # {
+/// let StderrLossy(stderr) = run_output!(%"sh -c", "printf 'foo\\377' >&2");
+/// assert_eq!(stderr, "foo\u{FFFD}");
+/// # }
+/// ```
+///
+/// Like [`Stderr`], this suppresses relaying the child's `stderr`
+/// to the parent's `stderr`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct StderrLossy(pub String);
+
+impl Output for StderrLossy {
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        StderrBytes::configure(config);
+    }
+
+    #[doc(hidden)]
+    fn from_child_output(config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        let StderrBytes(stderr) = StderrBytes::from_child_output(config, child_output)?;
+        Ok(StderrLossy(String::from_utf8_lossy(&stderr).into_owned()))
+    }
+}
+
+/// [`CombinedOutput`] captures both `stdout` and `stderr` of the child process
+/// into a single [`String`],
+/// in the order in which the child process wrote them:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let (CombinedOutput(output), Status(_)) = run_output!(%"ls does-not-exist");
+/// assert!(output.contains("No such file or directory"));
+/// ```
+///
+/// This is similar to what you would see in a terminal.
+/// Note however that the order is only preserved for writes that
+/// the child process flushes in between.
+///
+/// This assumes that the output written to `stdout` and `stderr` is encoded
+/// as utf-8, and will error otherwise.
+///
+/// When using [`CombinedOutput`],
+/// neither the child's `stdout` nor its `stderr` are relayed to the parent.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CombinedOutput(pub String);
+
+impl Output for CombinedOutput {
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        config.capture_combined_output = true;
+    }
+
+    #[doc(hidden)]
+    fn from_child_output(config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        let combined = child_output
+            .combined
+            .clone()
+            .ok_or_else(|| Error::internal("combined output not captured", config))?;
+        Ok(CombinedOutput(String::from_utf8(combined).map_err(
+            |source| Error::InvalidUtf8ToStdoutOrStderr {
+                full_command: config.full_command(),
+                source,
+            },
+        )?))
+    }
+}
+
 /// Use [`Status`] as the return type for [`run_output!`] to retrieve the
 /// [`ExitStatus`] of the child process:
 ///
@@ -293,6 +845,96 @@ impl Output for Status {
     }
 }
 
+/// Returns the signal that terminated the child process,
+/// or `None` if it exited normally:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let Signal(signal) = run_output!(%"sh -c", "kill -TERM $$");
+/// assert_eq!(signal, Some(15));
+///
+/// let Signal(signal) = run_output!("false");
+/// assert_eq!(signal, None);
+/// ```
+///
+/// Like with [`Status`], neither non-zero exit codes nor signals
+/// result in a panic or a [`cradle::Error`](crate::Error).
+///
+/// [`Signal`] is only available on unix.
+#[cfg(unix)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Signal(pub Option<i32>);
+
+#[cfg(unix)]
+impl Output for Signal {
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        Status::configure(config);
+    }
+
+    #[doc(hidden)]
+    fn from_child_output(config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        use std::os::unix::process::ExitStatusExt;
+        let Status(exit_status) = Status::from_child_output(config, child_output)?;
+        Ok(Signal(exit_status.signal()))
+    }
+}
+
+/// [`CapturedOutput`] captures everything at once:
+/// the raw bytes written to `stdout` and `stderr`, and the [`ExitStatus`]:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let CapturedOutput {
+///     stdout,
+///     stderr,
+///     status,
+/// } = run_output!(%"sh -c", "echo foo; echo bar >&2; exit 42");
+/// assert_eq!(stdout, b"foo\n");
+/// assert_eq!(stderr, b"bar\n");
+/// assert_eq!(status.code(), Some(42));
+/// ```
+///
+/// Like with [`Status`], non-zero exit codes will not result in a panic
+/// or a [`cradle::Error`](crate::Error).
+/// Neither `stdout` nor `stderr` are relayed to the parent process.
+///
+/// If you need full access to the output of a child process,
+/// [`CapturedOutput`] is recommended over writing a
+/// [custom `Output` impl](Output#custom-output-impls).
+#[derive(Debug, Clone)]
+pub struct CapturedOutput {
+    /// The bytes the child process wrote to `stdout`.
+    pub stdout: Vec<u8>,
+    /// The bytes the child process wrote to `stderr`.
+    pub stderr: Vec<u8>,
+    /// The exit status of the child process.
+    pub status: ExitStatus,
+}
+
+impl Output for CapturedOutput {
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        StdoutBytes::configure(config);
+        StderrBytes::configure(config);
+        Status::configure(config);
+    }
+
+    #[doc(hidden)]
+    fn from_child_output(config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        let StdoutBytes(stdout) = StdoutBytes::from_child_output(config, child_output)?;
+        let StderrBytes(stderr) = StderrBytes::from_child_output(config, child_output)?;
+        let Status(status) = Status::from_child_output(config, child_output)?;
+        Ok(CapturedOutput {
+            stdout,
+            stderr,
+            status,
+        })
+    }
+}
+
 /// Using [`bool`] as the return type for [`run_output!`] will return `true` if
 /// the command returned successfully, and `false` otherwise:
 ///
@@ -331,3 +973,374 @@ impl Output for bool {
         Ok(child_output.exit_status.success())
     }
 }
+
+/// Using [`i32`] as the return type for [`run_output!`] will return the
+/// exit code of the child process:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let exit_code: i32 = run_output!("false");
+/// assert_eq!(exit_code, 1);
+/// ```
+///
+/// Like with [`Status`] and [`bool`], non-zero exit codes will not result in a panic
+/// or [`std::result::Result::Err`].
+///
+/// If the child process didn't exit with an exit code,
+/// because it was terminated by a signal,
+/// the negated signal number is returned on unix.
+/// For example a process that is killed with `SIGKILL` (signal number 9)
+/// results in `-9`.
+/// On other platforms `-1` is returned in that case.
+/// If you need to distinguish these cases reliably, use [`Status`] instead.
+///
+/// Also see the
+/// [section about error handling](index.html#error-handling) in
+/// the module documentation.
+impl Output for i32 {
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        config.error_on_non_zero_exit_code = false;
+    }
+
+    #[doc(hidden)]
+    fn from_child_output(_config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        Ok(exit_code(child_output.exit_status))
+    }
+}
+
+/// Using [`Result<String, String>`](std::result::Result) as the return type
+/// will capture both `stdout` and `stderr`,
+/// and return `Ok(stdout)` if the command succeeded, and `Err(stderr)` otherwise:
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let output: Result<String, String> = run_output!(%"echo foo");
+/// assert_eq!(output, Ok("foo\n".to_string()));
+/// let output: Result<String, String> = run_output!(%"sh -c", "echo bar >&2; false");
+/// assert_eq!(output, Err("bar\n".to_string()));
+/// ```
+///
+/// Like with [`bool`], non-zero exit codes will not result in a panic
+/// or a [`cradle::Error`](crate::Error).
+/// Both streams are captured untrimmed and are not relayed to the parent process.
+impl Output for Result<String, String> {
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        StdoutUntrimmed::configure(config);
+        Stderr::configure(config);
+        config.error_on_non_zero_exit_code = false;
+    }
+
+    #[doc(hidden)]
+    fn from_child_output(config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        Ok(if child_output.exit_status.success() {
+            let StdoutUntrimmed(stdout) = StdoutUntrimmed::from_child_output(config, child_output)?;
+            Ok(stdout)
+        } else {
+            let Stderr(stderr) = Stderr::from_child_output(config, child_output)?;
+            Err(stderr)
+        })
+    }
+}
+
+fn exit_code(exit_status: ExitStatus) -> i32 {
+    match exit_status.code() {
+        Some(code) => code,
+        None => {
+            #[cfg(unix)]
+            {
+                use std::os::unix::process::ExitStatusExt;
+                exit_status.signal().map_or(-1, |signal| -signal)
+            }
+            #[cfg(not(unix))]
+            {
+                -1
+            }
+        }
+    }
+}
+
+/// [`Timed`] wraps another [`Output`] type
+/// and additionally returns how long the child process ran:
+///
+/// ```
+/// use cradle::prelude::*;
+/// use std::time::Duration;
+///
+/// # #[cfg(unix)]
+/// # {
+/// let Timed(StdoutTrimmed(output), duration) = run_output!(%"sh -c", "sleep 0.1; echo foo");
+/// assert_eq!(output, "foo");
+/// assert!(duration >= Duration::from_millis(100));
+/// # }
+/// ```
+///
+/// The measured duration starts right before the child process is spawned
+/// and ends after the child process has exited
+/// and all of its output streams have been fully read.
+#[derive(Debug, Clone)]
+pub struct Timed<T: Output>(pub T, pub Duration);
+
+impl<T> Output for Timed<T>
+where
+    T: Output,
+{
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        T::configure(config);
+    }
+
+    #[doc(hidden)]
+    fn from_child_output(config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        Ok(Timed(
+            T::from_child_output(config, child_output)?,
+            child_output.duration,
+        ))
+    }
+}
+
+/// Returns the writer that was passed in with the [`StdoutTo`] input,
+/// after the child process wrote its `stdout` into it.
+/// See [`StdoutTo`] for an example.
+impl<W> Output for StdoutTo<W>
+where
+    W: Write + Send + 'static,
+{
+    #[doc(hidden)]
+    fn configure(_config: &mut Config) {}
+
+    #[doc(hidden)]
+    fn from_child_output(config: &Config, _child_output: &ChildOutput) -> Result<Self, Error> {
+        let writer = config
+            .options
+            .stdout_to
+            .clone()
+            .and_then(|slot| slot.downcast::<Mutex<Option<W>>>().ok())
+            .and_then(|slot| WriterSlot(slot).take());
+        match writer {
+            Some(writer) => Ok(StdoutTo(writer)),
+            None => Err(Error::StdoutToNotGiven {
+                full_command: config.full_command(),
+            }),
+        }
+    }
+}
+
+/// Captures what the child process writes to `stdout`
+/// and deserializes it as [TOML](https://toml.io).
+/// Like [`StdoutUntrimmed`], this suppresses relaying the child's `stdout`
+/// to the parent's `stdout`.
+///
+/// ```
+/// # #[cfg(feature = "toml")]
+/// # {
+/// use cradle::prelude::*;
+/// use std::collections::BTreeMap;
+///
+/// let Toml(config): Toml<BTreeMap<String, String>> = run_output!("echo", "foo = 'bar'");
+/// assert_eq!(config["foo"], "bar");
+/// # }
+/// ```
+///
+/// If the output is not valid utf-8, an [`Error::InvalidUtf8ToStdout`] is raised.
+/// If it cannot be deserialized, an [`Error::Deserialization`] is raised.
+///
+/// This is only available with the `toml` feature enabled.
+#[cfg(feature = "toml")]
+#[derive(Debug, Clone)]
+pub struct Toml<T: serde::de::DeserializeOwned>(pub T);
+
+#[cfg(feature = "toml")]
+impl<T> Output for Toml<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        StdoutUntrimmed::configure(config);
+    }
+
+    #[doc(hidden)]
+    fn from_child_output(config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        let StdoutUntrimmed(stdout) = StdoutUntrimmed::from_child_output(config, child_output)?;
+        let value = toml_crate::from_str(&stdout).map_err(|source| Error::Deserialization {
+            full_command: config.full_command(),
+            source: Box::new(source),
+        })?;
+        Ok(Toml(value))
+    }
+}
+
+/// Captures what the child process writes to `stdout`
+/// and deserializes it as [JSON](https://www.json.org).
+/// Like [`StdoutUntrimmed`], this suppresses relaying the child's `stdout`
+/// to the parent's `stdout`.
+///
+/// ```
+/// # #[cfg(feature = "serde_json")]
+/// # {
+/// use cradle::prelude::*;
+/// use std::collections::BTreeMap;
+///
+/// let Json(object): Json<BTreeMap<String, i64>> = run_output!("echo", r#"{"foo": 42}"#);
+/// assert_eq!(object["foo"], 42);
+/// # }
+/// ```
+///
+/// If the output is not valid utf-8, an [`Error::InvalidUtf8ToStdout`] is raised.
+/// If it cannot be deserialized, an [`Error::Deserialization`] is raised.
+/// See `JsonValue` for capturing arbitrary JSON without defining a type for it.
+///
+/// This is only available with the `serde_json` feature enabled.
+#[cfg(feature = "serde_json")]
+#[derive(Debug, Clone)]
+pub struct Json<T: serde::de::DeserializeOwned>(pub T);
+
+#[cfg(feature = "serde_json")]
+impl<T> Output for Json<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        StdoutUntrimmed::configure(config);
+    }
+
+    #[doc(hidden)]
+    fn from_child_output(config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        let StdoutUntrimmed(stdout) = StdoutUntrimmed::from_child_output(config, child_output)?;
+        let value =
+            serde_json_crate::from_str(&stdout).map_err(|source| Error::Deserialization {
+                full_command: config.full_command(),
+                source: Box::new(source),
+            })?;
+        Ok(Json(value))
+    }
+}
+
+/// Like [`Json`], but captures arbitrary JSON as a
+/// [`serde_json::Value`](https://docs.rs/serde_json/1/serde_json/enum.Value.html),
+/// without the need to define a type for it:
+///
+/// ```
+/// # #[cfg(feature = "serde_json")]
+/// # {
+/// use cradle::prelude::*;
+///
+/// let JsonValue(value) = run_output!("echo", r#"{"foo": [1, 2]}"#);
+/// assert_eq!(value["foo"][1], 2);
+/// # }
+/// ```
+///
+/// This is only available with the `serde_json` feature enabled.
+#[cfg(feature = "serde_json")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonValue(pub serde_json_crate::Value);
+
+#[cfg(feature = "serde_json")]
+impl Output for JsonValue {
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        Json::<serde_json_crate::Value>::configure(config);
+    }
+
+    #[doc(hidden)]
+    fn from_child_output(config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        let Json(value) = Json::from_child_output(config, child_output)?;
+        Ok(JsonValue(value))
+    }
+}
+
+/// Returns what the child process writes to `stdout` as [`Bytes`](bytes::Bytes).
+/// This is the same as [`StdoutBytes`], but avoids converting the output
+/// for code that uses the [`bytes`](https://docs.rs/bytes) crate:
+///
+/// ```
+/// # #[cfg(feature = "bytes")]
+/// # {
+/// use cradle::prelude::*;
+///
+/// let StdoutBytesBuf(output) = run_output!(%"echo foo");
+/// assert_eq!(output, bytes::Bytes::from_static(b"foo\n"));
+/// # }
+/// ```
+///
+/// Like [`StdoutBytes`], this suppresses relaying the child's `stdout`
+/// to the parent's `stdout`.
+///
+/// This is only available with the `bytes` feature enabled.
+#[cfg(feature = "bytes")]
+#[derive(Debug, PartialEq, Clone)]
+pub struct StdoutBytesBuf(pub bytes::Bytes);
+
+#[cfg(feature = "bytes")]
+impl Output for StdoutBytesBuf {
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        StdoutBytes::configure(config);
+    }
+
+    #[doc(hidden)]
+    fn from_child_output(config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        let StdoutBytes(stdout) = StdoutBytes::from_child_output(config, child_output)?;
+        Ok(StdoutBytesBuf(bytes::Bytes::from(stdout)))
+    }
+}
+
+/// Returns what the child process writes to `stdout`,
+/// decoded with the encoding configured by [`StdoutEncoding`](crate::StdoutEncoding).
+/// This is useful for tools that don't write utf-8, e.g. legacy windows tools:
+///
+/// ```
+/// # #[cfg(feature = "encoding")]
+/// # {
+/// use cradle::prelude::*;
+///
+/// let StdoutEncoded { stdout, encoding } = run_output!(
+///     %"printf caf\\351",
+///     StdoutEncoding(encoding_rs::WINDOWS_1252)
+/// );
+/// assert_eq!(stdout, "café");
+/// assert_eq!(encoding, encoding_rs::WINDOWS_1252);
+/// # }
+/// ```
+///
+/// Without [`StdoutEncoding`](crate::StdoutEncoding), `stdout` is decoded as utf-8.
+/// A byte order mark at the start of the output is removed.
+/// Unlike with [`StdoutUntrimmed`], malformed sequences don't result in errors,
+/// but are replaced with `U+FFFD REPLACEMENT CHARACTER`.
+///
+/// Like [`StdoutUntrimmed`], this suppresses relaying the child's `stdout`
+/// to the parent's `stdout`.
+///
+/// This is only available with the `encoding` feature enabled.
+#[cfg(feature = "encoding")]
+#[derive(Debug, PartialEq, Clone)]
+pub struct StdoutEncoded {
+    /// The decoded output of the child process.
+    pub stdout: String,
+    /// The encoding that was used to decode the output.
+    pub encoding: &'static encoding_rs::Encoding,
+}
+
+#[cfg(feature = "encoding")]
+impl Output for StdoutEncoded {
+    #[doc(hidden)]
+    fn configure(config: &mut Config) {
+        StdoutBytes::configure(config);
+    }
+
+    #[doc(hidden)]
+    fn from_child_output(config: &Config, child_output: &ChildOutput) -> Result<Self, Error> {
+        let StdoutBytes(stdout) = StdoutBytes::from_child_output(config, child_output)?;
+        let encoding = config.options.stdout_encoding.unwrap_or(encoding_rs::UTF_8);
+        let (decoded, _) = encoding.decode_with_bom_removal(&stdout);
+        Ok(StdoutEncoded {
+            stdout: decoded.into_owned(),
+            encoding,
+        })
+    }
+}
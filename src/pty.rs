@@ -0,0 +1,86 @@
+//! An internal module for connecting child processes to pseudo-terminals,
+//! see [`Pty`](crate::Pty).
+
+use std::{
+    fs::File,
+    io::{self, Read},
+    os::unix::io::FromRawFd,
+    ptr,
+};
+
+/// A pseudo-terminal.
+/// The child process writes to the `slave` side,
+/// the parent process reads the output from the `master` side.
+pub(crate) struct PseudoTerminal {
+    pub(crate) master: MasterReader,
+    pub(crate) slave: File,
+}
+
+impl PseudoTerminal {
+    pub(crate) fn open() -> io::Result<Self> {
+        let mut master: libc::c_int = -1;
+        let mut slave: libc::c_int = -1;
+        let result = unsafe {
+            libc::openpty(
+                &mut master,
+                &mut slave,
+                ptr::null_mut(),
+                ptr::null(),
+                ptr::null(),
+            )
+        };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let master = unsafe { File::from_raw_fd(master) };
+        let slave = unsafe { File::from_raw_fd(slave) };
+        set_close_on_exec(&master)?;
+        set_close_on_exec(&slave)?;
+        disable_output_processing(&slave)?;
+        Ok(PseudoTerminal {
+            master: MasterReader(master),
+            slave,
+        })
+    }
+}
+
+/// `openpty` doesn't set `FD_CLOEXEC`, so without this, all child processes
+/// (including the one connected to the [`PseudoTerminal`]) would inherit both sides.
+/// Reading from the master side would then block until all of them exited.
+fn set_close_on_exec(file: &File) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+    if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Terminals translate `\n` into `\r\n` by default.
+/// This is disabled, so that output doesn't change when it's written to a [`PseudoTerminal`].
+fn disable_output_processing(slave: &File) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+    let mut termios = unsafe { std::mem::zeroed::<libc::termios>() };
+    if unsafe { libc::tcgetattr(slave.as_raw_fd(), &mut termios) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    termios.c_oflag &= !libc::ONLCR;
+    if unsafe { libc::tcsetattr(slave.as_raw_fd(), libc::TCSANOW, &termios) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Reads from the master side of a [`PseudoTerminal`].
+/// On linux, reading from the master side fails with `EIO`
+/// once all file descriptors for the slave side are closed.
+/// That's treated as the end of the output.
+pub(crate) struct MasterReader(File);
+
+impl Read for MasterReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.read(buf) {
+            Err(error) if error.raw_os_error() == Some(libc::EIO) => Ok(0),
+            result => result,
+        }
+    }
+}
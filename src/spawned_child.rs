@@ -0,0 +1,278 @@
+//! The [`SpawnedChild`] type, for child processes that run in the background,
+//! and [`StdoutLines`], for processing their `stdout` lazily.
+
+use crate::{
+    child_output::{ChildOutput, RunningStage},
+    config::Config,
+    context::Context,
+    error::Error,
+};
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    process::{ChildStdout, ExitStatus},
+    thread,
+};
+
+/// A handle to a child process that is running in the background.
+/// [`SpawnedChild`]s are created with [`Input::spawn`](crate::Input::spawn):
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// let mut child = ("sleep", "10").spawn().unwrap();
+/// child.kill().unwrap();
+/// let status = child.wait().unwrap();
+/// assert!(!status.success());
+/// ```
+///
+/// The output of the child process is relayed to the parent's `stdout` and `stderr`
+/// in the background. Inputs like [`Stdin`](crate::Stdin) work as usual.
+///
+/// By default, dropping a [`SpawnedChild`] doesn't stop the child process,
+/// like with [`std::process::Child`].
+/// Use [`KillOnDrop`](crate::KillOnDrop) or [`SpawnedChild::kill_on_drop`] to change that.
+#[derive(Debug)]
+pub struct SpawnedChild {
+    config: Config,
+    stages: Vec<RunningStage>,
+    kill_on_drop: bool,
+}
+
+impl SpawnedChild {
+    pub(crate) fn spawn<Stdout, Stderr>(
+        context: Context<Stdout, Stderr>,
+        config: Config,
+    ) -> Result<Self, Error>
+    where
+        Stdout: Write + Clone + Send + 'static,
+        Stderr: Write + Clone + Send + 'static,
+    {
+        let (spawned_child, _) = Self::spawn_with_stdout(context, config)?;
+        Ok(spawned_child)
+    }
+
+    fn spawn_with_stdout<Stdout, Stderr>(
+        context: Context<Stdout, Stderr>,
+        config: Config,
+    ) -> Result<(Self, Option<ChildStdout>), Error>
+    where
+        Stdout: Write + Clone + Send + 'static,
+        Stderr: Write + Clone + Send + 'static,
+    {
+        let (_, stages, stdout) = ChildOutput::spawn_pipeline(context, &config)?;
+        let kill_on_drop = config.options.kill_on_drop;
+        Ok((
+            SpawnedChild {
+                config,
+                stages,
+                kill_on_drop,
+            },
+            stdout,
+        ))
+    }
+
+    pub(crate) fn spawn_stdout<Stdout, Stderr>(
+        context: Context<Stdout, Stderr>,
+        mut config: Config,
+    ) -> Result<ChildStdout, Error>
+    where
+        Stdout: Write + Clone + Send + 'static,
+        Stderr: Write + Clone + Send + 'static,
+    {
+        config.options.stream_stdout = true;
+        let (child, stdout) = Self::spawn_with_stdout(context, config)?;
+        let stdout = match stdout {
+            Some(stdout) => stdout,
+            None => return Err(Error::internal("stdout not available", &child.config)),
+        };
+        // Reaps the child process once it exits, so that it doesn't become a zombie.
+        let child = child.kill_on_drop(false);
+        thread::spawn(move || child.wait());
+        Ok(stdout)
+    }
+
+    /// Returns the process id of the child process.
+    /// For a [`Pipe`](crate::Pipe), this is the process id of the last command.
+    pub fn id(&self) -> u32 {
+        let (child, _) = self
+            .stages
+            .last()
+            .expect("spawned child should have pipeline stages");
+        child.id()
+    }
+
+    /// Configures whether the child process is killed
+    /// when the [`SpawnedChild`] is dropped without being waited for:
+    ///
+    /// ```
+    /// use cradle::prelude::*;
+    ///
+    /// let child = ("sleep", "10").spawn().unwrap().kill_on_drop(true);
+    /// // kills `sleep`
+    /// drop(child);
+    /// ```
+    ///
+    /// This can also be configured with the [`KillOnDrop`](crate::KillOnDrop) input.
+    pub fn kill_on_drop(mut self, kill_on_drop: bool) -> Self {
+        self.kill_on_drop = kill_on_drop;
+        self
+    }
+
+    /// Kills the child process.
+    /// For a [`Pipe`](crate::Pipe), all commands are killed.
+    /// Use [`SpawnedChild::wait`] afterwards to wait for the child process to exit.
+    pub fn kill(&mut self) -> Result<(), Error> {
+        let config = &self.config;
+        for (child, _) in self.stages.iter_mut() {
+            child
+                .kill()
+                .map_err(|error| Error::command_io_error(config, error))?;
+        }
+        Ok(())
+    }
+
+    /// Waits for the child process to exit
+    /// and for its output to be fully relayed.
+    /// Returns the [`ExitStatus`] of the child process.
+    ///
+    /// Like with [`Status`](crate::Status),
+    /// non-zero exit codes don't result in errors.
+    /// For a [`Pipe`](crate::Pipe), the exit status of the last failing command is returned,
+    /// or the exit status of the last command if all commands succeeded.
+    /// If waiting fails for one of the commands, the remaining commands are killed.
+    pub fn wait(mut self) -> Result<ExitStatus, Error> {
+        let mut exit_statuses = Vec::new();
+        let mut stages = std::mem::take(&mut self.stages).into_iter();
+        while let Some((mut child, waiter)) = stages.next() {
+            let result = child
+                .wait()
+                .map_err(|error| Error::command_io_error(&self.config, error))
+                .and_then(|exit_status| {
+                    waiter.join(&self.config, None)?;
+                    Ok(exit_status)
+                });
+            match result {
+                Ok(exit_status) => exit_statuses.push(exit_status),
+                Err(error) => {
+                    // The handle is consumed, so the remaining stages
+                    // couldn't be stopped otherwise.
+                    for mut child in std::iter::once(child).chain(stages.map(|(child, _)| child)) {
+                        let _ = child.kill();
+                        let _ = child.wait();
+                    }
+                    return Err(error);
+                }
+            }
+        }
+        let last_failing = exit_statuses.iter().rev().find(|status| !status.success());
+        match last_failing.or_else(|| exit_statuses.last()) {
+            Some(exit_status) => Ok(*exit_status),
+            None => Err(Error::internal("no pipeline stages", &self.config)),
+        }
+    }
+}
+
+impl Drop for SpawnedChild {
+    fn drop(&mut self) {
+        if self.kill_on_drop {
+            for (child, _) in self.stages.iter_mut() {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+        }
+        // The relaying threads are detached instead of joined,
+        // since grandchildren may keep the output streams open indefinitely.
+        // They terminate on their own once the streams are closed.
+    }
+}
+
+/// An iterator over the lines that a child process writes to `stdout`,
+/// created with [`Input::stream_stdout_lines`](crate::Input::stream_stdout_lines):
+///
+/// ```
+/// use cradle::prelude::*;
+///
+/// # fn test() -> Result<(), Error> {
+/// let lines = ("yes", "foo").stream_stdout_lines()?;
+/// for line in lines.take(3) {
+///     assert_eq!(line.unwrap(), "foo");
+/// }
+/// // `yes` is killed here
+/// # Ok(())
+/// # }
+/// # test().unwrap();
+/// ```
+///
+/// Lines are yielded while the child process is running,
+/// without line endings (`\n` or `\r\n`).
+/// Lines that are not valid utf-8 result in an [`io::Error`]
+/// of kind [`InvalidData`](io::ErrorKind::InvalidData).
+///
+/// When the [`StdoutLines`] are dropped, the child process is killed,
+/// so you can stop reading early.
+/// Use [`StdoutLines::wait`] to wait for the child process to exit instead.
+#[derive(Debug)]
+pub struct StdoutLines {
+    stdout: BufReader<ChildStdout>,
+    child: SpawnedChild,
+}
+
+impl StdoutLines {
+    pub(crate) fn spawn<Stdout, Stderr>(
+        context: Context<Stdout, Stderr>,
+        mut config: Config,
+    ) -> Result<Self, Error>
+    where
+        Stdout: Write + Clone + Send + 'static,
+        Stderr: Write + Clone + Send + 'static,
+    {
+        config.options.stream_stdout = true;
+        let (child, stdout) = SpawnedChild::spawn_with_stdout(context, config)?;
+        let stdout = match stdout {
+            Some(stdout) => stdout,
+            None => return Err(Error::internal("stdout not available", &child.config)),
+        };
+        Ok(StdoutLines {
+            stdout: BufReader::new(stdout),
+            child: child.kill_on_drop(true),
+        })
+    }
+
+    /// Returns the process id of the child process.
+    pub fn id(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// Waits for the child process to exit and returns its [`ExitStatus`],
+    /// like [`SpawnedChild::wait`].
+    /// Lines that haven't been read yet are discarded.
+    pub fn wait(self) -> Result<ExitStatus, Error> {
+        let StdoutLines { mut stdout, child } = self;
+        io::copy(&mut stdout, &mut io::sink())
+            .map_err(|error| Error::command_io_error(&child.config, error))?;
+        child.kill_on_drop(false).wait()
+    }
+}
+
+impl Iterator for StdoutLines {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = Vec::new();
+        match self.stdout.read_until(b'\n', &mut line) {
+            Ok(0) => return None,
+            Ok(_) => {}
+            Err(error) => return Some(Err(error)),
+        }
+        if line.last() == Some(&b'\n') {
+            line.pop();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+        }
+        Some(
+            String::from_utf8(line)
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error)),
+        )
+    }
+}
@@ -38,6 +38,30 @@ fn main() {
                 sleep(Duration::from_secs_f32(0.1));
             }
         }
+        "write to stdout and stderr interleaved" => {
+            for stream in &["stdout", "stderr", "stdout"] {
+                if *stream == "stdout" {
+                    println!("to stdout");
+                    io::stdout().flush().unwrap();
+                } else {
+                    eprintln!("to stderr");
+                }
+                sleep(Duration::from_secs_f32(0.1));
+            }
+        }
+        "succeed on attempt" => {
+            let succeeding_attempt: u32 = args.next().unwrap().parse().unwrap();
+            let file = PathBuf::from("./attempts");
+            let attempt = match std::fs::read_to_string(&file) {
+                Ok(attempts) => attempts.parse::<u32>().unwrap() + 1,
+                Err(_) => 1,
+            };
+            std::fs::write(&file, attempt.to_string()).unwrap();
+            println!("attempt {}", attempt);
+            if attempt < succeeding_attempt {
+                std::process::exit(1);
+            }
+        }
         "reverse" => {
             let mut input = Vec::new();
             io::stdin().read_to_end(&mut input).unwrap();
@@ -57,6 +81,24 @@ fn main() {
                 }
             }
         }
+        "print environment" => {
+            for (key, value) in std::env::vars() {
+                println!("{}={}", key, value);
+            }
+        }
+        "print process group" => {
+            #[cfg(unix)]
+            {
+                use nix::unistd::{getpgrp, getpid};
+                println!("pid: {}, process group: {}", getpid(), getpgrp());
+            }
+            #[cfg(windows)]
+            panic!("print process group is not supported on windows")
+        }
+        "allocate 512 mib" => {
+            let bytes = vec![1_u8; 512 * 1024 * 1024];
+            println!("allocated {} bytes", bytes.len());
+        }
         arg => panic!("cradle_test_helper: invalid arg: {}", arg),
     }
 }
@@ -35,7 +35,7 @@ fn result_succeeding() {
 
     fn test() -> Result<(), Error> {
         // make sure 'ls' is installed
-        run_result!(WHICH, "ls")?;
+        let () = run_result!(WHICH, "ls")?;
         Ok(())
     }
 
@@ -47,7 +47,7 @@ fn result_failing() {
     use cradle::prelude::*;
 
     fn test() -> Result<(), Error> {
-        run_result!(WHICH, "does-not-exist")?;
+        let () = run_result!(WHICH, "does-not-exist")?;
         Ok(())
     }
 
@@ -101,7 +101,7 @@ fn box_dyn_errors_succeeding() {
     type MyResult<T> = Result<T, Box<dyn std::error::Error>>;
 
     fn test() -> MyResult<()> {
-        run_result!(WHICH, "ls")?;
+        let () = run_result!(WHICH, "ls")?;
         Ok(())
     }
 
@@ -115,7 +115,7 @@ fn box_dyn_errors_failing() {
     type MyResult<T> = Result<T, Box<dyn std::error::Error>>;
 
     fn test() -> MyResult<()> {
-        run_result!(WHICH, "does-not-exist")?;
+        let () = run_result!(WHICH, "does-not-exist")?;
         Ok(())
     }
 
@@ -134,6 +134,7 @@ fn user_supplied_errors_succeeding() {
     use cradle::prelude::*;
 
     #[derive(Debug)]
+    #[allow(dead_code)]
     enum Error {
         Cradle(cradle::Error),
     }
@@ -145,7 +146,7 @@ fn user_supplied_errors_succeeding() {
     }
 
     fn test() -> Result<(), Error> {
-        run_result!(WHICH, "ls")?;
+        let () = run_result!(WHICH, "ls")?;
         Ok(())
     }
 
@@ -177,7 +178,7 @@ fn user_supplied_errors_failing() {
     }
 
     fn test() -> Result<(), Error> {
-        run_result!(WHICH, "does-not-exist")?;
+        let () = run_result!(WHICH, "does-not-exist")?;
         Ok(())
     }
 
@@ -200,7 +201,7 @@ mod run_interface {
 
         fn test() -> Result<(), Error> {
             // make sure 'ls' is installed
-            (WHICH, "ls").run_result()?;
+            let () = (WHICH, "ls").run_result()?;
             Ok(())
         }
 
@@ -212,7 +213,7 @@ mod run_interface {
         use cradle::prelude::*;
 
         fn test() -> Result<(), Error> {
-            (WHICH, "does-not-exist").run_result()?;
+            let () = (WHICH, "does-not-exist").run_result()?;
             Ok(())
         }
 
@@ -233,7 +234,7 @@ mod run_interface {
         type MyResult<T> = Result<T, Box<dyn std::error::Error>>;
 
         fn test() -> MyResult<()> {
-            (WHICH, "ls").run_result()?;
+            let () = (WHICH, "ls").run_result()?;
             Ok(())
         }
 
@@ -247,7 +248,7 @@ mod run_interface {
         type MyResult<T> = Result<T, Box<dyn std::error::Error>>;
 
         fn test() -> MyResult<()> {
-            (WHICH, "does-not-exist").run_result()?;
+            let () = (WHICH, "does-not-exist").run_result()?;
             Ok(())
         }
 
@@ -266,6 +267,7 @@ mod run_interface {
         use cradle::prelude::*;
 
         #[derive(Debug)]
+        #[allow(dead_code)]
         enum Error {
             Cradle(cradle::Error),
         }
@@ -277,7 +279,7 @@ mod run_interface {
         }
 
         fn test() -> Result<(), Error> {
-            (WHICH, "ls").run_result()?;
+            let () = (WHICH, "ls").run_result()?;
             Ok(())
         }
 
@@ -309,7 +311,7 @@ mod run_interface {
         }
 
         fn test() -> Result<(), Error> {
-            (WHICH, "does-not-exist").run_result()?;
+            let () = (WHICH, "does-not-exist").run_result()?;
             Ok(())
         }
 